@@ -0,0 +1,39 @@
+#include "util.h"
+#include "beam_planner.h"
+
+#include "stdio.h"
+#include "stdlib.h"
+
+// `solve` backed by the Rust solver in `rust/`, so the harness can check and time it
+// the same way as `solution.c`. Built by `make test-rust`.
+void solve(const Vector3* users, const int num_users, const Vector3* sats, const int num_sats)
+{
+    bp_problem* problem = bp_problem_new();
+    // `Vector3` and `bp_vector3` are both three floats.
+    if (bp_add_users(problem, (const bp_vector3*)users, num_users) != BP_OK
+        || bp_add_sats(problem, (const bp_vector3*)sats, num_sats) != BP_OK
+        || bp_set_min_coverage(problem, 0.0) != BP_OK
+        || bp_solve(problem, 60.0) == BP_ERROR)
+    {
+        printf("Rust solver failed.\n");
+        exit(1);
+    }
+
+    const size_t n = bp_assignment_count(problem);
+    bp_assignment* assignments = (bp_assignment*)malloc(n * sizeof(bp_assignment));
+    bp_get_assignments(problem, assignments, n);
+
+    FILE* file = fopen("solution.txt", "w");
+    for (size_t i = 0; i < n; i++)
+    {
+        fprintf(
+            file,
+            "%u %u %c\n",
+            assignments[i].user,
+            assignments[i].sat,
+            'A' + assignments[i].color - 1);
+    }
+    fclose(file);
+    free(assignments);
+    bp_problem_free(problem);
+}
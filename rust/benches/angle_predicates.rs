@@ -0,0 +1,83 @@
+//! The interference check before and after `Vector3::within_angle`, on every pair of
+//! users that a satellite in tests 05 and 06 can see. Run with `cargo bench`.
+//!
+//! "acos" is the old predicate, `angle_between(..) < MINIMUM_BEAM_ANGLE`, which
+//! normalizes both sides and takes an `acos`. "cosine" is `within_angle` against the
+//! precomputed cosine of the same angle. Each line is the median of 5 passes over all
+//! pairs, with the number of pairs each predicate found interfering.
+use beam_planner::util::{MAX_ALLOWABLE_BEAM_ANGLE, MINIMUM_BEAM_ANGLE};
+use beam_planner::{Scenario, Vector3};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// The users each satellite can see, as the solvers find them.
+fn visible_users(scenario: &Scenario) -> Vec<(Vector3, Vec<Vector3>)> {
+    let cos_max_beam_angle = MAX_ALLOWABLE_BEAM_ANGLE.to_radians().cos();
+    scenario
+        .sats
+        .values()
+        .map(|&sat| {
+            let users = scenario
+                .users
+                .values()
+                .copied()
+                .filter(|&user| {
+                    Vector3::zero().within_angle(&user, &(sat - user), cos_max_beam_angle)
+                })
+                .collect();
+            (sat, users)
+        })
+        .collect()
+}
+
+// Median time of 5 passes of `interferes` over every pair, and how many pairs it held for.
+fn time(
+    sats: &[(Vector3, Vec<Vector3>)],
+    interferes: impl Fn(&Vector3, &Vector3, &Vector3) -> bool,
+) -> (Duration, usize) {
+    let mut count = 0;
+    let mut times: Vec<Duration> = (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            count = 0;
+            for (sat, users) in sats {
+                for (i, a) in users.iter().enumerate() {
+                    for b in &users[i + 1..] {
+                        count += usize::from(interferes(black_box(sat), a, b));
+                    }
+                }
+            }
+            black_box(count);
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    (times[2], count)
+}
+
+fn main() {
+    let cos_min_beam_angle = MINIMUM_BEAM_ANGLE.to_radians().cos();
+    for path in [
+        "../test/05_fifty_thousand_low_coverage.txt",
+        "../test/06_ten_thousand.txt",
+    ] {
+        let sats = visible_users(&Scenario::new(path).unwrap());
+        let pairs: usize = sats
+            .iter()
+            .map(|(_, users)| users.len() * users.len().saturating_sub(1) / 2)
+            .sum();
+        let (acos, acos_count) = time(&sats, |sat, a, b| {
+            sat.angle_between(a, b) < MINIMUM_BEAM_ANGLE
+        });
+        let (cosine, cosine_count) = time(&sats, |sat, a, b| {
+            sat.within_angle(a, b, cos_min_beam_angle)
+        });
+        println!("{} ({} pairs)", path, pairs);
+        println!("  acos    {:>10.2?}  {} interfering", acos, acos_count);
+        println!("  cosine  {:>10.2?}  {} interfering", cosine, cosine_count);
+        println!(
+            "  speedup {:>10.2}x",
+            acos.as_secs_f64() / cosine.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,118 @@
+#pragma once
+
+// The Rust solver as a C library. Build it with `cargo build --release` in `rust/` and
+// link against `target/release/libbeam_planner.so` (`.dylib` on macOS, `.dll` on
+// Windows).
+//
+// A problem is built up with users and satellites, which take ids 0, 1, 2... in the
+// order they are added, then solved and read back as an array of assignments:
+//
+//     bp_problem *problem = bp_problem_new();
+//     bp_add_users(problem, users, num_users);
+//     bp_add_sats(problem, sats, num_sats);
+//     bp_set_min_coverage(problem, 0.8);
+//     if (bp_solve(problem, 60.0) != BP_ERROR) {
+//         size_t n = bp_assignment_count(problem);
+//         bp_assignment *assignments = malloc(n * sizeof(bp_assignment));
+//         bp_get_assignments(problem, assignments, n);
+//         ...
+//     }
+//     bp_problem_free(problem);
+//
+// Limits:
+//
+// - Only the share of users to serve can be set, with bp_set_min_coverage. The beam
+//   rules are fixed: a satellite must be within BP_MAX_BEAM_ANGLE degrees of a user's
+//   vertical, serves at most BP_MAX_USERS_PER_SAT users on BP_COLORS colors, and two of
+//   its users on the same color must be at least BP_MIN_SEPARATION degrees apart as
+//   seen from it. There are no setters for these.
+// - The time budget passed to bp_solve doesn't limit how long it runs. The solver always
+//   runs to completion; going over the budget only turns the result into BP_MISSED.
+//
+// Positions are ECEF in km. Functions taking a problem accept NULL and return
+// BP_INVALID_ARGUMENT, or zero, instead of crashing. A problem must not be used from
+// two threads at once; the solver itself runs on its own threads.
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// Changes whenever a declaration below changes incompatibly.
+#define BP_ABI_VERSION 1
+
+// Return codes, as for the `solve` command's exit status.
+#define BP_OK               0 // Solved within the rules, coverage and budget.
+#define BP_MISSED           1 // Solved, but the plan breaks a rule, serves too few users
+                              // or took longer than the budget. It can still be read.
+#define BP_INVALID_ARGUMENT 2 // NULL problem or positions, or an out of range value.
+#define BP_ERROR            3 // The solver failed. The problem has no plan.
+
+// The fixed beam rules, for reference. Changing them here changes nothing.
+#define BP_MAX_BEAM_ANGLE    45
+#define BP_MIN_SEPARATION    10
+#define BP_MAX_USERS_PER_SAT 32
+#define BP_COLORS            4
+
+typedef struct bp_problem bp_problem;
+
+typedef struct
+{
+    float x;
+    float y;
+    float z;
+} bp_vector3;
+
+typedef struct
+{
+    uint32_t user;
+    uint32_t sat;
+    uint8_t color; // 1 to 4 for colors A to D.
+} bp_assignment;
+
+// The BP_ABI_VERSION the library was built with.
+uint32_t bp_abi_version(void);
+
+// Return a new, empty problem that must be freed with bp_problem_free.
+bp_problem *bp_problem_new(void);
+
+// Free a problem. NULL is ignored.
+void bp_problem_free(bp_problem *problem);
+
+// Add `count` users or satellites, numbered on from those already added. Adding drops
+// the last plan.
+int bp_add_users(bp_problem *problem, const bp_vector3 *positions, size_t count);
+int bp_add_sats(bp_problem *problem, const bp_vector3 *positions, size_t count);
+
+// Set the share of users bp_solve must serve, from 0 to 1. Defaults to 1. This is the
+// only constraint that can be set; see the fixed rules above.
+int bp_set_min_coverage(bp_problem *problem, double min_coverage);
+
+// Pick the solver by its command line name, such as "v/fewest" (the default) or "e".
+int bp_set_solver(bp_problem *problem, const char *name);
+
+// Set the seed for breaking ties and the number of solver threads, at most 1024; 0
+// threads picks one per core. Both default to 0.
+int bp_set_config(bp_problem *problem, uint64_t seed, size_t threads);
+
+// Solve the problem, replacing any earlier plan. The solver runs to completion and can't
+// be stopped, so `time_budget` (in seconds) is only checked afterwards. Any non-negative budget is
+// accepted; one too long to measure, or infinity, can't be missed, as on the command
+// line and in Python.
+int bp_solve(bp_problem *problem, double time_budget);
+
+// How long the last bp_solve took, in seconds.
+double bp_solve_seconds(const bp_problem *problem);
+
+// How many users the last bp_solve served.
+size_t bp_assignment_count(const bp_problem *problem);
+
+// Copy up to `capacity` assignments, in user id order, into `out` and return how many
+// were copied.
+size_t bp_get_assignments(const bp_problem *problem, bp_assignment *out, size_t capacity);
+
+#ifdef __cplusplus
+}
+#endif
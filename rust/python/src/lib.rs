@@ -0,0 +1,356 @@
+//! Python bindings for the solvers, verifier and diagnostics.
+//!
+//! Users and satellites are `(n, 3)` arrays of ECEF positions in km, and take their
+//! row numbers as ids. Assignments go back and forth as a dict of three equally long
+//! arrays, `user`, `sat` and `color` (1 to 4 for A to D), so `solve`'s result can be
+//! passed straight to `verify` and `explain`.
+//!
+//! Every function takes an optional constraints dict:
+//!
+//! - `min_coverage`: the share of users to serve, from 0 to 1. Defaults to 1.
+//! - `weights`: `{user: weight}` for weighted coverage, each finite and non-negative.
+//!   Unlisted users weigh 1.
+//! - `solver`: the command line name of the solver, such as `"v/fewest"` (the default).
+//! - `seed` and `threads`: as in `SolverConfig`.
+//! - `time_budget`: seconds `solve` may take before `in_time` and `passed` are false.
+//!   Defaults to 60. Budgets too long to measure, or infinity, can't be missed.
+use beam_planner::config::{self, Solver};
+use beam_planner::json::Json;
+use beam_planner::stats::Stats;
+use beam_planner::validate::{Severity, ValidationConfig};
+use beam_planner::{
+    Color, Sat, Scenario, Solution, SolverConfig, User, Vector3, Violation, TIMEOUT,
+};
+use numpy::{AllowTypeChange, PyArray1, PyArrayLike1, PyArrayLike2};
+use pyo3::exceptions::{PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyList, PyString};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+type Positions<'py> = PyArrayLike2<'py, f64, AllowTypeChange>;
+
+const CONSTRAINTS: [&str; 6] = [
+    "min_coverage",
+    "weights",
+    "solver",
+    "seed",
+    "threads",
+    "time_budget",
+];
+
+struct Constraints {
+    min_coverage: f32,
+    weights: BTreeMap<User, f32>,
+    solver: Solver,
+    config: SolverConfig,
+    time_budget: Duration,
+}
+
+impl Constraints {
+    fn new(constraints: Option<&Bound<PyDict>>) -> PyResult<Self> {
+        let mut parsed = Self {
+            min_coverage: 1.0,
+            weights: BTreeMap::new(),
+            solver: Solver::default(),
+            config: SolverConfig::default(),
+            time_budget: TIMEOUT,
+        };
+        let Some(constraints) = constraints else {
+            return Ok(parsed);
+        };
+        for (key, value) in constraints {
+            let key: String = key.extract()?;
+            match key.as_str() {
+                "min_coverage" => {
+                    parsed.min_coverage = value.extract()?;
+                    if !(0.0..=1.0).contains(&parsed.min_coverage) {
+                        return Err(PyValueError::new_err("min_coverage must be from 0 to 1"));
+                    }
+                }
+                "weights" => {
+                    let weights: BTreeMap<u64, f32> = value.extract()?;
+                    if let Some((user, weight)) = weights
+                        .iter()
+                        .find(|(_, weight)| !(weight.is_finite() && **weight >= 0.0))
+                    {
+                        return Err(PyValueError::new_err(format!(
+                            "weight {} for user {} is not a finite, non-negative number",
+                            weight, user
+                        )));
+                    }
+                    parsed.weights = weights.into_iter().map(|(u, w)| (User(u), w)).collect();
+                }
+                "solver" => {
+                    let name: String = value.extract()?;
+                    parsed.solver = name.parse().map_err(PyValueError::new_err)?;
+                }
+                "seed" => parsed.config.seed = value.extract()?,
+                "threads" => {
+                    parsed.config.threads = value.extract()?;
+                    parsed.config.validate().map_err(PyValueError::new_err)?;
+                }
+                "time_budget" => {
+                    let seconds: f64 = value.extract()?;
+                    parsed.time_budget =
+                        config::time_budget(seconds).map_err(PyValueError::new_err)?;
+                }
+                _ => {
+                    return Err(PyKeyError::new_err(format!(
+                        "unknown constraint {:?}, expected one of {:?}",
+                        key, CONSTRAINTS
+                    )))
+                }
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+fn positions<K: Ord>(
+    array: &Positions,
+    what: &str,
+    id: impl Fn(u64) -> K,
+) -> PyResult<BTreeMap<K, Vector3>> {
+    let array = array.as_array();
+    if array.ncols() != 3 {
+        return Err(PyValueError::new_err(format!(
+            "{} must have shape (n, 3), not {:?}",
+            what,
+            array.shape()
+        )));
+    }
+    array
+        .rows()
+        .into_iter()
+        .enumerate()
+        .map(|(row, v)| {
+            // Checked after narrowing, since values past `f32::MAX` become infinite.
+            let v = [v[0] as f32, v[1] as f32, v[2] as f32];
+            if !v.iter().all(|x| x.is_finite()) {
+                return Err(PyValueError::new_err(format!(
+                    "{} row {} is not finite as a 32-bit float",
+                    what, row
+                )));
+            }
+            Ok((id(row as u64), Vector3::new(v[0], v[1], v[2])))
+        })
+        .collect()
+}
+
+fn scenario(users: &Positions, sats: &Positions, constraints: &Constraints) -> PyResult<Scenario> {
+    Ok(Scenario {
+        users: positions(users, "users", User)?,
+        sats: positions(sats, "sats", Sat)?,
+        min_coverage: constraints.min_coverage,
+        weights: constraints.weights.clone(),
+        ..Scenario::default()
+    })
+}
+
+fn column<'py>(
+    assignments: &Bound<'py, PyAny>,
+    key: &str,
+) -> PyResult<PyArrayLike1<'py, u64, AllowTypeChange>> {
+    assignments.get_item(key)?.extract()
+}
+
+/// The `{user, sat, color}` arrays as a solution.
+fn solution(assignments: &Bound<PyAny>) -> PyResult<Solution> {
+    let (users, sats, colors) = (
+        column(assignments, "user")?,
+        column(assignments, "sat")?,
+        column(assignments, "color")?,
+    );
+    let (users, sats, colors) = (users.as_array(), sats.as_array(), colors.as_array());
+    if users.len() != sats.len() || users.len() != colors.len() {
+        return Err(PyValueError::new_err(
+            "user, sat and color must be equally long",
+        ));
+    }
+    let mut solution = Solution::new();
+    for ((user, sat), color) in users.iter().zip(&sats).zip(&colors) {
+        if !(1..=4).contains(color) {
+            return Err(PyValueError::new_err(format!(
+                "bad color {} for user {}, expected 1 to 4",
+                color, user
+            )));
+        }
+        let color = Color::from_id(*color as i32);
+        if solution.insert(User(*user), (Sat(*sat), color)).is_some() {
+            return Err(PyValueError::new_err(format!(
+                "user {} is assigned twice",
+                user
+            )));
+        }
+    }
+    Ok(solution)
+}
+
+fn to_py<'py>(py: Python<'py>, json: &Json) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match json {
+        Json::Null => py.None().into_bound(py),
+        Json::Bool(value) => PyBool::new(py, *value).to_owned().into_any(),
+        Json::Number(text) => match text.parse::<i64>() {
+            Ok(value) => value.into_pyobject(py)?.into_any(),
+            Err(_) => text
+                .parse::<f64>()
+                .map_err(|_| PyValueError::new_err(format!("bad number {}", text)))?
+                .into_pyobject(py)?
+                .into_any(),
+        },
+        Json::String(text) => PyString::new(py, text).into_any(),
+        Json::Array(items) => {
+            let items = items
+                .iter()
+                .map(|item| to_py(py, item))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, items)?.into_any()
+        }
+        Json::Object(members) => {
+            let dict = PyDict::new(py);
+            for (key, value) in members {
+                dict.set_item(key, to_py(py, value)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
+
+fn violation_list<'py>(py: Python<'py>, violations: &[Violation]) -> PyResult<Bound<'py, PyList>> {
+    let violations = violations
+        .iter()
+        .map(|violation| to_py(py, &violation.to_json()))
+        .collect::<PyResult<Vec<_>>>()?;
+    PyList::new(py, violations)
+}
+
+/// Solves the problem and returns the assignments as `user`, `sat` and `color` arrays,
+/// in user order, with `seconds` taken, whether that was `in_time`, the `violations`
+/// `verify` would return and whether the plan `passed`, as in the run report.
+#[pyfunction]
+#[pyo3(signature = (users, sats, constraints=None))]
+fn solve<'py>(
+    py: Python<'py>,
+    users: Positions<'py>,
+    sats: Positions<'py>,
+    constraints: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyDict>> {
+    let constraints = Constraints::new(constraints)?;
+    let scenario = scenario(&users, &sats, &constraints)?;
+    let (solution, duration, violations) = py.detach(|| {
+        let start = Instant::now();
+        let solution = constraints.solver.solve(&scenario, &constraints.config);
+        let duration = start.elapsed();
+        let violations = scenario.verify(&solution);
+        (solution, duration, violations)
+    });
+    let in_time = duration <= constraints.time_budget;
+
+    let result = PyDict::new(py);
+    let users: Vec<u64> = solution.keys().map(|user| user.0).collect();
+    let sats: Vec<u64> = solution.values().map(|(sat, _)| sat.0).collect();
+    let colors: Vec<u8> = solution.values().map(|(_, color)| *color as u8).collect();
+    result.set_item("user", PyArray1::from_vec(py, users))?;
+    result.set_item("sat", PyArray1::from_vec(py, sats))?;
+    result.set_item("color", PyArray1::from_vec(py, colors))?;
+    result.set_item("seconds", duration.as_secs_f64())?;
+    result.set_item("in_time", in_time)?;
+    result.set_item("passed", violations.is_empty() && in_time)?;
+    result.set_item("violations", violation_list(py, &violations)?)?;
+    Ok(result)
+}
+
+/// Every rule the assignments break, including too little coverage, as dicts with a
+/// `kind` and a `message`. Empty when they pass.
+#[pyfunction]
+#[pyo3(signature = (users, sats, assignments, constraints=None))]
+fn verify<'py>(
+    py: Python<'py>,
+    users: Positions<'py>,
+    sats: Positions<'py>,
+    assignments: &Bound<'py, PyAny>,
+    constraints: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyList>> {
+    let constraints = Constraints::new(constraints)?;
+    let scenario = scenario(&users, &sats, &constraints)?;
+    let solution = solution(assignments)?;
+    let violations = py.detach(|| scenario.verify(&solution));
+    violation_list(py, &violations)
+}
+
+/// What `beam_planner stats --json` reports: sizes, visibility and coverage bounds.
+#[pyfunction]
+#[pyo3(signature = (users, sats, constraints=None))]
+fn stats<'py>(
+    py: Python<'py>,
+    users: Positions<'py>,
+    sats: Positions<'py>,
+    constraints: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let constraints = Constraints::new(constraints)?;
+    let scenario = scenario(&users, &sats, &constraints)?;
+    let stats = py.detach(|| Stats::new(&scenario));
+    to_py(py, &stats.to_json())
+}
+
+/// Anomalies in the input, such as users off the surface or weights for unknown users,
+/// as dicts with a `severity` and a `message`.
+#[pyfunction]
+#[pyo3(signature = (users, sats, constraints=None))]
+fn validate<'py>(
+    py: Python<'py>,
+    users: Positions<'py>,
+    sats: Positions<'py>,
+    constraints: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyList>> {
+    let constraints = Constraints::new(constraints)?;
+    let scenario = scenario(&users, &sats, &constraints)?;
+    let issues = beam_planner::validate::validate(&scenario, &ValidationConfig::default())
+        .iter()
+        .map(|issue| {
+            let severity = match issue.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                // `validate` leaves out ignored issues.
+                Severity::Ignore => "ignore",
+            };
+            let json = Json::object()
+                .with("severity", severity)
+                .with("message", issue.to_string());
+            to_py(py, &json)
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    PyList::new(py, issues)
+}
+
+/// Why `user` is or isn't served by the assignments: a dict with a `reason`, the
+/// details behind it and a `message`.
+#[pyfunction]
+#[pyo3(signature = (users, sats, assignments, user, constraints=None))]
+fn explain<'py>(
+    py: Python<'py>,
+    users: Positions<'py>,
+    sats: Positions<'py>,
+    assignments: &Bound<'py, PyAny>,
+    user: u64,
+    constraints: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let constraints = Constraints::new(constraints)?;
+    let scenario = scenario(&users, &sats, &constraints)?;
+    let solution = solution(assignments)?;
+    let explanation = beam_planner::explain::explain(&scenario, &solution, User(user))
+        .ok_or_else(|| PyKeyError::new_err(format!("no user {}", user)))?;
+    to_py(py, &explanation.to_json())
+}
+
+#[pymodule]
+#[pyo3(name = "beam_planner")]
+fn python_module(module: &Bound<PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(solve, module)?)?;
+    module.add_function(wrap_pyfunction!(verify, module)?)?;
+    module.add_function(wrap_pyfunction!(stats, module)?)?;
+    module.add_function(wrap_pyfunction!(validate, module)?)?;
+    module.add_function(wrap_pyfunction!(explain, module)?)?;
+    Ok(())
+}
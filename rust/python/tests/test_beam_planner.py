@@ -0,0 +1,94 @@
+"""Run with `python -m pytest tests` after `maturin develop --release`."""
+import os
+
+import numpy as np
+import pytest
+
+import beam_planner
+
+SCENARIO = os.path.join(os.path.dirname(__file__), "../../../test/02_five_users.txt")
+
+
+def load(path):
+    users, sats, min_coverage = [], [], 1.0
+    with open(path) as f:
+        for line in f:
+            parts = line.split()
+            if parts and parts[0] == "user":
+                users.append([float(x) for x in parts[2:5]])
+            elif parts and parts[0] == "sat":
+                sats.append([float(x) for x in parts[2:5]])
+            elif parts and parts[0] == "min_coverage":
+                min_coverage = float(parts[1])
+    return np.array(users), np.array(sats), min_coverage
+
+
+def test_solve_verify_explain():
+    users, sats, min_coverage = load(SCENARIO)
+    constraints = {"min_coverage": min_coverage, "solver": "e/least", "threads": 1}
+    result = beam_planner.solve(users, sats, constraints)
+    assert len(result["user"]) == 4
+    assert result["sat"].tolist() == [0, 0, 0, 0]
+    assert set(result["color"].tolist()) <= {1, 2, 3, 4}
+    assert result["in_time"]
+    assert result["passed"] and result["violations"] == []
+
+    strict = beam_planner.solve(users, sats, {"min_coverage": 1.0, "threads": 1})
+    assert not strict["passed"]
+    assert [v["kind"] for v in strict["violations"]] == ["low_coverage"]
+
+    assert beam_planner.verify(users, sats, result, constraints) == []
+    low = beam_planner.verify(users, sats, result, {"min_coverage": 1.0})
+    assert [v["kind"] for v in low] == ["low_coverage"]
+
+    unserved = (set(range(len(users))) - set(result["user"].tolist())).pop()
+    explanation = beam_planner.explain(users, sats, result, unserved)
+    assert explanation["reason"] == "blocked"
+    assert beam_planner.explain(users, sats, result, int(result["user"][0]))["reason"] == "served"
+
+
+def test_verify_catches_clashes():
+    users, sats, _ = load(SCENARIO)
+    # Users 0 and 1 are almost in line from the satellite.
+    assignments = {"user": [0, 1], "sat": [0, 0], "color": [1, 1]}
+    violations = beam_planner.verify(users, sats, assignments, {"min_coverage": 0.0})
+    assert [v["kind"] for v in violations] == ["co_channel"]
+
+
+def test_diagnostics():
+    users, sats, _ = load(SCENARIO)
+    stats = beam_planner.stats(users.astype(np.float32), sats, {"weights": {0: 2.0}})
+    assert stats["users"] == 5
+    issues = beam_planner.validate(users, sats, {"weights": {9: 1.0}})
+    assert [i["severity"] for i in issues] == ["error"]
+
+
+def test_bad_input():
+    users, sats, _ = load(SCENARIO)
+    with pytest.raises(ValueError):
+        beam_planner.solve(users[:, :2], sats)
+    with pytest.raises(KeyError):
+        beam_planner.solve(users, sats, {"coverage": 0.5})
+    with pytest.raises(ValueError):
+        beam_planner.solve(users, sats, {"solver": "x"})
+    with pytest.raises(ValueError):
+        beam_planner.verify(users, sats, {"user": [0], "sat": [0], "color": [5]})
+    with pytest.raises(ValueError):
+        beam_planner.solve(users, sats, {"time_budget": -1.0})
+    with pytest.raises(ValueError):
+        beam_planner.solve(users, sats, {"threads": 10**9})
+    # Finite as a double but not as the solvers' 32-bit floats.
+    far = users.copy()
+    far[0, 0] = 1e300
+    with pytest.raises(ValueError):
+        beam_planner.solve(far, sats)
+    for weight in [float("nan"), float("inf"), -1.0]:
+        with pytest.raises(ValueError):
+            beam_planner.validate(users, sats, {"weights": {0: weight}})
+
+
+def test_unmeasurable_time_budget():
+    users, sats, _ = load(SCENARIO)
+    # As on the command line and in C, a budget too long for a Duration is no limit.
+    for budget in [1e20, float("inf")]:
+        assert beam_planner.solve(users, sats, {"time_budget": budget})["in_time"]
@@ -0,0 +1,923 @@
+use crate::config::{self, Solver, SolverConfig};
+use crate::explain;
+use crate::format::{self, Format};
+use crate::generate;
+use crate::json::Json;
+use crate::orbit::Epoch;
+use crate::report::{self, RunReport};
+use crate::scenario::{Scenario, TIMEOUT};
+use crate::stats::Stats;
+use crate::test_util::{BOLD, GRAY, GREEN, RED, RESET, YELLOW};
+use crate::trace;
+use crate::util::User;
+use crate::validate::{Severity, ValidationConfig};
+use crate::verify::Violation;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    io::Write,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+const USAGE: &str = "COMMAND [ARGS]
+
+Commands:
+  solve SCENARIO [--out PATH|-] [--format text|json|csv] [--solver v|e] [--scoring fewest|least]
+        [--threads N] [--seed N] [--time-budget SECONDS] [--epoch EPOCH] [--json]
+        [--report PATH|-] [--trace PATH] [--trace-summary]
+  check SCENARIO SOLUTION [--epoch EPOCH] [--json]
+  explain SCENARIO USER_ID [--solution PATH] [--solver v|e] [--scoring fewest|least]
+        [--threads N] [--seed N] [--epoch EPOCH] [--json]
+  generate walker delta|star ALTITUDE_KM INCLINATION PLANES SATS_PER_PLANE PHASING EPOCH [--elements]
+  generate users uniform|land MASK_FILE|density RASTER_FILE|hotspots HOTSPOTS SIGMA_KM
+        COUNT SEED [--sats SCENARIO]
+  convert [--solution] [--from text|json|csv] [--to text|json|csv] IN_PATH OUT_PATH
+  stats SCENARIO [--epoch EPOCH] [--json]
+  compare SCENARIO [--solvers v/fewest,v/least,e/fewest,e/least] [--threads N] [--seed N]
+        [--time-budget SECONDS] [--json]
+
+`OUT_PATH TEST_CASE [EPOCH]` is short for `solve TEST_CASE --out OUT_PATH --epoch EPOCH`.
+Scenario and solution formats come from the file extension unless a flag gives them.
+explain says why a user is unserved, in a saved solution or a fresh solve.
+With --json, the result is printed as one JSON object. --report writes a versioned JSON
+run report with stats, bounds, phase timings and peak memory. --trace writes the solver's
+phases as a Chrome trace (open it in chrome://tracing or Perfetto); --trace-summary prints
+them.
+
+Exit codes: 0 success; 1 a solution breaks a rule, misses the scenario's coverage or the
+time budget; 2 bad arguments; 3 unreadable or invalid input, or unwritable output.";
+
+/// Why a command didn't succeed, which decides the exit code.
+#[derive(Debug)]
+pub enum Failure {
+    /// A solution breaks a rule, misses coverage or the time budget. The command has
+    /// already said why.
+    Rejected,
+    /// Bad command line.
+    Usage(String),
+    /// Unreadable or invalid input, or unwritable output.
+    Input(String),
+}
+
+impl Failure {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Rejected => 1,
+            Self::Usage(_) => 2,
+            Self::Input(_) => 3,
+        }
+    }
+}
+
+fn input(error: impl Display) -> Failure {
+    Failure::Input(error.to_string())
+}
+
+type CommandResult = Result<(), Failure>;
+
+const COMMANDS: [&str; 8] = [
+    "solve", "check", "explain", "generate", "convert", "stats", "compare", "help",
+];
+
+/// Runs the command line `args`, including the program name, and returns the exit code.
+pub fn run(args: &[String]) -> i32 {
+    let program = args.first().map_or("beam_planner", String::as_str);
+    let rest = args.get(2..).unwrap_or_default();
+    let result = match args.get(1).map(String::as_str) {
+        Some("solve") => solve_main(rest),
+        Some("check") => check_main(rest),
+        Some("explain") => explain_main(rest),
+        Some("generate") => generate_main(rest),
+        Some("convert") => convert_main(rest),
+        Some("stats") => stats_main(rest),
+        Some("compare") => compare_main(rest),
+        Some("help" | "--help" | "-h") => {
+            println!("USAGE: {} {}", program, USAGE);
+            Ok(())
+        }
+        // The original interface: OUT_PATH TEST_CASE [EPOCH].
+        Some(out_path) if (3..=4).contains(&args.len()) => {
+            let mut solve = vec![args[2].clone(), "--out".to_string(), args[1].clone()];
+            if let Some(epoch) = args.get(3) {
+                solve.extend(["--epoch".to_string(), epoch.clone()]);
+            }
+            legacy_out_path(out_path).and_then(|()| solve_main(&solve))
+        }
+        _ => Err(Failure::Usage("expected a command".to_string())),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(failure) => {
+            match &failure {
+                Failure::Rejected => {}
+                Failure::Usage(message) => {
+                    eprintln!("{}", message);
+                    eprintln!("USAGE: {} {}", program, USAGE);
+                }
+                Failure::Input(message) => eprintln!("{}", message),
+            }
+            failure.exit_code()
+        }
+    }
+}
+
+/// Checks the first word of an `OUT_PATH TEST_CASE [EPOCH]` command line. Any path but a
+/// command name is fine, as it always was, but one a typo away from a command gets a
+/// warning in case it was meant as that command.
+fn legacy_out_path(word: &str) -> CommandResult {
+    if COMMANDS.contains(&word) {
+        return Err(Failure::Usage(format!(
+            "{:?} is a command; write to it with `solve TEST_CASE --out ./{}`",
+            word, word
+        )));
+    }
+    if let Some(command) = COMMANDS
+        .iter()
+        .find(|command| edit_distance(word, command) <= 2)
+    {
+        eprintln!(
+            "{YELLOW}warning: writing the solution to {:?}; did you mean the {:?} command?{RESET}",
+            word, command
+        );
+    }
+    Ok(())
+}
+
+/// Levenshtein distance, counting characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// A command line split into positional arguments, `--name value` options and
+/// `--name` switches.
+struct Args {
+    positional: Vec<String>,
+    options: BTreeMap<&'static str, String>,
+    switches: BTreeSet<&'static str>,
+}
+
+impl Args {
+    fn parse(
+        args: &[String],
+        options: &[&'static str],
+        switches: &[&'static str],
+    ) -> Result<Self, Failure> {
+        let mut parsed = Self {
+            positional: Vec::new(),
+            options: BTreeMap::new(),
+            switches: BTreeSet::new(),
+        };
+        let mut rest = args.iter();
+        while let Some(arg) = rest.next() {
+            if let Some(&name) = options.iter().find(|name| arg == *name) {
+                let value = rest
+                    .next()
+                    .ok_or_else(|| Failure::Usage(format!("{} needs a value", name)))?;
+                parsed.options.insert(name, value.clone());
+            } else if let Some(&name) = switches.iter().find(|name| arg == *name) {
+                parsed.switches.insert(name);
+            } else if arg.starts_with("--") {
+                return Err(Failure::Usage(format!("unknown option {}", arg)));
+            } else {
+                parsed.positional.push(arg.clone());
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn get<T: FromStr>(&self, name: &str) -> Result<Option<T>, Failure>
+    where
+        T::Err: Display,
+    {
+        self.options
+            .get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|error| Failure::Usage(format!("{} {:?}: {}", name, value, error)))
+            })
+            .transpose()
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.switches.contains(name)
+    }
+
+    /// Exactly `N` positional arguments.
+    fn positional<const N: usize>(&self, what: &str) -> Result<[&str; N], Failure> {
+        let strs: Vec<&str> = self.positional.iter().map(String::as_str).collect();
+        strs.try_into()
+            .map_err(|_| Failure::Usage(format!("expected {}", what)))
+    }
+
+    fn config(&self) -> Result<SolverConfig, Failure> {
+        let config = SolverConfig {
+            seed: self.get("--seed")?.unwrap_or(0),
+            threads: self.get("--threads")?.unwrap_or(0),
+        };
+        config.validate().map_err(Failure::Usage)?;
+        Ok(config)
+    }
+
+    fn time_budget(&self) -> Result<Duration, Failure> {
+        match self.get::<f64>("--time-budget")? {
+            Some(seconds) => config::time_budget(seconds).map_err(Failure::Usage),
+            None => Ok(TIMEOUT),
+        }
+    }
+}
+
+/// Where human-readable output goes: stdout, or stderr when stdout carries a solution
+/// or report, or nowhere with `--json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Console {
+    Stdout,
+    Stderr,
+    Quiet,
+}
+
+impl Console {
+    fn new(args: &Args) -> Self {
+        if args.has("--json") {
+            Self::Quiet
+        } else if ["--out", "--report"]
+            .iter()
+            .any(|option| args.options.get(option).map(String::as_str) == Some("-"))
+        {
+            Self::Stderr
+        } else {
+            Self::Stdout
+        }
+    }
+
+    fn line(self, text: impl Display) {
+        match self {
+            Self::Stdout => println!("{}", text),
+            Self::Stderr => eprintln!("{}", text),
+            Self::Quiet => {}
+        }
+    }
+
+    fn violations(self, violations: &[Violation]) {
+        for violation in violations {
+            self.line(format!("{RED}{BOLD}FAIL: {RESET}{}", violation));
+        }
+    }
+}
+
+/// Reads a scenario, moves it to `--epoch` if given, and validates it. Validation
+/// errors fail the command; warnings are shown and returned.
+fn load_scenario(
+    path: &str,
+    args: &Args,
+    console: Console,
+) -> Result<(Scenario, Vec<String>), Failure> {
+    // Duplicate ids come back from validation with everything else that's wrong.
+    let mut scenario =
+        format::read_scenario_lenient(path, Format::from_path(path)).map_err(input)?;
+    if let Some(epoch) = args.options.get("--epoch") {
+        let epoch = Epoch::parse(epoch).map_err(|error| Failure::Usage(error.to_string()))?;
+        scenario = scenario.at(epoch).map_err(input)?;
+    }
+    let issues = scenario.validate(&ValidationConfig::default());
+    let (errors, warnings): (Vec<_>, Vec<_>) = issues
+        .iter()
+        .partition(|issue| issue.severity == Severity::Error);
+    if !errors.is_empty() {
+        let errors: Vec<String> = errors.iter().map(|issue| issue.to_string()).collect();
+        return Err(Failure::Input(errors.join("\n")));
+    }
+    let warnings: Vec<String> = warnings.iter().map(|issue| issue.to_string()).collect();
+    for warning in &warnings {
+        console.line(format!("{YELLOW}{}{RESET}", warning));
+    }
+    Ok((scenario, warnings))
+}
+
+fn coverage(served: usize, users: usize) -> f32 {
+    if users == 0 {
+        1.0
+    } else {
+        served as f32 / users as f32
+    }
+}
+
+fn solve_main(args: &[String]) -> CommandResult {
+    let args = Args::parse(
+        args,
+        &[
+            "--out",
+            "--format",
+            "--solver",
+            "--scoring",
+            "--threads",
+            "--seed",
+            "--time-budget",
+            "--epoch",
+            "--report",
+            "--trace",
+        ],
+        &["--json", "--trace-summary"],
+    )?;
+    let [path] = args.positional("one scenario")?;
+    let json = args.has("--json");
+    let mut solver: Solver = args.get("--solver")?.unwrap_or_default();
+    if let Some(scoring) = args.get("--scoring")? {
+        solver.scoring = scoring;
+    }
+    let config = args.config()?;
+    let time_budget = args.time_budget()?;
+    let out = args.options.get("--out");
+    let report_path = args.options.get("--report");
+    let to_stdout: Vec<&str> = [
+        ("--json", json),
+        ("--out -", out.map(String::as_str) == Some("-")),
+        ("--report -", report_path.map(String::as_str) == Some("-")),
+    ]
+    .into_iter()
+    .filter(|(_, stdout)| *stdout)
+    .map(|(flag, _)| flag)
+    .collect();
+    if to_stdout.len() > 1 {
+        return Err(Failure::Usage(format!(
+            "{} all write to stdout",
+            to_stdout.join(" and ")
+        )));
+    }
+    let out_format = match (args.get("--format")?, out) {
+        (Some(format), _) => format,
+        (None, Some(out)) => Format::from_path(out),
+        (None, None) => Format::Text,
+    };
+
+    let console = Console::new(&args);
+    let mut timings = Vec::new();
+    let mut phase = Instant::now();
+    let mut end_phase = |name: &'static str| {
+        timings.push((name, phase.elapsed()));
+        phase = Instant::now();
+    };
+    let (scenario, warnings) = load_scenario(path, &args, console)?;
+    end_phase("load");
+    console.line(format!(
+        "{GRAY}Scenario: {RESET}{}% coverage ({} users, {} sats){RESET}",
+        100.0 * scenario.min_coverage,
+        scenario.users.len(),
+        scenario.sats.len(),
+    ));
+
+    let trace_path = args.options.get("--trace");
+    let tracing = trace_path.is_some() || report_path.is_some() || args.has("--trace-summary");
+    if tracing {
+        trace::start();
+    }
+    let start = Instant::now();
+    let solution = solver.solve(&scenario, &config);
+    let duration = start.elapsed();
+    end_phase("solve");
+    let spans = if tracing { trace::finish() } else { Vec::new() };
+    let covered = coverage(solution.len(), scenario.users.len());
+    let violations = scenario.verify(&solution);
+    end_phase("verify");
+    let in_time = duration <= time_budget;
+
+    if let Some(out) = out {
+        let mut text = Vec::new();
+        if out_format == Format::Text {
+            // The summary line the output file used to hold, kept as a comment.
+            writeln!(
+                text,
+                "# {} {} {}s",
+                path,
+                100.0 * covered,
+                duration.as_secs()
+            )
+            .unwrap();
+        }
+        format::write_solution(&mut text, &solution, out_format).unwrap();
+        if out == "-" {
+            std::io::stdout().write_all(&text).map_err(input)?;
+        } else {
+            std::fs::write(out, text).map_err(|error| input(format!("{}: {}", out, error)))?;
+        }
+        end_phase("write");
+    }
+    if let Some(trace_path) = trace_path {
+        std::fs::write(trace_path, format!("{}\n", trace::chrome_trace(&spans)))
+            .map_err(|error| input(format!("{}: {}", trace_path, error)))?;
+    }
+    let trace_summary = if args.has("--trace-summary") {
+        trace::summary(&spans)
+    } else {
+        Vec::new()
+    };
+
+    if let Some(report_path) = report_path {
+        let stats = Stats::new(&scenario);
+        end_phase("stats");
+        let report = RunReport {
+            scenario_path: path,
+            scenario: &scenario,
+            stats,
+            solver: solver.to_string(),
+            config,
+            time_budget,
+            solution: &solution,
+            violations: &violations,
+            warnings: &warnings,
+            timings,
+            spans,
+            peak_memory: report::peak_memory(),
+        };
+        let text = format!("{}\n", report.to_json());
+        if report_path == "-" {
+            print!("{}", text);
+        } else {
+            std::fs::write(report_path, text)
+                .map_err(|error| input(format!("{}: {}", report_path, error)))?;
+        }
+    }
+
+    if json {
+        let report = Json::object()
+            .with("scenario", path)
+            .with("solver", solver.to_string())
+            .with("seed", config.seed)
+            .with("users", scenario.users.len())
+            .with("sats", scenario.sats.len())
+            .with("served", solution.len())
+            .with("coverage", covered)
+            .with("min_coverage", scenario.min_coverage)
+            .with("seconds", duration.as_secs_f64())
+            .with("time_budget", time_budget.as_secs_f64())
+            .with("warnings", warnings)
+            .with("violations", violation_strings(&violations))
+            .with("passed", violations.is_empty() && in_time);
+        println!("{}", report);
+    } else {
+        console.line(format!(
+            "{GRAY}Solution: {RESET}{BOLD}{}{}% coverage ({} users) in {}{BOLD}{}s{RESET}",
+            if covered >= scenario.min_coverage {
+                GREEN
+            } else {
+                RED
+            },
+            100.0 * covered,
+            solution.len(),
+            if !in_time {
+                RED
+            } else if duration > time_budget / 2 {
+                YELLOW
+            } else {
+                GREEN
+            },
+            duration.as_secs(),
+        ));
+        for line in &trace_summary {
+            console.line(format!("{GRAY}{}{RESET}", line));
+        }
+        console.violations(&violations);
+        if !in_time {
+            console.line(format!(
+                "{RED}{BOLD}FAIL: {RESET}Took too long to produce a solution"
+            ));
+        }
+    }
+
+    if violations.is_empty() && in_time {
+        Ok(())
+    } else {
+        Err(Failure::Rejected)
+    }
+}
+
+fn violation_strings(violations: &[Violation]) -> Vec<String> {
+    violations.iter().map(ToString::to_string).collect()
+}
+
+// Audits a saved solution against its scenario.
+fn check_main(args: &[String]) -> CommandResult {
+    let args = Args::parse(args, &["--epoch"], &["--json"])?;
+    let [scenario_path, solution_path] = args.positional("a scenario and a solution")?;
+    let json = args.has("--json");
+    let console = Console::new(&args);
+    let (scenario, warnings) = load_scenario(scenario_path, &args, console)?;
+    let solution =
+        format::read_solution(solution_path, Format::from_path(solution_path)).map_err(input)?;
+
+    let violations = scenario.verify(&solution);
+    let covered = coverage(solution.len(), scenario.users.len());
+    if json {
+        let report = Json::object()
+            .with("scenario", scenario_path)
+            .with("solution", solution_path)
+            .with("users", scenario.users.len())
+            .with("served", solution.len())
+            .with("coverage", covered)
+            .with("min_coverage", scenario.min_coverage)
+            .with("warnings", warnings)
+            .with("violations", violation_strings(&violations))
+            .with("passed", violations.is_empty());
+        println!("{}", report);
+    } else {
+        println!(
+            "{GRAY}Solution: {RESET}{BOLD}{}{}% coverage ({} users){RESET}",
+            if covered >= scenario.min_coverage {
+                GREEN
+            } else {
+                RED
+            },
+            100.0 * covered,
+            solution.len(),
+        );
+        console.violations(&violations);
+    }
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Failure::Rejected)
+    }
+}
+
+// Says why one user is or isn't served.
+fn explain_main(args: &[String]) -> CommandResult {
+    let args = Args::parse(
+        args,
+        &[
+            "--solution",
+            "--solver",
+            "--scoring",
+            "--threads",
+            "--seed",
+            "--epoch",
+        ],
+        &["--json"],
+    )?;
+    let [path, user] = args.positional("a scenario and a user id")?;
+    let user = User::new(
+        user.parse()
+            .map_err(|_| Failure::Usage(format!("bad user id {:?}", user)))?,
+    );
+    let mut solver: Solver = args.get("--solver")?.unwrap_or_default();
+    if let Some(scoring) = args.get("--scoring")? {
+        solver.scoring = scoring;
+    }
+    let config = args.config()?;
+    let (scenario, _) = load_scenario(path, &args, Console::new(&args))?;
+    let solution = match args.options.get("--solution") {
+        Some(solution_path) => {
+            format::read_solution(solution_path, Format::from_path(solution_path)).map_err(input)?
+        }
+        None => solver.solve(&scenario, &config),
+    };
+
+    let explanation = explain::explain(&scenario, &solution, user)
+        .ok_or_else(|| Failure::Input(format!("{}: no user {}", path, user)))?;
+    if args.has("--json") {
+        println!("{}", explanation.to_json().with("user", user.0));
+    } else {
+        println!("{GRAY}User {}: {RESET}{}", user, explanation);
+    }
+    Ok(())
+}
+
+// Rewrites a scenario, or a solution with `--solution`, in another format. Formats not
+// given by a flag come from the file extensions.
+fn convert_main(args: &[String]) -> CommandResult {
+    let args = Args::parse(args, &["--from", "--to"], &["--solution"])?;
+    let [in_path, out_path] = args.positional("an input and an output path")?;
+    let from = args
+        .get("--from")?
+        .unwrap_or_else(|| Format::from_path(in_path));
+    let to = args
+        .get("--to")?
+        .unwrap_or_else(|| Format::from_path(out_path));
+
+    let mut out = Vec::new();
+    if args.has("--solution") {
+        let solution = format::read_solution(in_path, from).map_err(input)?;
+        format::write_solution(&mut out, &solution, to).unwrap();
+    } else {
+        let scenario = format::read_scenario(in_path, from).map_err(input)?;
+        format::write_scenario(&mut out, &scenario, to).unwrap();
+    }
+    std::fs::write(out_path, out).map_err(|error| input(format!("{}: {}", out_path, error)))
+}
+
+fn stats_main(args: &[String]) -> CommandResult {
+    let args = Args::parse(args, &["--epoch"], &["--json"])?;
+    let [path] = args.positional("one scenario")?;
+    let json = args.has("--json");
+    let (scenario, warnings) = load_scenario(path, &args, Console::new(&args))?;
+    let stats = Stats::new(&scenario);
+    if json {
+        println!(
+            "{}",
+            stats
+                .to_json()
+                .with("scenario", path)
+                .with("warnings", warnings)
+        );
+        return Ok(());
+    }
+
+    let range = |range: Option<(f32, f32)>| match range {
+        Some((min, max)) => format!("{:.1} to {:.1} km", min, max),
+        None => "-".to_string(),
+    };
+    println!(
+        "{GRAY}Users: {RESET}{} at radius {}",
+        stats.users,
+        range(stats.user_radius)
+    );
+    println!(
+        "{GRAY}Satellites: {RESET}{} ({} with orbits) at radius {}",
+        stats.sats,
+        stats.orbits,
+        range(stats.sat_radius)
+    );
+    println!(
+        "{GRAY}Visibility: {RESET}{} users see a satellite, {:.2} satellites per user \
+         on average, at most {}; at most {} users per satellite",
+        stats.reachable_users,
+        stats.mean_sats_per_user,
+        stats.max_sats_per_user,
+        stats.max_users_per_sat
+    );
+    println!(
+        "{GRAY}Coverage: {RESET}{}% required, at most {}% possible",
+        100.0 * stats.min_coverage,
+        100.0 * stats.coverage_bound
+    );
+    Ok(())
+}
+
+// Runs each solver on the same scenario and tabulates the results.
+fn compare_main(args: &[String]) -> CommandResult {
+    let args = Args::parse(
+        args,
+        &[
+            "--solvers",
+            "--threads",
+            "--seed",
+            "--time-budget",
+            "--epoch",
+        ],
+        &["--json"],
+    )?;
+    let [path] = args.positional("one scenario")?;
+    let json = args.has("--json");
+    let solvers = match args.options.get("--solvers") {
+        Some(list) => list
+            .split(',')
+            .map(str::parse)
+            .collect::<Result<Vec<Solver>, String>>()
+            .map_err(Failure::Usage)?,
+        None => Solver::ALL.to_vec(),
+    };
+    let config = args.config()?;
+    let time_budget = args.time_budget()?;
+    let (scenario, warnings) = load_scenario(path, &args, Console::new(&args))?;
+
+    if !json {
+        println!(
+            "{BOLD}{:<12} {:>9} {:>9} {:>9} {:>10}{RESET}",
+            "solver", "served", "coverage", "seconds", "violations"
+        );
+    }
+    let mut results = Vec::new();
+    let mut passed = true;
+    for solver in solvers {
+        let start = Instant::now();
+        let solution = solver.solve(&scenario, &config);
+        let seconds = start.elapsed().as_secs_f64();
+        let violations = scenario.verify(&solution).len();
+        let covered = coverage(solution.len(), scenario.users.len());
+        let ok = violations == 0 && seconds <= time_budget.as_secs_f64();
+        passed &= ok;
+        if json {
+            results.push(
+                Json::object()
+                    .with("solver", solver.to_string())
+                    .with("served", solution.len())
+                    .with("coverage", covered)
+                    .with("seconds", seconds)
+                    .with("violations", violations)
+                    .with("passed", ok),
+            );
+        } else {
+            println!(
+                "{:<12} {:>9} {:>8.2}% {:>9.3} {}{:>10}{RESET}",
+                solver.to_string(),
+                solution.len(),
+                100.0 * covered,
+                seconds,
+                if ok { GREEN } else { RED },
+                violations
+            );
+        }
+    }
+    if json {
+        let report = Json::object()
+            .with("scenario", path)
+            .with("users", scenario.users.len())
+            .with("sats", scenario.sats.len())
+            .with("min_coverage", scenario.min_coverage)
+            .with("warnings", warnings)
+            .with("results", results);
+        println!("{}", report);
+    }
+    if passed {
+        Ok(())
+    } else {
+        Err(Failure::Rejected)
+    }
+}
+
+// Writes a generated constellation or user population to stdout.
+fn generate_main(args: &[String]) -> CommandResult {
+    let args = Args::parse(args, &["--sats"], &["--elements"])?;
+    let mut out = Vec::new();
+    match args.positional.first().map(String::as_str) {
+        Some("walker") => generate_walker(&args, &mut out)?,
+        Some("users") => generate_users(&args, &mut out)?,
+        _ => return Err(Failure::Usage("expected walker or users".to_string())),
+    }
+    match std::io::stdout().write_all(&out) {
+        // A closed pipe (like `| head`) is not worth a complaint.
+        Err(error) if error.kind() != std::io::ErrorKind::BrokenPipe => Err(input(error)),
+        _ => Ok(()),
+    }
+}
+
+fn number<T: FromStr>(text: &str, what: &str) -> Result<T, Failure> {
+    text.parse()
+        .map_err(|_| Failure::Usage(format!("bad {} {:?}", what, text)))
+}
+
+fn generate_walker(args: &Args, out: &mut Vec<u8>) -> CommandResult {
+    let [_, pattern, altitude, inclination, planes, sats_per_plane, phasing, epoch] =
+        args.positional("a walker constellation")?;
+    let walker = generate::Walker {
+        pattern: match pattern {
+            "delta" => generate::WalkerPattern::Delta,
+            "star" => generate::WalkerPattern::Star,
+            _ => return Err(Failure::Usage(format!("bad pattern {:?}", pattern))),
+        },
+        altitude: number(altitude, "altitude")?,
+        inclination: number(inclination, "inclination")?,
+        planes: number(planes, "plane count")?,
+        sats_per_plane: number(sats_per_plane, "satellites per plane")?,
+        phasing: number(phasing, "phasing")?,
+        epoch: Epoch::parse(epoch).map_err(|error| Failure::Usage(error.to_string()))?,
+        first_id: 1,
+    };
+    let scenario = walker.scenario().map_err(input)?;
+    generate::write_sats(out, &scenario, args.has("--elements")).unwrap();
+    Ok(())
+}
+
+fn generate_users(args: &Args, out: &mut Vec<u8>) -> CommandResult {
+    let positional: Vec<&str> = args.positional.iter().map(String::as_str).collect();
+    let read =
+        |path: &str| std::fs::read_to_string(path).map_err(|e| input(format!("{}: {}", path, e)));
+    let (population, rest) = match positional[1..] {
+        ["uniform", ref rest @ ..] => (generate::Population::Uniform, rest),
+        ["land", mask, ref rest @ ..] => (
+            generate::Population::Raster(
+                generate::Raster::parse_mask(&read(mask)?).map_err(input)?,
+            ),
+            rest,
+        ),
+        ["density", raster, ref rest @ ..] => (
+            generate::Population::Raster(
+                generate::Raster::parse_density(&read(raster)?).map_err(input)?,
+            ),
+            rest,
+        ),
+        ["hotspots", count, sigma, ref rest @ ..] => {
+            let seed: u64 = number(rest.get(1).copied().unwrap_or_default(), "seed")?;
+            let count: usize = number(count, "hotspot count")?;
+            let sigma: f64 = number(sigma, "sigma")?;
+            if count == 0 {
+                return Err(Failure::Usage("expected at least one hotspot".to_string()));
+            }
+            if !(sigma.is_finite() && sigma >= 0.0) {
+                return Err(Failure::Usage(format!("bad sigma {}", sigma)));
+            }
+            // Centers come from the seed too, but not from the users' own sequence.
+            let hotspots = generate::Hotspot::random(count, sigma, &mut generate::Rng::new(!seed));
+            (generate::Population::Hotspots(hotspots), rest)
+        }
+        _ => return Err(Failure::Usage("expected a user population".to_string())),
+    };
+    let [count, seed] = rest[..] else {
+        return Err(Failure::Usage("expected COUNT SEED".to_string()));
+    };
+    let sats = match args.options.get("--sats") {
+        Some(path) => Scenario::new(path).map_err(input)?.sats,
+        None => BTreeMap::new(),
+    };
+
+    let users = generate::users(
+        &population,
+        number(count, "user count")?,
+        number(seed, "seed")?,
+        1,
+    )
+    .map_err(input)?;
+    let min_coverage = generate::suggest_min_coverage(&users, &sats);
+    generate::write_users(out, &users, min_coverage).unwrap();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_args() {
+        let args = Args::parse(
+            &strings(&["a", "--seed", "7", "--json", "b"]),
+            &["--seed"],
+            &["--json"],
+        )
+        .unwrap();
+        assert_eq!(args.positional("two").unwrap(), ["a", "b"]);
+        assert_eq!(args.get::<u64>("--seed").unwrap(), Some(7));
+        assert_eq!(args.get::<u64>("--threads").unwrap(), None);
+        assert!(args.has("--json"));
+        assert!(args.positional::<1>("one").is_err());
+
+        let unknown = Args::parse(&strings(&["--sed", "7"]), &["--seed"], &[]);
+        assert_eq!(unknown.err().unwrap().exit_code(), 2);
+        let missing = Args::parse(&strings(&["--seed"]), &["--seed"], &[]);
+        assert!(matches!(missing, Err(Failure::Usage(_))));
+        let args = Args::parse(&strings(&["--seed", "x"]), &["--seed"], &[]).unwrap();
+        assert!(args.get::<u64>("--seed").is_err());
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let run = |args: &[&str]| run(&strings(&[&["beam_planner"], args].concat()));
+        assert_eq!(run(&["check", "../test/02_five_users.txt"]), 2);
+        assert_eq!(run(&["stats", "/nonexistent.txt"]), 3);
+        assert_eq!(run(&["stats", "../test/02_five_users.txt", "--json"]), 0);
+        assert_eq!(run(&["frobnicate"]), 2);
+        // Any legacy OUT_PATH but a command name; near misses only get a warning.
+        assert!(legacy_out_path("slove").is_ok());
+        assert!(legacy_out_path("output").is_ok());
+        assert!(matches!(legacy_out_path("help"), Err(Failure::Usage(_))));
+        assert_eq!(edit_distance("slove", "solve"), 2);
+        assert_eq!(edit_distance("", "help"), 4);
+
+        let path = std::env::temp_dir().join("beam_planner_cli_unserved.txt");
+        std::fs::write(&path, "").unwrap();
+        let path = path.to_str().unwrap();
+        // Nobody served: below the scenario's 80%.
+        assert_eq!(run(&["check", "../test/02_five_users.txt", path]), 1);
+        assert_eq!(
+            run(&["solve", "../test/02_five_users.txt", "--out", path]),
+            0
+        );
+        assert_eq!(run(&["check", "../test/02_five_users.txt", path]), 0);
+
+        for args in [["0", "100"], ["5", "-1"], ["5", "NaN"], ["5", "inf"]] {
+            let command = [&["generate", "users", "hotspots"], &args[..], &["100", "7"]];
+            assert_eq!(run(&command.concat()), 2, "{args:?}");
+        }
+
+        let args = [
+            "solve",
+            "../test/01_two_users.txt",
+            "--threads",
+            "1000000000000",
+        ];
+        assert_eq!(run(&[&args[..], &["--out", path]].concat()), 2);
+
+        // Too long for a `Duration` is no limit, as in the C and Python bindings.
+        for (budget, code) in [("1e20", 0), ("inf", 0), ("-1", 2), ("NaN", 2)] {
+            let args = ["solve", "../test/01_two_users.txt", "--time-budget", budget];
+            assert_eq!(
+                run(&[&args[..], &["--out", path]].concat()),
+                code,
+                "{budget}"
+            );
+        }
+    }
+}
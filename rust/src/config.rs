@@ -0,0 +1,217 @@
+use crate::greedy::{FewestOptions, LeastInterference};
+use crate::scenario::Scenario;
+use crate::util::{Color, Sat, User};
+use crate::{solution_e, solution_v};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    time::Duration,
+};
+
+type SolutionMap = BTreeMap<User, (Sat, Color)>;
+
+/// Knobs shared by the solvers. Solving the same scenario with the same config always
+/// produces the same plan, whatever the thread count.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct SolverConfig {
+    /// Shuffles the order in which equally scored links are tried. Seed 0 tries them in
+    /// satellite/user id order.
+    pub seed: u64,
+    /// Worker threads for the parallel phases. 0 leaves the choice to rayon.
+    pub threads: usize,
+}
+
+impl SolverConfig {
+    /// The most worker threads `validate` accepts.
+    pub const MAX_THREADS: usize = 1024;
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.threads > Self::MAX_THREADS {
+            return Err(format!(
+                "{} threads is more than the {} allowed",
+                self.threads,
+                Self::MAX_THREADS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Runs `f` on a thread pool sized by `threads`. If the pool can't be built, `f` runs
+    /// on the global pool instead, which gives the same plan.
+    pub fn install<R: Send>(&self, f: impl FnOnce() -> R + Send) -> R {
+        if self.threads == 0 {
+            return f();
+        }
+        match rayon::ThreadPoolBuilder::new()
+            .num_threads(self.threads)
+            .build()
+        {
+            Ok(pool) => pool.install(f),
+            Err(_) => f(),
+        }
+    }
+}
+
+/// Reads a time budget in seconds, for the command line and both bindings. Budgets too
+/// long for a `Duration`, infinity included, become `Duration::MAX` and can't be missed.
+pub fn time_budget(seconds: f64) -> Result<Duration, String> {
+    if seconds.is_nan() || seconds < 0.0 {
+        return Err(format!(
+            "bad time budget {}, expected a non-negative number of seconds",
+            seconds
+        ));
+    }
+    Ok(Duration::try_from_secs_f64(seconds).unwrap_or(Duration::MAX))
+}
+
+/// The solver engines. `V` builds each satellite's interference graph in parallel; `E`
+/// is the earlier sequential version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    V,
+    E,
+}
+
+/// The `greedy::Scoring` implementations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoringChoice {
+    FewestOptions,
+    LeastInterference,
+}
+
+/// An engine and a scoring, written `v/fewest`. A bare engine uses `fewest`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Solver {
+    pub engine: Engine,
+    pub scoring: ScoringChoice,
+}
+
+impl Solver {
+    pub const ALL: [Solver; 4] = [
+        Solver::new(Engine::V, ScoringChoice::FewestOptions),
+        Solver::new(Engine::V, ScoringChoice::LeastInterference),
+        Solver::new(Engine::E, ScoringChoice::FewestOptions),
+        Solver::new(Engine::E, ScoringChoice::LeastInterference),
+    ];
+
+    pub const fn new(engine: Engine, scoring: ScoringChoice) -> Self {
+        Self { engine, scoring }
+    }
+
+    pub fn solve(&self, scenario: &Scenario, config: &SolverConfig) -> SolutionMap {
+        let (users, sats) = (&scenario.users, &scenario.sats);
+        match (self.engine, self.scoring) {
+            (Engine::V, ScoringChoice::FewestOptions) => {
+                solution_v::solve_with(users, sats, config, &FewestOptions)
+            }
+            (Engine::V, ScoringChoice::LeastInterference) => {
+                solution_v::solve_with(users, sats, config, &LeastInterference)
+            }
+            (Engine::E, ScoringChoice::FewestOptions) => {
+                solution_e::solve_with(users, sats, config, &FewestOptions)
+            }
+            (Engine::E, ScoringChoice::LeastInterference) => {
+                solution_e::solve_with(users, sats, config, &LeastInterference)
+            }
+        }
+    }
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::ALL[0]
+    }
+}
+
+impl FromStr for Solver {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let (engine, scoring) = text.split_once('/').unwrap_or((text, "fewest"));
+        Ok(Self {
+            engine: engine.parse()?,
+            scoring: scoring.parse()?,
+        })
+    }
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        match text {
+            "v" => Ok(Self::V),
+            "e" => Ok(Self::E),
+            _ => Err(format!("unknown solver {:?}, expected v or e", text)),
+        }
+    }
+}
+
+impl FromStr for ScoringChoice {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        match text {
+            "fewest" => Ok(Self::FewestOptions),
+            "least" => Ok(Self::LeastInterference),
+            _ => Err(format!(
+                "unknown scoring {:?}, expected fewest or least",
+                text
+            )),
+        }
+    }
+}
+
+impl Display for Solver {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let engine = match self.engine {
+            Engine::V => "v",
+            Engine::E => "e",
+        };
+        let scoring = match self.scoring {
+            ScoringChoice::FewestOptions => "fewest",
+            ScoringChoice::LeastInterference => "least",
+        };
+        write!(f, "{}/{}", engine, scoring)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solver_names() {
+        assert_eq!("v".parse(), Ok(Solver::default()));
+        for solver in Solver::ALL {
+            assert_eq!(solver.to_string().parse(), Ok(solver));
+        }
+        assert!("x/fewest".parse::<Solver>().is_err());
+        assert!("v/most".parse::<Solver>().is_err());
+    }
+
+    #[test]
+    fn test_thread_counts() {
+        let config = |threads| SolverConfig { seed: 0, threads };
+        assert!(config(0).validate().is_ok());
+        assert!(config(SolverConfig::MAX_THREADS).validate().is_ok());
+        assert!(config(SolverConfig::MAX_THREADS + 1).validate().is_err());
+        assert_eq!(config(2).install(rayon::current_num_threads), 2);
+    }
+
+    #[test]
+    fn test_time_budgets() {
+        assert_eq!(time_budget(1.5), Ok(Duration::from_millis(1500)));
+        assert_eq!(time_budget(0.0), Ok(Duration::ZERO));
+        assert_eq!(time_budget(1e20), Ok(Duration::MAX));
+        assert_eq!(time_budget(f64::INFINITY), Ok(Duration::MAX));
+        assert!(time_budget(-1.0).is_err());
+        assert!(time_budget(f64::NAN).is_err());
+    }
+}
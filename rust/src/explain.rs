@@ -0,0 +1,374 @@
+use crate::json::Json;
+use crate::scenario::Scenario;
+use crate::util::{
+    Color, Sat, User, Vector3, MAX_ALLOWABLE_BEAM_ANGLE, MAX_ALLOWED_USERS, MINIMUM_BEAM_ANGLE,
+};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+const COLORS: [Color; 4] = [Color::A, Color::B, Color::C, Color::D];
+
+type SolutionMap = BTreeMap<User, (Sat, Color)>;
+
+/// A served user less than 10° from the explained user as seen from `sat`, so the
+/// explained user can't have `color` there. Angles are in degrees.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Blocker {
+    pub color: Color,
+    pub user: User,
+    pub angle: f32,
+}
+
+/// A visible satellite with room whose every color is taken by a nearby user.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockedSat {
+    pub sat: Sat,
+    /// Users the satellite serves.
+    pub load: usize,
+    /// By color, then by angle.
+    pub blockers: Vec<Blocker>,
+}
+
+/// Why a user is or isn't served by a solution.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "reason", rename_all = "snake_case")
+)]
+pub enum Explanation {
+    Served {
+        sat: Sat,
+        color: Color,
+    },
+    /// No satellite is within 45° of the user's vertical. `nearest` is the satellite
+    /// closest to vertical and its angle, if there are any satellites.
+    NoVisibleSat {
+        nearest: Option<(Sat, f32)>,
+    },
+    /// Every visible satellite already serves 32 users.
+    AllVisibleFull {
+        sats: Vec<Sat>,
+    },
+    /// Every visible satellite is either full or has all four colors taken by users
+    /// within 10°.
+    Blocked {
+        full: Vec<Sat>,
+        blocked: Vec<BlockedSat>,
+    },
+    /// A visible satellite has room and a free color: the solution could serve the
+    /// user as it stands.
+    Assignable {
+        sat: Sat,
+        color: Color,
+    },
+}
+
+/// Explains why `user` is or isn't served by `solution`, or `None` if the scenario has
+/// no such user. Assignments to users or satellites the scenario doesn't have are
+/// ignored.
+pub fn explain(scenario: &Scenario, solution: &SolutionMap, user: User) -> Option<Explanation> {
+    let user_pos = scenario.users.get(&user)?;
+    if let Some((sat, color)) = solution.get(&user) {
+        if scenario.sats.contains_key(sat) {
+            return Some(Explanation::Served {
+                sat: *sat,
+                color: *color,
+            });
+        }
+    }
+
+    let elevation_angle = |sat_pos: &Vector3| {
+        user_pos
+            .unit()
+            .dot((sat_pos - user_pos).unit())
+            .clamp(-1.0, 1.0)
+            .acos()
+            .to_degrees()
+    };
+    let mut nearest: Option<(Sat, f32)> = None;
+    let mut visible = Vec::new();
+    for (sat, sat_pos) in &scenario.sats {
+        let angle = elevation_angle(sat_pos);
+        if nearest.is_none_or(|(_, best)| angle < best) {
+            nearest = Some((*sat, angle));
+        }
+        if angle <= MAX_ALLOWABLE_BEAM_ANGLE {
+            visible.push(*sat);
+        }
+    }
+    if visible.is_empty() {
+        return Some(Explanation::NoVisibleSat { nearest });
+    }
+
+    // Who each visible satellite serves, on which color.
+    let mut beams: BTreeMap<Sat, Vec<(User, Color)>> =
+        visible.iter().map(|sat| (*sat, Vec::new())).collect();
+    for (other, (sat, color)) in solution {
+        if let Some(served) = beams.get_mut(sat) {
+            if scenario.users.contains_key(other) {
+                served.push((*other, *color));
+            }
+        }
+    }
+
+    let mut full = Vec::new();
+    let mut blocked = Vec::new();
+    for (sat, served) in &beams {
+        if served.len() >= MAX_ALLOWED_USERS {
+            full.push(*sat);
+            continue;
+        }
+        let sat_pos = &scenario.sats[sat];
+        let mut blockers: Vec<Blocker> = served
+            .iter()
+            .filter_map(|(other, color)| {
+                let angle = sat_pos.angle_between(user_pos, &scenario.users[other]);
+                (angle < MINIMUM_BEAM_ANGLE).then_some(Blocker {
+                    color: *color,
+                    user: *other,
+                    angle,
+                })
+            })
+            .collect();
+        if let Some(color) = COLORS
+            .into_iter()
+            .find(|color| blockers.iter().all(|b| b.color != *color))
+        {
+            return Some(Explanation::Assignable { sat: *sat, color });
+        }
+        blockers.sort_by(|a, b| a.color.cmp(&b.color).then(a.angle.total_cmp(&b.angle)));
+        blocked.push(BlockedSat {
+            sat: *sat,
+            load: served.len(),
+            blockers,
+        });
+    }
+    Some(if blocked.is_empty() {
+        Explanation::AllVisibleFull { sats: full }
+    } else {
+        Explanation::Blocked { full, blocked }
+    })
+}
+
+fn sat_list(sats: &[Sat]) -> String {
+    let ids: Vec<String> = sats.iter().map(ToString::to_string).collect();
+    ids.join(", ")
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Served { sat, color } => {
+                write!(f, "Served by satellite {} on color {}", sat, color)
+            }
+            Self::NoVisibleSat { nearest: None } => write!(f, "Unserved: there are no satellites"),
+            Self::NoVisibleSat {
+                nearest: Some((sat, angle)),
+            } => write!(
+                f,
+                "Unserved: no satellite within {} degrees of vertical \
+                 (nearest is satellite {} at {} degrees)",
+                MAX_ALLOWABLE_BEAM_ANGLE, sat, angle
+            ),
+            Self::AllVisibleFull { sats } => write!(
+                f,
+                "Unserved: every visible satellite already serves {} users ({})",
+                MAX_ALLOWED_USERS,
+                sat_list(sats)
+            ),
+            Self::Blocked { full, blocked } => {
+                write!(
+                    f,
+                    "Unserved: every color on every visible satellite with room is taken \
+                     by a user within {} degrees",
+                    MINIMUM_BEAM_ANGLE
+                )?;
+                if !full.is_empty() {
+                    write!(f, "\n  full: {}", sat_list(full))?;
+                }
+                for sat in blocked {
+                    write!(f, "\n  satellite {} ({} users):", sat.sat, sat.load)?;
+                    for color in COLORS {
+                        let users: Vec<String> = sat
+                            .blockers
+                            .iter()
+                            .filter(|b| b.color == color)
+                            .map(|b| format!("user {} ({} degrees)", b.user, b.angle))
+                            .collect();
+                        write!(f, "\n    color {}: {}", color, users.join(", "))?;
+                    }
+                }
+                Ok(())
+            }
+            Self::Assignable { sat, color } => write!(
+                f,
+                "Unserved, but satellite {} could serve it on color {}",
+                sat, color
+            ),
+        }
+    }
+}
+
+impl Explanation {
+    /// The explanation as an object with a snake_case `reason`, its fields, and the
+    /// `Display` text as `message`.
+    pub fn to_json(&self) -> Json {
+        let reason = |reason: &str| Json::object().with("reason", reason);
+        let sat_ids = |sats: &[Sat]| sats.iter().map(|sat| sat.0).collect::<Vec<_>>();
+        let json = match self {
+            Self::Served { sat, color } => reason("served")
+                .with("sat", sat.0)
+                .with("color", *color as i32),
+            Self::NoVisibleSat { nearest } => reason("no_visible_sat").with(
+                "nearest",
+                nearest.map(|(sat, angle)| Json::object().with("sat", sat.0).with("angle", angle)),
+            ),
+            Self::AllVisibleFull { sats } => reason("all_visible_full").with("sats", sat_ids(sats)),
+            Self::Blocked { full, blocked } => {
+                let blocked: Vec<Json> = blocked
+                    .iter()
+                    .map(|sat| {
+                        let blockers: Vec<Json> = sat
+                            .blockers
+                            .iter()
+                            .map(|b| {
+                                Json::object()
+                                    .with("color", b.color as i32)
+                                    .with("user", b.user.0)
+                                    .with("angle", b.angle)
+                            })
+                            .collect();
+                        Json::object()
+                            .with("sat", sat.sat.0)
+                            .with("load", sat.load)
+                            .with("blockers", blockers)
+                    })
+                    .collect();
+                reason("blocked")
+                    .with("full", sat_ids(full))
+                    .with("blocked", blocked)
+            }
+            Self::Assignable { sat, color } => reason("assignable")
+                .with("sat", sat.0)
+                .with("color", *color as i32),
+        };
+        json.with("message", self.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_scenario;
+
+    // A satellite 550 km above (6371, 0, 0), five users around the point beneath it,
+    // 0.2° of longitude (about 2° from the satellite) apart, and one on the far side.
+    fn scenario() -> Scenario {
+        let mut text = String::from("min_coverage 0\nsat 1 6921 0 0\n");
+        for i in 0..5 {
+            let longitude = (i as f32 * 0.2).to_radians();
+            text += &format!(
+                "user {} {} {} 0\n",
+                i,
+                6371.0 * longitude.cos(),
+                6371.0 * longitude.sin()
+            );
+        }
+        text += "user 100 -6371 0 0\n";
+        parse_scenario(&text, "explain.txt").unwrap()
+    }
+
+    fn assign(assignments: &[(u64, u64, i32)]) -> SolutionMap {
+        assignments
+            .iter()
+            .map(|&(user, sat, color)| (User(user), (Sat(sat), Color::from_id(color))))
+            .collect()
+    }
+
+    #[test]
+    fn test_reasons() {
+        let s = scenario();
+        let solution = assign(&[(0, 1, 1), (1, 1, 2), (2, 1, 3), (3, 1, 4)]);
+        assert_eq!(explain(&s, &solution, User(7)), None);
+        assert_eq!(
+            explain(&s, &solution, User(2)),
+            Some(Explanation::Served {
+                sat: Sat(1),
+                color: Color::C
+            })
+        );
+
+        let Some(Explanation::NoVisibleSat {
+            nearest: Some((sat, angle)),
+        }) = explain(&s, &solution, User(100))
+        else {
+            panic!("user 100 sees a satellite")
+        };
+        assert_eq!(sat, Sat(1));
+        assert!(angle > 90.0, "{}", angle);
+
+        let Some(Explanation::Blocked { full, blocked }) = explain(&s, &solution, User(4)) else {
+            panic!("user 4 isn't blocked")
+        };
+        assert!(full.is_empty());
+        assert_eq!((blocked[0].sat, blocked[0].load), (Sat(1), 4));
+        let blockers: Vec<(Color, User)> = blocked[0]
+            .blockers
+            .iter()
+            .map(|b| (b.color, b.user))
+            .collect();
+        assert_eq!(
+            blockers,
+            vec![
+                (Color::A, User(0)),
+                (Color::B, User(1)),
+                (Color::C, User(2)),
+                (Color::D, User(3))
+            ]
+        );
+        let text = explain(&s, &solution, User(4)).unwrap().to_string();
+        assert!(text.contains("\n    color 4: user 3 ("), "{}", text);
+
+        let solution = assign(&[(0, 1, 1), (1, 1, 2), (2, 1, 2)]);
+        assert_eq!(
+            explain(&s, &solution, User(4)),
+            Some(Explanation::Assignable {
+                sat: Sat(1),
+                color: Color::C
+            })
+        );
+    }
+
+    #[test]
+    fn test_full_satellites() {
+        // 33 users in a row under the satellite.
+        let mut text = String::from("sat 1 6921 0 0\n");
+        for i in 0..33 {
+            let longitude = (i as f32 * 0.1).to_radians();
+            text += &format!(
+                "user {} {} {} {}\n",
+                i,
+                6371.0 * longitude.cos(),
+                6371.0 * longitude.sin(),
+                (i % 2) as f32
+            );
+        }
+        let s = parse_scenario(&text, "full.txt").unwrap();
+        let solution: SolutionMap = (0..32)
+            .map(|i| (User(i), (Sat(1), COLORS[i as usize % 4])))
+            .collect();
+        let explanation = explain(&s, &solution, User(32)).unwrap();
+        assert_eq!(
+            explanation,
+            Explanation::AllVisibleFull { sats: vec![Sat(1)] }
+        );
+        assert_eq!(
+            explanation.to_json().to_string(),
+            "{\"reason\":\"all_visible_full\",\"sats\":[1],\"message\":\
+             \"Unserved: every visible satellite already serves 32 users (1)\"}"
+        );
+    }
+}
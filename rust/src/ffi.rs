@@ -0,0 +1,433 @@
+//! The C interface declared in `include/beam_planner.h`.
+//!
+//! A problem is built up with users and satellites, which take ids 0, 1, 2… in the
+//! order they are added, then solved and read back as a flat array of assignments.
+//! Every function taking a problem accepts `NULL` and reports `BP_INVALID_ARGUMENT` (or
+//! returns zero) rather than crashing, and a panic inside the solver is reported as
+//! `BP_ERROR` instead of unwinding into C.
+//!
+//! Two limits, which the header spells out:
+//!
+//! - Only the minimum coverage can be set. The beam angle, users per satellite, beam
+//!   separation and colors are the fixed rules in `util`, which the solvers and the
+//!   verifier are built around; the header repeats them for reference.
+//! - The time budget doesn't stop the solver. It runs to completion and `bp_solve` only
+//!   reports afterwards whether it took longer than the budget.
+use crate::config::{self, Solver, SolverConfig};
+use crate::scenario::Scenario;
+use crate::util::{Color, Sat, User, Vector3};
+use std::{
+    collections::BTreeMap,
+    ffi::{c_char, c_int, CStr},
+    panic::{self, AssertUnwindSafe},
+    slice,
+    time::Instant,
+};
+
+type SolutionMap = BTreeMap<User, (Sat, Color)>;
+
+/// Bumped whenever a declaration in the header changes incompatibly.
+pub const ABI_VERSION: u32 = 1;
+
+pub const BP_OK: c_int = 0;
+pub const BP_MISSED: c_int = 1;
+pub const BP_INVALID_ARGUMENT: c_int = 2;
+pub const BP_ERROR: c_int = 3;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BpVector3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BpAssignment {
+    pub user: u32,
+    pub sat: u32,
+    /// 1 to 4 for colors A to D.
+    pub color: u8,
+}
+
+/// Opaque to C.
+#[derive(Debug, Default)]
+pub struct BpProblem {
+    scenario: Scenario,
+    solver: Solver,
+    config: SolverConfig,
+    solution: SolutionMap,
+    seconds: f64,
+}
+
+impl BpProblem {
+    // Changing the problem invalidates the last plan.
+    fn reset(&mut self) {
+        self.solution.clear();
+        self.seconds = 0.0;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn bp_abi_version() -> u32 {
+    ABI_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn bp_problem_new() -> *mut BpProblem {
+    Box::into_raw(Box::default())
+}
+
+/// # Safety
+///
+/// `problem` is `NULL` or came from `bp_problem_new` and hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bp_problem_free(problem: *mut BpProblem) {
+    if !problem.is_null() {
+        drop(Box::from_raw(problem));
+    }
+}
+
+fn add<K: Ord>(
+    positions: &mut BTreeMap<K, Vector3>,
+    id: impl Fn(u64) -> K,
+    added: *const BpVector3,
+    count: usize,
+) -> Result<(), c_int> {
+    if count == 0 {
+        return Ok(());
+    }
+    if added.is_null() {
+        return Err(BP_INVALID_ARGUMENT);
+    }
+    // SAFETY: the caller passes `count` readable positions.
+    let added = unsafe { slice::from_raw_parts(added, count) };
+    if added
+        .iter()
+        .any(|v| !(v.x.is_finite() && v.y.is_finite() && v.z.is_finite()))
+    {
+        return Err(BP_INVALID_ARGUMENT);
+    }
+    let first = positions.len() as u64;
+    for (n, v) in (first..).zip(added) {
+        positions.insert(id(n), Vector3::new(v.x, v.y, v.z));
+    }
+    Ok(())
+}
+
+fn status(result: Result<(), c_int>) -> c_int {
+    result.err().unwrap_or(BP_OK)
+}
+
+/// Adds `count` users, numbered on from the users already added.
+///
+/// # Safety
+///
+/// `problem` is `NULL` or a live problem, and `positions` points to `count` positions.
+#[no_mangle]
+pub unsafe extern "C" fn bp_add_users(
+    problem: *mut BpProblem,
+    positions: *const BpVector3,
+    count: usize,
+) -> c_int {
+    let Some(problem) = problem.as_mut() else {
+        return BP_INVALID_ARGUMENT;
+    };
+    let result = add(&mut problem.scenario.users, User, positions, count);
+    problem.reset();
+    status(result)
+}
+
+/// Adds `count` satellites, numbered on from the satellites already added.
+///
+/// # Safety
+///
+/// `problem` is `NULL` or a live problem, and `positions` points to `count` positions.
+#[no_mangle]
+pub unsafe extern "C" fn bp_add_sats(
+    problem: *mut BpProblem,
+    positions: *const BpVector3,
+    count: usize,
+) -> c_int {
+    let Some(problem) = problem.as_mut() else {
+        return BP_INVALID_ARGUMENT;
+    };
+    let result = add(&mut problem.scenario.sats, Sat, positions, count);
+    problem.reset();
+    status(result)
+}
+
+/// Sets the share of users `bp_solve` must serve, from 0 to 1. Defaults to 1.
+///
+/// # Safety
+///
+/// `problem` is `NULL` or a live problem.
+#[no_mangle]
+pub unsafe extern "C" fn bp_set_min_coverage(problem: *mut BpProblem, min_coverage: f64) -> c_int {
+    let Some(problem) = problem.as_mut() else {
+        return BP_INVALID_ARGUMENT;
+    };
+    if !(0.0..=1.0).contains(&min_coverage) {
+        return BP_INVALID_ARGUMENT;
+    }
+    problem.scenario.min_coverage = min_coverage as f32;
+    BP_OK
+}
+
+/// Picks the solver by its command line name, such as `"v/fewest"` or `"e"`.
+///
+/// # Safety
+///
+/// `problem` is `NULL` or a live problem, and `name` is `NULL` or a NUL-terminated
+/// string.
+#[no_mangle]
+pub unsafe extern "C" fn bp_set_solver(problem: *mut BpProblem, name: *const c_char) -> c_int {
+    let Some(problem) = problem.as_mut() else {
+        return BP_INVALID_ARGUMENT;
+    };
+    if name.is_null() {
+        return BP_INVALID_ARGUMENT;
+    }
+    match CStr::from_ptr(name).to_str().map(str::parse::<Solver>) {
+        Ok(Ok(solver)) => {
+            problem.solver = solver;
+            BP_OK
+        }
+        _ => BP_INVALID_ARGUMENT,
+    }
+}
+
+/// Sets `SolverConfig::seed` and `SolverConfig::threads`.
+///
+/// # Safety
+///
+/// `problem` is `NULL` or a live problem.
+#[no_mangle]
+pub unsafe extern "C" fn bp_set_config(
+    problem: *mut BpProblem,
+    seed: u64,
+    threads: usize,
+) -> c_int {
+    let Some(problem) = problem.as_mut() else {
+        return BP_INVALID_ARGUMENT;
+    };
+    let config = SolverConfig { seed, threads };
+    if config.validate().is_err() {
+        return BP_INVALID_ARGUMENT;
+    }
+    problem.config = config;
+    BP_OK
+}
+
+/// Solves the problem, replacing any earlier plan. The solvers can't be interrupted, so
+/// `time_budget` doesn't limit the run: it is checked afterwards, as
+/// `solve --time-budget` does. Returns
+/// `BP_MISSED` when the plan breaks a rule, serves too few users or took too long; it
+/// can still be read.
+///
+/// # Safety
+///
+/// `problem` is `NULL` or a live problem.
+#[no_mangle]
+pub unsafe extern "C" fn bp_solve(problem: *mut BpProblem, time_budget: f64) -> c_int {
+    let Some(problem) = problem.as_mut() else {
+        return BP_INVALID_ARGUMENT;
+    };
+    let Ok(time_budget) = config::time_budget(time_budget) else {
+        return BP_INVALID_ARGUMENT;
+    };
+    problem.reset();
+    let solved = panic::catch_unwind(AssertUnwindSafe(|| {
+        let start = Instant::now();
+        let solution = problem.solver.solve(&problem.scenario, &problem.config);
+        let duration = start.elapsed();
+        // `verify` also reports low coverage.
+        let passed = problem.scenario.verify(&solution).is_empty() && duration <= time_budget;
+        (solution, duration, passed)
+    }));
+    let Ok((solution, duration, passed)) = solved else {
+        return BP_ERROR;
+    };
+    problem.solution = solution;
+    problem.seconds = duration.as_secs_f64();
+    if passed {
+        BP_OK
+    } else {
+        BP_MISSED
+    }
+}
+
+/// How long the last `bp_solve` took, in seconds.
+///
+/// # Safety
+///
+/// `problem` is `NULL` or a live problem.
+#[no_mangle]
+pub unsafe extern "C" fn bp_solve_seconds(problem: *const BpProblem) -> f64 {
+    problem.as_ref().map_or(0.0, |problem| problem.seconds)
+}
+
+/// How many users the last `bp_solve` served.
+///
+/// # Safety
+///
+/// `problem` is `NULL` or a live problem.
+#[no_mangle]
+pub unsafe extern "C" fn bp_assignment_count(problem: *const BpProblem) -> usize {
+    problem.as_ref().map_or(0, |problem| problem.solution.len())
+}
+
+/// Copies up to `capacity` assignments, by user id, into `out` and returns how many it
+/// copied.
+///
+/// # Safety
+///
+/// `problem` is `NULL` or a live problem, and `out` has room for `capacity`
+/// assignments.
+#[no_mangle]
+pub unsafe extern "C" fn bp_get_assignments(
+    problem: *const BpProblem,
+    out: *mut BpAssignment,
+    capacity: usize,
+) -> usize {
+    let Some(problem) = problem.as_ref() else {
+        return 0;
+    };
+    if out.is_null() || capacity == 0 {
+        return 0;
+    }
+    let out = slice::from_raw_parts_mut(out, capacity);
+    let mut copied = 0;
+    for (slot, (user, (sat, color))) in out.iter_mut().zip(&problem.solution) {
+        *slot = BpAssignment {
+            user: user.0 as u32,
+            sat: sat.0 as u32,
+            color: *color as u8,
+        };
+        copied += 1;
+    }
+    copied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{
+        MAX_ALLOWABLE_BEAM_ANGLE, MAX_ALLOWED_USERS, MAX_COLOR_OPTIONS, MINIMUM_BEAM_ANGLE,
+    };
+    use std::ptr;
+
+    fn positions(map: &BTreeMap<impl Copy, Vector3>) -> Vec<BpVector3> {
+        map.values()
+            .map(|v| {
+                let [x, y, z] = v.to_array();
+                BpVector3 { x, y, z }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_solve_through_the_c_interface() {
+        let scenario = Scenario::new("../test/02_five_users.txt").unwrap();
+        let (users, sats) = (positions(&scenario.users), positions(&scenario.sats));
+        unsafe {
+            let problem = bp_problem_new();
+            assert_eq!(bp_add_users(problem, users.as_ptr(), 2), BP_OK);
+            assert_eq!(bp_add_users(problem, users[2..].as_ptr(), 3), BP_OK);
+            assert_eq!(bp_add_sats(problem, sats.as_ptr(), sats.len()), BP_OK);
+            assert_eq!(bp_set_solver(problem, c"e/least".as_ptr()), BP_OK);
+            assert_eq!(bp_set_solver(problem, c"x".as_ptr()), BP_INVALID_ARGUMENT);
+            assert_eq!(bp_set_config(problem, 0, usize::MAX), BP_INVALID_ARGUMENT);
+            assert_eq!(bp_set_config(problem, 0, 1), BP_OK);
+            assert_eq!(bp_set_min_coverage(problem, 2.0), BP_INVALID_ARGUMENT);
+
+            assert_eq!(bp_set_min_coverage(problem, 1.0), BP_OK);
+            assert_eq!(bp_solve(problem, 60.0), BP_MISSED);
+            assert_eq!(bp_set_min_coverage(problem, 0.8), BP_OK);
+            assert_eq!(bp_solve(problem, 60.0), BP_OK);
+            // Longer than a `Duration` holds, so it can't be missed.
+            assert_eq!(bp_solve(problem, 1e20), BP_OK);
+            assert_eq!(bp_solve(problem, f64::INFINITY), BP_OK);
+            assert_eq!(bp_solve(problem, -1.0), BP_INVALID_ARGUMENT);
+            assert_eq!(bp_solve(problem, f64::NAN), BP_INVALID_ARGUMENT);
+            assert_eq!(bp_assignment_count(problem), 4);
+
+            let mut out = [BpAssignment::default(); 8];
+            assert_eq!(bp_get_assignments(problem, out.as_mut_ptr(), 3), 3);
+            assert_eq!(bp_get_assignments(problem, out.as_mut_ptr(), 8), 4);
+            let solution: SolutionMap = out[..4]
+                .iter()
+                .map(|a| {
+                    let color = Color::from_id(a.color as i32);
+                    (User(a.user as u64), (Sat(a.sat as u64), color))
+                })
+                .collect();
+            let expected = Solver::new(
+                crate::config::Engine::E,
+                crate::config::ScoringChoice::LeastInterference,
+            )
+            .solve(
+                &scenario,
+                &SolverConfig {
+                    seed: 0,
+                    threads: 1,
+                },
+            );
+            assert_eq!(solution, expected);
+
+            // Like a C `malloc`, the buffer can start out uninitialized.
+            let mut uninit = Vec::<BpAssignment>::with_capacity(8);
+            assert_eq!(bp_get_assignments(problem, uninit.as_mut_ptr(), 8), 4);
+            uninit.set_len(4);
+            assert_eq!(uninit, out[..4]);
+
+            // A new user drops the plan.
+            assert_eq!(bp_add_users(problem, users.as_ptr(), 1), BP_OK);
+            assert_eq!(bp_assignment_count(problem), 0);
+            bp_problem_free(problem);
+        }
+    }
+
+    #[test]
+    fn test_null_arguments() {
+        unsafe {
+            assert_eq!(
+                bp_add_users(ptr::null_mut(), ptr::null(), 0),
+                BP_INVALID_ARGUMENT
+            );
+            assert_eq!(bp_solve(ptr::null_mut(), 1.0), BP_INVALID_ARGUMENT);
+            assert_eq!(bp_assignment_count(ptr::null()), 0);
+            bp_problem_free(ptr::null_mut());
+
+            let problem = bp_problem_new();
+            assert_eq!(bp_add_sats(problem, ptr::null(), 1), BP_INVALID_ARGUMENT);
+            assert_eq!(bp_add_sats(problem, ptr::null(), 0), BP_OK);
+            assert_eq!(bp_set_solver(problem, ptr::null()), BP_INVALID_ARGUMENT);
+            bp_problem_free(problem);
+        }
+    }
+
+    #[test]
+    fn test_header_matches_the_rules() {
+        let header = include_str!("../include/beam_planner.h");
+        for (name, value) in [
+            ("BP_ABI_VERSION", ABI_VERSION.to_string()),
+            ("BP_MAX_BEAM_ANGLE", MAX_ALLOWABLE_BEAM_ANGLE.to_string()),
+            ("BP_MIN_SEPARATION", MINIMUM_BEAM_ANGLE.to_string()),
+            ("BP_MAX_USERS_PER_SAT", MAX_ALLOWED_USERS.to_string()),
+            ("BP_COLORS", MAX_COLOR_OPTIONS.to_string()),
+        ] {
+            let line = header
+                .lines()
+                .find(|line| line.starts_with(&format!("#define {} ", name)))
+                .unwrap_or_else(|| panic!("no {}", name));
+            assert_eq!(
+                line.split_whitespace().nth(2),
+                Some(value.as_str()),
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,1259 @@
+use crate::json::{self, Node, Value};
+use crate::orbit::{Epoch, KeplerianElements, Orbit};
+use crate::parse::{self, Duplicate, Fields, Located, ParseError, ParseErrorKind};
+use crate::scenario::Scenario;
+use crate::util::{Color, Sat, User, Vector3};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+    io::{self, Write},
+    str::FromStr,
+};
+
+type SolutionMap = BTreeMap<User, (Sat, Color)>;
+
+/// File formats for scenarios and solutions. All of them write floats in their shortest
+/// exact form, so converting between them never changes a position.
+///
+/// Text is the `parse` module's format. JSON scenarios are an object with optional
+/// `min_coverage`, `epoch` (a Julian date), `sats` and `users` arrays of
+/// `{"id": 1, "position": [x, y, z]}` and a `weights` array of `{"id": 1, "weight": 2}`;
+/// JSON solutions are an array of `{"user": 1, "sat": 2, "color": 3}`. CSV scenarios have
+/// a `kind,id,x,y,z` header and one row per satellite or user, with `min_coverage`,
+/// `epoch` and `weight` rows carrying their value in the `x` column; CSV solutions have
+/// a `user,sat,color` header.
+///
+/// Satellites with orbits are written as their orbits: a JSON satellite has a `kepler`
+/// object with the `KeplerianElements` fields or a `tle` array of its two lines in
+/// place of `position`, and a CSV one is a `kepler` row with the elements after the id
+/// or two `tle` rows with a line each in the `x` column. Reading places them at the
+/// scenario's epoch, so writing them needs one.
+///
+/// With the `serde` feature, `Scenario` serializes to the same JSON, and `schema` has
+/// the helpers for solutions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+}
+
+impl Format {
+    /// The format a file's extension implies: `.json`, `.csv`, and text for anything
+    /// else.
+    pub fn from_path(path: &str) -> Self {
+        let extension = std::path::Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("json") => Self::Json,
+            Some("csv") => Self::Csv,
+            _ => Self::Text,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        match name {
+            "text" | "txt" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!(
+                "unknown format {:?}, expected text, json or csv",
+                name
+            )),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Json => write!(f, "json"),
+            Self::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+const SCENARIO_CSV_HEADER: &str = "kind,id,x,y,z";
+const SOLUTION_CSV_HEADER: &str = "user,sat,color";
+
+/// Reads the scenario file at `path` in `format`.
+pub fn read_scenario(path: &str, format: Format) -> Result<Scenario, ParseError> {
+    read_scenario_lenient(path, format)
+        .and_then(|scenario| parse::reject_duplicates(scenario, path))
+}
+
+/// Parses a scenario in `format`, using `file` in errors. An id defined twice is an
+/// error.
+pub fn parse_scenario(text: &str, file: &str, format: Format) -> Result<Scenario, ParseError> {
+    parse_scenario_lenient(text, file, format)
+        .and_then(|scenario| parse::reject_duplicates(scenario, file))
+}
+
+/// Like `read_scenario`, but see `parse_scenario_lenient`.
+pub fn read_scenario_lenient(path: &str, format: Format) -> Result<Scenario, ParseError> {
+    let text = std::fs::read_to_string(path).map_err(|error| parse::io_error(path, error))?;
+    parse_scenario_lenient(&text, path, format)
+}
+
+/// Like `parse_scenario`, but records ids defined twice in `Scenario::duplicates`, as
+/// `parse::parse_scenario_lenient` does.
+pub fn parse_scenario_lenient(
+    text: &str,
+    file: &str,
+    format: Format,
+) -> Result<Scenario, ParseError> {
+    match format {
+        Format::Text => parse::parse_scenario_lenient(text, file),
+        Format::Json => scenario_from_json(text).map_err(parse::in_file(file)),
+        Format::Csv => scenario_from_csv(text).map_err(parse::in_file(file)),
+    }
+}
+
+/// Reads the solution file at `path` in `format`.
+pub fn read_solution(path: &str, format: Format) -> Result<SolutionMap, ParseError> {
+    let text = std::fs::read_to_string(path).map_err(|error| parse::io_error(path, error))?;
+    parse_solution(&text, path, format)
+}
+
+/// Parses a solution in `format`, using `file` in errors.
+pub fn parse_solution(text: &str, file: &str, format: Format) -> Result<SolutionMap, ParseError> {
+    match format {
+        Format::Text => parse::parse_solution(text, file),
+        Format::Json => solution_from_json(text).map_err(parse::in_file(file)),
+        Format::Csv => solution_from_csv(text).map_err(parse::in_file(file)),
+    }
+}
+
+/// Writes `scenario` in `format`. Satellites with orbits are written as their orbits,
+/// which need the scenario's epoch to be read back.
+pub fn write_scenario(out: &mut impl Write, scenario: &Scenario, format: Format) -> io::Result<()> {
+    if !scenario.orbits.is_empty() && scenario.epoch.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "satellites with orbits need an epoch",
+        ));
+    }
+    match format {
+        Format::Text => {
+            writeln!(out, "min_coverage {}", scenario.min_coverage)?;
+            if let Some(epoch) = scenario.epoch {
+                writeln!(out, "epoch {}", epoch)?;
+            }
+            for sat in sat_ids(scenario) {
+                match scenario.orbits.get(&sat) {
+                    Some(Orbit::Kepler(elements)) => {
+                        writeln!(out, "kepler {} {}", sat, kepler_fields(elements, " "))?
+                    }
+                    Some(Orbit::Sgp4 { tle, .. }) => {
+                        for line in tle {
+                            writeln!(out, "tle {} {}", sat, line)?;
+                        }
+                    }
+                    None => {
+                        let [x, y, z] = scenario.sats[&sat].to_array();
+                        writeln!(out, "sat {} {} {} {}", sat, x, y, z)?;
+                    }
+                }
+            }
+            for (user, pos) in &scenario.users {
+                let [x, y, z] = pos.to_array();
+                writeln!(out, "user {} {} {} {}", user, x, y, z)?;
+            }
+            for (user, weight) in &scenario.weights {
+                writeln!(out, "weight {} {}", user, weight)?;
+            }
+        }
+        Format::Json => {
+            writeln!(out, "{{")?;
+            writeln!(out, "  \"min_coverage\": {},", scenario.min_coverage)?;
+            if let Some(epoch) = scenario.epoch {
+                writeln!(out, "  \"epoch\": {},", epoch)?;
+            }
+            let sats = sat_ids(scenario).into_iter().map(|sat| {
+                let definition = match scenario.orbits.get(&sat) {
+                    Some(Orbit::Kepler(k)) => format!(
+                        "\"kepler\": {{\"semi_major_axis\": {}, \"eccentricity\": {}, \
+                         \"inclination\": {}, \"raan\": {}, \"arg_of_perigee\": {}, \
+                         \"mean_anomaly\": {}, \"epoch\": {}}}",
+                        k.semi_major_axis,
+                        k.eccentricity,
+                        k.inclination,
+                        k.raan,
+                        k.arg_of_perigee,
+                        k.mean_anomaly,
+                        k.epoch
+                    ),
+                    Some(Orbit::Sgp4 { tle, .. }) => format!(
+                        "\"tle\": [{}, {}]",
+                        json::quote(&tle[0]),
+                        json::quote(&tle[1])
+                    ),
+                    None => json_position(&scenario.sats[&sat]),
+                };
+                format!("{{\"id\": {}, {}}}", sat, definition)
+            });
+            write_json_items(out, "sats", sats)?;
+            writeln!(out, ",")?;
+            let users = scenario
+                .users
+                .iter()
+                .map(|(user, pos)| format!("{{\"id\": {}, {}}}", user, json_position(pos)));
+            write_json_items(out, "users", users)?;
+            if !scenario.weights.is_empty() {
+                write!(out, ",\n  \"weights\": [")?;
+                for (i, (user, weight)) in scenario.weights.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { "," };
+                    write!(
+                        out,
+                        "{}\n    {{\"id\": {}, \"weight\": {}}}",
+                        separator, user, weight
+                    )?;
+                }
+                write!(out, "\n  ]")?;
+            }
+            writeln!(out, "\n}}")?;
+        }
+        Format::Csv => {
+            writeln!(out, "{}", SCENARIO_CSV_HEADER)?;
+            writeln!(out, "min_coverage,,{},,", scenario.min_coverage)?;
+            if let Some(epoch) = scenario.epoch {
+                writeln!(out, "epoch,,{},,", epoch)?;
+            }
+            for sat in sat_ids(scenario) {
+                match scenario.orbits.get(&sat) {
+                    Some(Orbit::Kepler(elements)) => {
+                        writeln!(out, "kepler,{},{}", sat, kepler_fields(elements, ","))?
+                    }
+                    Some(Orbit::Sgp4 { tle, .. }) => {
+                        for line in tle {
+                            writeln!(out, "tle,{},{},,", sat, line)?;
+                        }
+                    }
+                    None => {
+                        let [x, y, z] = scenario.sats[&sat].to_array();
+                        writeln!(out, "sat,{},{},{},{}", sat, x, y, z)?;
+                    }
+                }
+            }
+            for (user, pos) in &scenario.users {
+                let [x, y, z] = pos.to_array();
+                writeln!(out, "user,{},{},{},{}", user, x, y, z)?;
+            }
+            for (user, weight) in &scenario.weights {
+                writeln!(out, "weight,{},{},,", user, weight)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Satellites with positions or orbits, in id order.
+fn sat_ids(scenario: &Scenario) -> BTreeSet<Sat> {
+    scenario
+        .sats
+        .keys()
+        .chain(scenario.orbits.keys())
+        .copied()
+        .collect()
+}
+
+fn kepler_fields(elements: &KeplerianElements, separator: &str) -> String {
+    let k = elements;
+    [
+        k.semi_major_axis,
+        k.eccentricity,
+        k.inclination,
+        k.raan,
+        k.arg_of_perigee,
+        k.mean_anomaly,
+        k.epoch.0,
+    ]
+    .map(|value| value.to_string())
+    .join(separator)
+}
+
+fn json_position(pos: &Vector3) -> String {
+    let [x, y, z] = pos.to_array();
+    format!("\"position\": [{}, {}, {}]", x, y, z)
+}
+
+// Writes `"key": [...]` with one object from `items` per line.
+fn write_json_items(
+    out: &mut impl Write,
+    key: &str,
+    items: impl Iterator<Item = String>,
+) -> io::Result<()> {
+    write!(out, "  {}: [", json::quote(key))?;
+    let mut first = true;
+    for item in items {
+        let separator = if first { "" } else { "," };
+        write!(out, "{}\n    {}", separator, item)?;
+        first = false;
+    }
+    write!(out, "{}]", if first { "" } else { "\n  " })
+}
+
+pub fn write_solution(
+    out: &mut impl Write,
+    solution: &SolutionMap,
+    format: Format,
+) -> io::Result<()> {
+    match format {
+        Format::Text => {
+            for (user, (sat, color)) in solution {
+                writeln!(out, "{} {} {}", user, sat, color)?;
+            }
+        }
+        Format::Json => {
+            write!(out, "[")?;
+            let mut first = true;
+            for (user, (sat, color)) in solution {
+                let separator = if first { "" } else { "," };
+                write!(
+                    out,
+                    "{}\n  {{\"user\": {}, \"sat\": {}, \"color\": {}}}",
+                    separator, user, sat, color
+                )?;
+                first = false;
+            }
+            writeln!(out, "{}]", if first { "" } else { "\n" })?;
+        }
+        Format::Csv => {
+            writeln!(out, "{}", SOLUTION_CSV_HEADER)?;
+            for (user, (sat, color)) in solution {
+                writeln!(out, "{},{},{}", user, sat, color)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Collects satellites, users and weights, keeping the first definition of each id like
+/// the text parser does.
+struct Builder {
+    scenario: Scenario,
+    sat_lines: BTreeMap<Sat, usize>,
+    user_lines: BTreeMap<User, usize>,
+    weight_lines: BTreeMap<User, usize>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            scenario: Scenario::default(),
+            sat_lines: BTreeMap::new(),
+            user_lines: BTreeMap::new(),
+            weight_lines: BTreeMap::new(),
+        }
+    }
+
+    fn sat(&mut self, id: u64, pos: Vector3, line: usize) {
+        if let Some(sat) = self.new_sat(id, line) {
+            self.scenario.sats.insert(sat, pos);
+        }
+    }
+
+    // `None` if the satellite was already defined.
+    fn new_sat(&mut self, id: u64, line: usize) -> Option<Sat> {
+        match self.sat_lines.get(&Sat::new(id)) {
+            Some(&first_line) => {
+                self.duplicate("sat", id, line, first_line);
+                None
+            }
+            None => {
+                self.sat_lines.insert(Sat::new(id), line);
+                Some(Sat::new(id))
+            }
+        }
+    }
+
+    fn user(&mut self, id: u64, pos: Vector3, line: usize) {
+        match self.user_lines.get(&User::new(id)) {
+            Some(&first_line) => self.duplicate("user", id, line, first_line),
+            None => {
+                self.user_lines.insert(User::new(id), line);
+                self.scenario.users.insert(User::new(id), pos);
+            }
+        }
+    }
+
+    fn weight(&mut self, id: u64, weight: f32, line: usize) {
+        match self.weight_lines.get(&User::new(id)) {
+            Some(&first_line) => self.duplicate("weight", id, line, first_line),
+            None => {
+                self.weight_lines.insert(User::new(id), line);
+                self.scenario.weights.insert(User::new(id), weight);
+            }
+        }
+    }
+
+    fn duplicate(&mut self, kind: &'static str, id: u64, line: usize, first_line: usize) {
+        self.scenario.duplicates.push(Duplicate {
+            kind,
+            id,
+            line,
+            first_line,
+        });
+    }
+}
+
+fn scenario_from_csv(text: &str) -> Result<Scenario, Located> {
+    let mut builder = Builder::new();
+    let mut tle_lines = parse::TleLines::new();
+    let mut first_orbit_line = None;
+    for (number, line) in csv_rows(text, SCENARIO_CSV_HEADER) {
+        let mut fields = Fields::csv(line, number);
+        let (kind, column) = fields.next("kind")?;
+        match kind {
+            "sat" | "user" => {
+                let (id, _) = fields.id(if kind == "sat" {
+                    "satellite id"
+                } else {
+                    "user id"
+                })?;
+                let pos = Vector3::new(fields.float("x")?, fields.float("y")?, fields.float("z")?);
+                fields.finish()?;
+                if kind == "sat" {
+                    builder.sat(id, pos, number);
+                } else {
+                    builder.user(id, pos, number);
+                }
+            }
+            "min_coverage" => {
+                fields.next("id")?;
+                builder.scenario.min_coverage = fields.float("coverage")?;
+                fields.finish()?;
+            }
+            "epoch" => {
+                fields.next("id")?;
+                builder.scenario.epoch = Some(fields.epoch("epoch")?);
+                fields.finish()?;
+            }
+            "weight" => {
+                let (id, _) = fields.id("user id")?;
+                let weight = fields.float("weight")?;
+                fields.finish()?;
+                builder.weight(id, weight, number);
+            }
+            "kepler" => {
+                let (id, elements) = parse::kepler(&mut fields)?;
+                if let Some(sat) = builder.new_sat(id, number) {
+                    builder.scenario.orbits.insert(sat, Orbit::Kepler(elements));
+                    first_orbit_line.get_or_insert(number);
+                }
+            }
+            "tle" => {
+                let (id, _) = fields.id("satellite id")?;
+                let (text, _) = fields.next("TLE line")?;
+                fields.finish()?;
+                if parse::tle_line(&mut tle_lines, id, text, number, |id| {
+                    builder.new_sat(id, number)
+                }) {
+                    first_orbit_line.get_or_insert(number);
+                }
+            }
+            _ => {
+                return Err(fields.error(column, ParseErrorKind::UnknownDirective(kind.to_string())))
+            }
+        }
+    }
+    parse::place_orbits(
+        &mut builder.scenario,
+        tle_lines,
+        first_orbit_line,
+        &builder.sat_lines,
+    )?;
+    Ok(builder.scenario)
+}
+
+fn solution_from_csv(text: &str) -> Result<SolutionMap, Located> {
+    let mut solution = SolutionMap::new();
+    let mut user_lines = BTreeMap::new();
+    for (number, line) in csv_rows(text, SOLUTION_CSV_HEADER) {
+        let (user, sat, color) =
+            parse::assignment(&mut Fields::csv(line, number), &mut user_lines)?;
+        solution.insert(user, (sat, color));
+    }
+    Ok(solution)
+}
+
+// Numbered non-blank lines, without the header if the first line is one.
+fn csv_rows<'a>(text: &'a str, header: &'a str) -> impl Iterator<Item = (usize, &'a str)> {
+    text.lines()
+        .enumerate()
+        .filter(move |(index, line)| {
+            let line = line.trim();
+            !(line.is_empty() || *index == 0 && line.replace(' ', "") == header)
+        })
+        .map(|(index, line)| (index + 1, line))
+}
+
+// A JSON error at a byte offset, before it's turned into a line and column.
+type JsonError = (usize, ParseErrorKind);
+
+fn locate(text: &str) -> impl Fn(JsonError) -> Located + '_ {
+    move |(offset, kind)| {
+        let before = &text[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        (line, column, kind)
+    }
+}
+
+/// Line numbers of byte offsets, for duplicates in files with one item per line.
+struct Lines(Vec<usize>);
+
+impl Lines {
+    fn new(text: &str) -> Self {
+        Self(text.match_indices('\n').map(|(offset, _)| offset).collect())
+    }
+
+    fn of(&self, offset: usize) -> usize {
+        self.0.partition_point(|&newline| newline < offset) + 1
+    }
+}
+
+fn syntax(node: &Node, message: String) -> JsonError {
+    (node.offset, ParseErrorKind::Syntax(message))
+}
+
+fn members<'a>(
+    node: &'a Node,
+    what: &str,
+    keys: &[&str],
+) -> Result<&'a [(String, Node)], JsonError> {
+    let Value::Object(members) = &node.value else {
+        return Err(syntax(node, format!("{} must be an object", what)));
+    };
+    if let Some((key, value)) = members
+        .iter()
+        .find(|(key, _)| !keys.contains(&key.as_str()))
+    {
+        return Err((value.offset, ParseErrorKind::UnexpectedField(key.clone())));
+    }
+    Ok(members)
+}
+
+fn field<'a>(node: &'a Node, key: &'static str) -> Result<&'a Node, JsonError> {
+    node.get(key)
+        .ok_or((node.offset, ParseErrorKind::MissingField(key)))
+}
+
+fn array<'a>(node: &'a Node, what: &str) -> Result<&'a [Node], JsonError> {
+    match &node.value {
+        Value::Array(items) => Ok(items),
+        _ => Err(syntax(node, format!("{} must be an array", what))),
+    }
+}
+
+fn number<'a>(node: &'a Node, what: &str) -> Result<&'a str, JsonError> {
+    match &node.value {
+        Value::Number(text) => Ok(text),
+        _ => Err(syntax(node, format!("{} must be a number", what))),
+    }
+}
+
+fn id(node: &Node, field: &'static str) -> Result<u64, JsonError> {
+    let text = number(node, field)?;
+    text.parse().map_err(|_| {
+        (
+            node.offset,
+            ParseErrorKind::BadInteger {
+                field,
+                text: text.to_string(),
+            },
+        )
+    })
+}
+
+fn float<T: FromStr + Into<f64> + Copy>(node: &Node, field: &'static str) -> Result<T, JsonError> {
+    let text = number(node, field)?;
+    match text.parse::<T>() {
+        Ok(value) if value.into().is_finite() => Ok(value),
+        _ => Err((
+            node.offset,
+            ParseErrorKind::BadFloat {
+                field,
+                text: text.to_string(),
+            },
+        )),
+    }
+}
+
+fn string<'a>(node: &'a Node, what: &str) -> Result<&'a str, JsonError> {
+    match &node.value {
+        Value::String(text) => Ok(text),
+        _ => Err(syntax(node, format!("{} must be a string", what))),
+    }
+}
+
+fn epoch(node: &Node, what: &str) -> Result<Epoch, JsonError> {
+    Epoch::parse(number(node, what)?).map_err(|error| (node.offset, ParseErrorKind::Orbit(error)))
+}
+
+fn kepler(node: &Node) -> Result<Orbit, JsonError> {
+    members(
+        node,
+        "kepler",
+        &[
+            "semi_major_axis",
+            "eccentricity",
+            "inclination",
+            "raan",
+            "arg_of_perigee",
+            "mean_anomaly",
+            "epoch",
+        ],
+    )?;
+    let elements = KeplerianElements {
+        semi_major_axis: float(field(node, "semi_major_axis")?, "semi-major axis")?,
+        eccentricity: float(field(node, "eccentricity")?, "eccentricity")?,
+        inclination: float(field(node, "inclination")?, "inclination")?,
+        raan: float(
+            field(node, "raan")?,
+            "right ascension of the ascending node",
+        )?,
+        arg_of_perigee: float(field(node, "arg_of_perigee")?, "argument of perigee")?,
+        mean_anomaly: float(field(node, "mean_anomaly")?, "mean anomaly")?,
+        epoch: epoch(field(node, "epoch")?, "element epoch")?,
+    };
+    elements
+        .validate()
+        .map_err(|error| (node.offset, ParseErrorKind::Orbit(error)))?;
+    Ok(Orbit::Kepler(elements))
+}
+
+fn tle(node: &Node) -> Result<Orbit, JsonError> {
+    let [line_1, line_2] = array(node, "tle")? else {
+        return Err(syntax(node, "tle must have 2 lines".to_string()));
+    };
+    Orbit::from_tle(string(line_1, "a TLE line")?, string(line_2, "a TLE line")?)
+        .map_err(|error| (node.offset, ParseErrorKind::Orbit(error)))
+}
+
+fn position(node: &Node) -> Result<Vector3, JsonError> {
+    match array(node, "position")? {
+        [x, y, z] => Ok(Vector3::new(float(x, "x")?, float(y, "y")?, float(z, "z")?)),
+        _ => Err(syntax(node, "position must have 3 coordinates".to_string())),
+    }
+}
+
+fn scenario_from_json(text: &str) -> Result<Scenario, Located> {
+    let parse =
+        || -> Result<(Builder, Option<usize>), JsonError> {
+            let root = json::parse(text)
+                .map_err(|(offset, message)| (offset, ParseErrorKind::Syntax(message)))?;
+            let lines = Lines::new(text);
+            let mut builder = Builder::new();
+            let mut first_orbit_line = None;
+            for (key, value) in members(
+                &root,
+                "a scenario",
+                &["min_coverage", "epoch", "sats", "users", "weights"],
+            )? {
+                match key.as_str() {
+                    "min_coverage" => builder.scenario.min_coverage = float(value, "coverage")?,
+                    "epoch" => builder.scenario.epoch = Some(epoch(value, "epoch")?),
+                    "weights" => {
+                        for item in array(value, key)? {
+                            members(item, "a weight", &["id", "weight"])?;
+                            builder.weight(
+                                id(field(item, "id")?, "user id")?,
+                                float(field(item, "weight")?, "weight")?,
+                                lines.of(item.offset),
+                            );
+                        }
+                    }
+                    "sats" => {
+                        for item in array(value, key)? {
+                            let keys = ["id", "position", "kepler", "tle"];
+                            let line = lines.of(item.offset);
+                            let sat = id(field(item, "id")?, "satellite id")?;
+                            match members(item, "a satellite", &keys)? {
+                                [_, (key, definition)] | [(key, definition), _] if key != "id" => {
+                                    let orbit = match key.as_str() {
+                                        "position" => {
+                                            builder.sat(sat, position(definition)?, line);
+                                            continue;
+                                        }
+                                        "kepler" => kepler(definition)?,
+                                        _ => tle(definition)?,
+                                    };
+                                    if let Some(sat) = builder.new_sat(sat, line) {
+                                        builder.scenario.orbits.insert(sat, orbit);
+                                        first_orbit_line.get_or_insert(line);
+                                    }
+                                }
+                                _ => return Err(syntax(
+                                    item,
+                                    "a satellite needs an id and one of position, kepler or tle"
+                                        .to_string(),
+                                )),
+                            }
+                        }
+                    }
+                    _ => {
+                        for item in array(value, key)? {
+                            members(item, "a user", &["id", "position"])?;
+                            builder.user(
+                                id(field(item, "id")?, "user id")?,
+                                position(field(item, "position")?)?,
+                                lines.of(item.offset),
+                            );
+                        }
+                    }
+                }
+            }
+            Ok((builder, first_orbit_line))
+        };
+    let (mut builder, first_orbit_line) = parse().map_err(locate(text))?;
+    parse::place_orbits(
+        &mut builder.scenario,
+        parse::TleLines::new(),
+        first_orbit_line,
+        &builder.sat_lines,
+    )?;
+    Ok(builder.scenario)
+}
+
+fn solution_from_json(text: &str) -> Result<SolutionMap, Located> {
+    let parse = || -> Result<SolutionMap, JsonError> {
+        let root = json::parse(text)
+            .map_err(|(offset, message)| (offset, ParseErrorKind::Syntax(message)))?;
+        let lines = Lines::new(text);
+        let mut solution = SolutionMap::new();
+        let mut user_lines = BTreeMap::new();
+        for item in array(&root, "a solution")? {
+            members(item, "an assignment", &["user", "sat", "color"])?;
+            let user = User::new(id(field(item, "user")?, "user id")?);
+            let sat = Sat::new(id(field(item, "sat")?, "satellite id")?);
+            let color_node = field(item, "color")?;
+            let color = id(color_node, "color")?;
+            if !(1..=4).contains(&color) {
+                return Err((
+                    color_node.offset,
+                    ParseErrorKind::BadColor(color.to_string()),
+                ));
+            }
+            if let Some(first_line) = user_lines.insert(user, lines.of(item.offset)) {
+                return Err((
+                    item.offset,
+                    ParseErrorKind::DuplicateAssignment {
+                        user: user.0,
+                        first_line,
+                    },
+                ));
+            }
+            solution.insert(user, (sat, Color::from_id(color as i32)));
+        }
+        Ok(solution)
+    };
+    parse().map_err(locate(text))
+}
+
+/// `serde` helpers that give scenarios and solutions the JSON schema `Format::Json`
+/// uses, for `#[serde(with = ...)]` on maps: `positions` for satellites and users,
+/// `weights`, and `solution`.
+#[cfg(feature = "serde")]
+pub mod schema {
+    use crate::orbit::{Epoch, KeplerianElements, Orbit};
+    use crate::scenario::Scenario;
+    use crate::util::{Color, Sat, User, Vector3};
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    /// A scenario as `Format::Json` writes it.
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct ScenarioJson {
+        #[serde(default = "full_coverage")]
+        min_coverage: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        epoch: Option<Epoch>,
+        #[serde(default)]
+        sats: Vec<SatJson>,
+        #[serde(default, with = "positions")]
+        users: BTreeMap<User, Vector3>,
+        #[serde(default, with = "weights", skip_serializing_if = "BTreeMap::is_empty")]
+        weights: BTreeMap<User, f32>,
+    }
+
+    fn full_coverage() -> f32 {
+        1.0
+    }
+
+    /// A satellite with exactly one of a position, Keplerian elements or a TLE.
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct SatJson {
+        id: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        position: Option<Vector3>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kepler: Option<KeplerianElements>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tle: Option<[String; 2]>,
+    }
+
+    impl Serialize for Scenario {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if !self.orbits.is_empty() && self.epoch.is_none() {
+                return Err(serde::ser::Error::custom(
+                    "satellites with orbits need an epoch",
+                ));
+            }
+            let sats = super::sat_ids(self).into_iter().map(|sat| {
+                let mut json = SatJson {
+                    id: sat.0,
+                    position: None,
+                    kepler: None,
+                    tle: None,
+                };
+                match self.orbits.get(&sat) {
+                    Some(Orbit::Kepler(elements)) => json.kepler = Some(elements.clone()),
+                    Some(Orbit::Sgp4 { tle, .. }) => json.tle = Some(tle.clone()),
+                    None => json.position = Some(self.sats[&sat]),
+                }
+                json
+            });
+            ScenarioJson {
+                min_coverage: self.min_coverage,
+                epoch: self.epoch,
+                sats: sats.collect(),
+                users: self.users.clone(),
+                weights: self.weights.clone(),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Scenario {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let json = ScenarioJson::deserialize(deserializer)?;
+            let mut scenario = Scenario {
+                min_coverage: json.min_coverage,
+                epoch: json.epoch,
+                users: json.users,
+                weights: json.weights,
+                ..Scenario::default()
+            };
+            for sat in json.sats {
+                let id = Sat(sat.id);
+                if scenario.sats.contains_key(&id) || scenario.orbits.contains_key(&id) {
+                    return Err(D::Error::custom(format!(
+                        "satellite {} is defined twice",
+                        id
+                    )));
+                }
+                match (sat.position, sat.kepler, sat.tle) {
+                    (Some(position), None, None) => {
+                        scenario.sats.insert(id, position);
+                    }
+                    (None, Some(elements), None) => {
+                        elements.validate().map_err(D::Error::custom)?;
+                        scenario.orbits.insert(id, Orbit::Kepler(elements));
+                    }
+                    (None, None, Some([line_1, line_2])) => {
+                        let orbit = Orbit::from_tle(&line_1, &line_2).map_err(D::Error::custom)?;
+                        scenario.orbits.insert(id, orbit);
+                    }
+                    _ => {
+                        return Err(D::Error::custom(format!(
+                            "satellite {} needs one of position, kepler or tle",
+                            id
+                        )))
+                    }
+                }
+            }
+            if scenario.orbits.is_empty() {
+                return Ok(scenario);
+            }
+            let epoch = scenario
+                .epoch
+                .ok_or_else(|| D::Error::custom("satellites with orbits need an epoch"))?;
+            scenario.at(epoch).map_err(D::Error::custom)
+        }
+    }
+
+    /// Satellite and user ids.
+    pub trait Id: Copy + Ord {
+        const KIND: &'static str;
+        fn id(self) -> u64;
+        fn from_id(id: u64) -> Self;
+    }
+
+    impl Id for Sat {
+        const KIND: &'static str = "satellite";
+        fn id(self) -> u64 {
+            self.0
+        }
+        fn from_id(id: u64) -> Self {
+            Self(id)
+        }
+    }
+
+    impl Id for User {
+        const KIND: &'static str = "user";
+        fn id(self) -> u64 {
+            self.0
+        }
+        fn from_id(id: u64) -> Self {
+            Self(id)
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Positioned {
+        id: u64,
+        position: Vector3,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Weighted {
+        id: u64,
+        weight: f32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Assignment {
+        user: u64,
+        sat: u64,
+        color: Color,
+    }
+
+    // Builds a map from `items`, failing on an id that comes twice as the readers do.
+    fn unique<K: Ord, V, E: Error>(
+        kind: &str,
+        items: impl IntoIterator<Item = (u64, K, V)>,
+    ) -> Result<BTreeMap<K, V>, E> {
+        let mut map = BTreeMap::new();
+        for (id, key, value) in items {
+            if map.insert(key, value).is_some() {
+                return Err(E::custom(format!("{} {} is defined twice", kind, id)));
+            }
+        }
+        Ok(map)
+    }
+
+    /// `[{"id": 1, "position": [x, y, z]}, ...]`.
+    pub mod positions {
+        use super::*;
+
+        pub fn serialize<K: Id, S: Serializer>(
+            map: &BTreeMap<K, Vector3>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(map.iter().map(|(key, position)| Positioned {
+                id: key.id(),
+                position: *position,
+            }))
+        }
+
+        pub fn deserialize<'de, K: Id, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<BTreeMap<K, Vector3>, D::Error> {
+            let items = Vec::<Positioned>::deserialize(deserializer)?;
+            unique(
+                K::KIND,
+                items
+                    .into_iter()
+                    .map(|item| (item.id, K::from_id(item.id), item.position)),
+            )
+        }
+    }
+
+    /// `[{"id": 1, "weight": 2}, ...]`.
+    pub mod weights {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            map: &BTreeMap<User, f32>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(map.iter().map(|(user, weight)| Weighted {
+                id: user.0,
+                weight: *weight,
+            }))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<BTreeMap<User, f32>, D::Error> {
+            let items = Vec::<Weighted>::deserialize(deserializer)?;
+            unique(
+                "weight",
+                items
+                    .into_iter()
+                    .map(|item| (item.id, User(item.id), item.weight)),
+            )
+        }
+    }
+
+    /// `[{"user": 1, "sat": 2, "color": 3}, ...]`.
+    pub mod solution {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            solution: &BTreeMap<User, (Sat, Color)>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(solution.iter().map(|(user, (sat, color))| Assignment {
+                user: user.0,
+                sat: sat.0,
+                color: *color,
+            }))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<BTreeMap<User, (Sat, Color)>, D::Error> {
+            let items = Vec::<Assignment>::deserialize(deserializer)?;
+            unique(
+                "user",
+                items
+                    .into_iter()
+                    .map(|item| (item.user, User(item.user), (Sat(item.sat), item.color))),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solution_v;
+
+    const FORMATS: [Format; 3] = [Format::Text, Format::Json, Format::Csv];
+
+    fn write(scenario: &Scenario, format: Format) -> String {
+        let mut out = Vec::new();
+        write_scenario(&mut out, scenario, format).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn assert_same(a: &Scenario, b: &Scenario) {
+        assert_eq!(a.sats, b.sats);
+        assert_eq!(a.users, b.users);
+        assert_eq!(a.min_coverage, b.min_coverage);
+        assert_eq!(a.epoch, b.epoch);
+        assert_eq!(a.weights, b.weights);
+        assert_eq!(a.orbits, b.orbits);
+    }
+
+    #[test]
+    fn test_scenarios_round_trip_through_every_format() {
+        for name in [
+            "01_two_users",
+            "02_five_users",
+            "03_equatorial_band",
+            "05_fifty_thousand_low_coverage",
+        ] {
+            let path = format!("../test/{}.txt", name);
+            let mut original = Scenario::new(&path).unwrap();
+            original.weights.insert(User::new(1), 0.25);
+            let text = write(&original, Format::Text);
+            for format in FORMATS {
+                let converted = parse_scenario(&write(&original, format), "x", format).unwrap();
+                assert_same(&original, &converted);
+                assert_eq!(
+                    write(&converted, Format::Text),
+                    text,
+                    "{} via {}",
+                    name,
+                    format
+                );
+            }
+        }
+    }
+
+    const ORBITS: &str = "\
+min_coverage 0.5
+epoch 2008-09-20T12:25:40Z
+sat 1 6921 0 0
+kepler 2 6921 0.001 53 10 0 0 2460000.25
+tle 3 1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927
+tle 3 2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537
+user 1 6371 0 0
+";
+
+    #[test]
+    fn test_orbits_round_trip_through_every_format() {
+        let original = parse::parse_scenario(ORBITS, "orbit.txt").unwrap();
+        assert_eq!(original.orbits.len(), 2);
+        let written = write(&original, Format::Text);
+        assert!(written.contains("\ntle 3 2 25544  51.6416 247.4627 0006703 130.5360 "));
+        for format in FORMATS {
+            let converted = parse_scenario(&write(&original, format), "x", format).unwrap();
+            assert_same(&original, &converted);
+            assert_eq!(write(&converted, Format::Text), written, "via {}", format);
+        }
+
+        let mut unplaced = original.clone();
+        unplaced.epoch = None;
+        let mut out = Vec::new();
+        assert!(write_scenario(&mut out, &unplaced, Format::Json).is_err());
+    }
+
+    #[test]
+    fn test_solutions_round_trip_through_every_format() {
+        let scenario = Scenario::new("../test/02_five_users.txt").unwrap();
+        let solution = solution_v::solve(&scenario.users, &scenario.sats);
+        assert!(!solution.is_empty());
+        for format in FORMATS.into_iter().chain([Format::Json]) {
+            let mut out = Vec::new();
+            write_solution(&mut out, &solution, format).unwrap();
+            let text = String::from_utf8(out).unwrap();
+            assert_eq!(parse_solution(&text, "x", format).unwrap(), solution);
+        }
+        let mut out = Vec::new();
+        write_solution(&mut out, &SolutionMap::new(), Format::Json).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "[]\n");
+        assert!(parse_solution("[]", "x", Format::Json).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json_matches_serde() {
+        let mut original = Scenario::new("../test/02_five_users.txt").unwrap();
+        original.weights.insert(User::new(1), 0.25);
+        original.epoch = Some(crate::orbit::Epoch(2460000.5));
+
+        let from_format: Scenario = serde_json::from_str(&write(&original, Format::Json)).unwrap();
+        assert_same(&original, &from_format);
+        let from_serde = serde_json::to_string(&original).unwrap();
+        assert_same(
+            &original,
+            &parse_scenario(&from_serde, "x", Format::Json).unwrap(),
+        );
+
+        let duplicate =
+            r#"{"users": [{"id": 1, "position": [1, 0, 0]}, {"id": 1, "position": [0, 1, 0]}]}"#;
+        let error = serde_json::from_str::<Scenario>(duplicate).unwrap_err();
+        assert!(error.to_string().contains("user 1 is defined twice"));
+
+        let orbits = parse::parse_scenario(ORBITS, "orbit.txt").unwrap();
+        let from_format: Scenario = serde_json::from_str(&write(&orbits, Format::Json)).unwrap();
+        assert_same(&orbits, &from_format);
+        let from_serde = serde_json::to_string(&orbits).unwrap();
+        assert_same(
+            &orbits,
+            &parse_scenario(&from_serde, "x", Format::Json).unwrap(),
+        );
+        let mut unplaced = orbits.clone();
+        unplaced.epoch = None;
+        assert!(serde_json::to_string(&unplaced).is_err());
+        let both = r#"{"sats": [{"id": 1, "position": [7000, 0, 0], "tle": ["", ""]}]}"#;
+        let error = serde_json::from_str::<Scenario>(both).unwrap_err();
+        assert!(error.to_string().contains("satellite 1 needs one of"));
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Wrapped(#[serde(with = "schema::solution")] SolutionMap);
+        let solution = solution_v::solve(&original.users, &original.sats);
+        let mut out = Vec::new();
+        write_solution(&mut out, &solution, Format::Json).unwrap();
+        let Wrapped(from_format) = serde_json::from_slice(&out).unwrap();
+        assert_eq!(from_format, solution);
+        let from_serde = serde_json::to_string(&Wrapped(solution.clone())).unwrap();
+        assert_eq!(
+            parse_solution(&from_serde, "x", Format::Json).unwrap(),
+            solution
+        );
+    }
+
+    #[test]
+    fn test_json_and_csv_errors() {
+        let error = |text: &str, format| parse_scenario(text, "bad", format).unwrap_err();
+
+        let e = error(
+            "{\n  \"users\": [{\"id\": 1, \"position\": [1, 2]}]\n}",
+            Format::Json,
+        );
+        assert_eq!((e.line, e.column), (2, 35));
+        let e = error(
+            "{\"users\": [{\"id\": -1, \"position\": [1, 2, 3]}]}",
+            Format::Json,
+        );
+        assert!(matches!(e.kind, ParseErrorKind::BadInteger { .. }));
+        let e = error("{\"user\": []}", Format::Json);
+        assert_eq!(e.kind, ParseErrorKind::UnexpectedField("user".to_string()));
+        let e = error("{\"sats\": [}", Format::Json);
+        assert!(matches!(e.kind, ParseErrorKind::Syntax(_)));
+        assert_eq!((e.line, e.column), (1, 11));
+
+        let e = error("kind,id,x,y,z\nuser,1,2,3\n", Format::Csv);
+        assert_eq!((e.line, e.kind), (2, ParseErrorKind::MissingField("z")));
+        let e = error("user,1,2,3,x\n", Format::Csv);
+        assert_eq!((e.line, e.column), (1, 12));
+        let e = error("beam,1,2,3,4\n", Format::Csv);
+        assert_eq!(e.kind, ParseErrorKind::UnknownDirective("beam".to_string()));
+
+        let csv = "user,1,2,3,4\nuser,1,5,6,7\n";
+        let e = error(csv, Format::Csv);
+        assert!(matches!(
+            e.kind,
+            ParseErrorKind::DuplicateId {
+                kind: "user",
+                id: 1,
+                ..
+            }
+        ));
+        let s = parse_scenario_lenient(csv, "dup", Format::Csv).unwrap();
+        assert_eq!(s.duplicates[0].line, 2);
+        let s = parse_scenario_lenient(
+            "{\"sats\": [\n{\"id\": 1, \"position\": [7000, 0, 0]},\n{\"id\": 1, \"position\": [0, 7000, 0]}]}",
+            "dup",
+            Format::Json,
+        )
+        .unwrap();
+        assert_eq!((s.duplicates[0].line, s.duplicates[0].first_line), (3, 2));
+
+        let e = error(
+            "{\"sats\": [{\"id\": 1, \"position\": [7000, 0, 0], \"tle\": []}]}",
+            Format::Json,
+        );
+        assert_eq!((e.line, e.column), (1, 11));
+        let e = error(
+            "{\"epoch\": 2460000.5, \"sats\": [{\"id\": 1, \"kepler\": {\"semi_major_axis\": 6921}}]}",
+            Format::Json,
+        );
+        assert_eq!(e.kind, ParseErrorKind::MissingField("eccentricity"));
+        let e = error(
+            "{\"sats\": [{\"id\": 1, \"tle\": [\"1 25544U\", \"2 25544\"]}]}",
+            Format::Json,
+        );
+        assert!(matches!(e.kind, ParseErrorKind::Orbit(_)));
+        let e = error("kepler,1,6921,0,53,0,0,0,2460000.5\n", Format::Csv);
+        assert_eq!(e.kind, ParseErrorKind::MissingEpoch);
+
+        let e = parse_solution(
+            "[{\"user\": 1, \"sat\": 2, \"color\": 0}]",
+            "bad",
+            Format::Json,
+        )
+        .unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::BadColor("0".to_string()));
+        let e = parse_solution("user,sat,color\n1,2,3\n1,2,4\n", "bad", Format::Csv).unwrap_err();
+        assert_eq!(e.line, 3);
+    }
+
+    #[test]
+    fn test_format_names() {
+        assert_eq!(Format::from_path("a/b.JSON"), Format::Json);
+        assert_eq!(Format::from_path("b.csv"), Format::Csv);
+        assert_eq!(Format::from_path("b.txt"), Format::Text);
+        assert_eq!(Format::from_path("json"), Format::Text);
+        assert_eq!("csv".parse(), Ok(Format::Csv));
+        assert!("yaml".parse::<Format>().is_err());
+    }
+}
@@ -0,0 +1,726 @@
+use crate::orbit::{Epoch, KeplerianElements, Orbit, OrbitError};
+use crate::scenario::Scenario;
+use crate::solution_v;
+use crate::util::{Sat, User, Vector3};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    f64::consts::{PI, TAU},
+    io::{self, Write},
+};
+
+/// Mean Earth radius in km, the radius the scenario files put users at.
+pub const EARTH_RADIUS: f64 = 6371.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalkerPattern {
+    /// Planes spread over 360° of right ascension, all ascending the same way.
+    Delta,
+    /// Planes spread over 180°, so neighbours at the seam cross in opposite directions.
+    /// Usually used with polar orbits.
+    Star,
+}
+
+/// A Walker constellation `i: t/p/f` of circular orbits: `planes` evenly spaced
+/// planes at `inclination` degrees, each with `sats_per_plane` evenly spaced
+/// satellites, and satellites in neighbouring planes offset by `phasing * 360° / t`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Walker {
+    pub pattern: WalkerPattern,
+    /// Above `EARTH_RADIUS`, in km.
+    pub altitude: f64,
+    pub inclination: f64,
+    pub planes: u64,
+    pub sats_per_plane: u64,
+    /// In `0..planes`.
+    pub phasing: u64,
+    pub epoch: Epoch,
+    /// Satellites are numbered plane by plane from this id.
+    pub first_id: u64,
+}
+
+impl Walker {
+    pub fn validate(&self) -> Result<(), OrbitError> {
+        if self.planes == 0 || self.sats_per_plane == 0 {
+            return Err(OrbitError::InvalidElements(
+                "a Walker constellation needs at least one plane and satellite".to_string(),
+            ));
+        }
+        if self.phasing >= self.planes {
+            return Err(OrbitError::InvalidElements(format!(
+                "Walker phasing {} must be below the plane count {}",
+                self.phasing, self.planes
+            )));
+        }
+        if !(self.altitude > 0.0 && self.altitude.is_finite()) {
+            return Err(OrbitError::InvalidElements(format!(
+                "altitude {} km is not a finite height above the surface",
+                self.altitude
+            )));
+        }
+        if !self.inclination.is_finite() {
+            return Err(OrbitError::InvalidElements(format!(
+                "inclination {} is not a finite angle",
+                self.inclination
+            )));
+        }
+        // `elements` multiplies the phasing by each plane index and numbers satellites
+        // up to `first_id + len - 1`, so all of those must fit in a u64.
+        let fits = self
+            .planes
+            .checked_mul(self.sats_per_plane)
+            .and_then(|len| self.first_id.checked_add(len - 1))
+            .is_some()
+            && self.phasing.checked_mul(self.planes - 1).is_some();
+        if !fits {
+            return Err(OrbitError::InvalidElements(format!(
+                "{} planes of {} satellites with phasing {} from id {} overflow the \
+                 satellite ids",
+                self.planes, self.sats_per_plane, self.phasing, self.first_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Saturates instead of overflowing; `validate` rejects constellations that would.
+    pub fn len(&self) -> u64 {
+        self.planes.saturating_mul(self.sats_per_plane)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Elements of every satellite, in id order.
+    pub fn elements(&self) -> Result<Vec<(Sat, KeplerianElements)>, OrbitError> {
+        self.validate()?;
+        let spread = match self.pattern {
+            WalkerPattern::Delta => 360.0,
+            WalkerPattern::Star => 180.0,
+        };
+        let total = self.len() as f64;
+        let mut sats = Vec::with_capacity(self.len() as usize);
+        for plane in 0..self.planes {
+            for slot in 0..self.sats_per_plane {
+                let mean_anomaly = 360.0 * slot as f64 / self.sats_per_plane as f64
+                    + 360.0 * (self.phasing * plane) as f64 / total;
+                let elements = KeplerianElements {
+                    semi_major_axis: EARTH_RADIUS + self.altitude,
+                    eccentricity: 0.0,
+                    inclination: self.inclination,
+                    raan: spread * plane as f64 / self.planes as f64,
+                    arg_of_perigee: 0.0,
+                    mean_anomaly: mean_anomaly % 360.0,
+                    epoch: self.epoch,
+                };
+                let id = self.first_id + plane * self.sats_per_plane + slot;
+                sats.push((Sat::new(id), elements));
+            }
+        }
+        Ok(sats)
+    }
+
+    /// A scenario with just these satellites, positioned at `epoch`.
+    pub fn scenario(&self) -> Result<Scenario, OrbitError> {
+        let mut scenario = Scenario::default();
+        for (sat, elements) in self.elements()? {
+            scenario.orbits.insert(sat, Orbit::Kepler(elements));
+        }
+        scenario.at(self.epoch)
+    }
+}
+
+/// Writes the scenario's satellites in the scenario format: an `epoch` line, then a
+/// `kepler` line for each satellite with Keplerian elements if `elements` is set, or
+/// else a `sat` line with its position at the epoch.
+pub fn write_sats(out: &mut impl Write, scenario: &Scenario, elements: bool) -> io::Result<()> {
+    if let Some(epoch) = scenario.epoch {
+        writeln!(out, "epoch {}", epoch)?;
+    }
+    for (sat, pos) in &scenario.sats {
+        match scenario.orbits.get(sat) {
+            Some(Orbit::Kepler(k)) if elements => writeln!(
+                out,
+                "kepler {} {} {} {} {} {} {} {}",
+                sat,
+                k.semi_major_axis,
+                k.eccentricity,
+                k.inclination,
+                k.raan,
+                k.arg_of_perigee,
+                k.mean_anomaly,
+                k.epoch
+            )?,
+            _ => {
+                let [x, y, z] = pos.to_array();
+                writeln!(out, "sat {} {} {} {}", sat, x, y, z)?
+            }
+        }
+    }
+    Ok(())
+}
+
+/// SplitMix64. Small, and gives the same numbers on every platform, so a seed always
+/// generates the same scenario.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn uniform(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal, by Box-Muller.
+    pub fn gaussian(&mut self) -> f64 {
+        let radius = (-2.0 * (1.0 - self.uniform()).ln()).sqrt();
+        radius * (TAU * self.uniform()).cos()
+    }
+}
+
+/// A latitude/longitude grid of relative weights, rows from north to south and columns
+/// from 180°W eastwards, each covering an equal span of degrees.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Raster {
+    rows: usize,
+    cols: usize,
+    weights: Vec<f64>,
+}
+
+impl Raster {
+    pub fn new(rows: usize, cols: usize, weights: Vec<f64>) -> Result<Self, Box<dyn Error>> {
+        if rows == 0 || cols == 0 || weights.len() != rows * cols {
+            return Err(format!(
+                "{} weights don't fill a {}x{} grid",
+                weights.len(),
+                rows,
+                cols
+            )
+            .into());
+        }
+        if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+            return Err("Raster weights must be finite and non-negative".into());
+        }
+        if weights.iter().all(|w| *w == 0.0) {
+            return Err("Raster has no cells to place users in".into());
+        }
+        Ok(Self {
+            rows,
+            cols,
+            weights,
+        })
+    }
+
+    /// One row per line of whitespace-separated numbers, such as population counts.
+    pub fn parse_density(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut rows = 0;
+        let mut cols = None;
+        let mut weights = Vec::new();
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let row: Vec<f64> = line
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()?;
+            if *cols.get_or_insert(row.len()) != row.len() {
+                return Err(format!("Raster row {} has {} columns", rows + 1, row.len()).into());
+            }
+            weights.extend(row);
+            rows += 1;
+        }
+        Self::new(rows, cols.unwrap_or(0), weights)
+    }
+
+    /// One row per line and one character per cell: `#` or `1` is land, anything else
+    /// is water.
+    pub fn parse_mask(text: &str) -> Result<Self, Box<dyn Error>> {
+        let lines: Vec<&str> = text
+            .lines()
+            .map(str::trim_end)
+            .filter(|l| !l.is_empty())
+            .collect();
+        let cols = lines.first().map_or(0, |line| line.chars().count());
+        let mut weights = Vec::with_capacity(lines.len() * cols);
+        for (row, line) in lines.iter().enumerate() {
+            if line.chars().count() != cols {
+                return Err(format!("Mask row {} is not {} cells wide", row + 1, cols).into());
+            }
+            weights.extend(
+                line.chars()
+                    .map(|c| if c == '#' || c == '1' { 1.0 } else { 0.0 }),
+            );
+        }
+        Self::new(lines.len(), cols, weights)
+    }
+
+    /// Latitude band of `row` as sines of its southern and northern edges.
+    fn band(&self, row: usize) -> (f64, f64) {
+        let north = 90.0 - 180.0 * row as f64 / self.rows as f64;
+        let south = 90.0 - 180.0 * (row + 1) as f64 / self.rows as f64;
+        (south.to_radians().sin(), north.to_radians().sin())
+    }
+}
+
+/// A Gaussian cluster of users around a point on the surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hotspot {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Standard deviation of the distance from the center along the surface, in km.
+    pub sigma: f64,
+    /// Share of users relative to the other hotspots.
+    pub weight: f64,
+}
+
+impl Hotspot {
+    /// `count` hotspots with uniformly random centers and weights between 0.5 and 1.5.
+    pub fn random(count: usize, sigma: f64, rng: &mut Rng) -> Vec<Self> {
+        (0..count)
+            .map(|_| {
+                let (latitude, longitude) = uniform_lat_lon(rng);
+                Self {
+                    latitude,
+                    longitude,
+                    sigma,
+                    weight: 0.5 + rng.uniform(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Where generated users are placed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Population {
+    /// Uniform over the sphere.
+    Uniform,
+    /// Uniform over the area of each cell, in proportion to the cell's weight. A land
+    /// mask is a raster with weight 1 on land.
+    Raster(Raster),
+    Hotspots(Vec<Hotspot>),
+}
+
+/// `count` users on the surface at `EARTH_RADIUS`, numbered from `first_id`. Fails if
+/// hotspots are missing or have a bad position, sigma or weight.
+pub fn users(
+    population: &Population,
+    count: usize,
+    seed: u64,
+    first_id: u64,
+) -> Result<BTreeMap<User, Vector3>, Box<dyn Error>> {
+    if let Population::Hotspots(hotspots) = population {
+        validate_hotspots(hotspots)?;
+    }
+    let mut rng = Rng::new(seed);
+    let picker = match population {
+        Population::Uniform => None,
+        Population::Raster(raster) => Some(cumulative((0..raster.weights.len()).map(|cell| {
+            let (south, north) = raster.band(cell / raster.cols);
+            raster.weights[cell] * (north - south)
+        }))),
+        Population::Hotspots(hotspots) => Some(cumulative(hotspots.iter().map(|h| h.weight))),
+    };
+
+    let users = (0..count as u64)
+        .map(|i| {
+            let (latitude, longitude) = match population {
+                Population::Uniform => uniform_lat_lon(&mut rng),
+                Population::Raster(raster) => {
+                    let cell = pick(picker.as_ref().unwrap(), &mut rng);
+                    let (south, north) = raster.band(cell / raster.cols);
+                    let z = south + (north - south) * rng.uniform();
+                    let west = -180.0 + 360.0 * (cell % raster.cols) as f64 / raster.cols as f64;
+                    let longitude = west + 360.0 / raster.cols as f64 * rng.uniform();
+                    (z.asin().to_degrees(), longitude)
+                }
+                Population::Hotspots(hotspots) => {
+                    let hotspot = &hotspots[pick(picker.as_ref().unwrap(), &mut rng)];
+                    around(hotspot, &mut rng)
+                }
+            };
+            (User::new(first_id + i), surface_point(latitude, longitude))
+        })
+        .collect();
+    Ok(users)
+}
+
+fn validate_hotspots(hotspots: &[Hotspot]) -> Result<(), Box<dyn Error>> {
+    if hotspots.is_empty() {
+        return Err("There are no hotspots to place users around".into());
+    }
+    for hotspot in hotspots {
+        if !(hotspot.latitude.is_finite() && hotspot.longitude.is_finite()) {
+            return Err(format!(
+                "Hotspot at {}, {} is not on the surface",
+                hotspot.latitude, hotspot.longitude
+            )
+            .into());
+        }
+        if !(hotspot.sigma.is_finite() && hotspot.sigma >= 0.0) {
+            return Err(format!(
+                "Hotspot sigma {} must be finite and non-negative",
+                hotspot.sigma
+            )
+            .into());
+        }
+        if !(hotspot.weight.is_finite() && hotspot.weight >= 0.0) {
+            return Err(format!(
+                "Hotspot weight {} must be finite and non-negative",
+                hotspot.weight
+            )
+            .into());
+        }
+    }
+    if hotspots.iter().all(|hotspot| hotspot.weight == 0.0) {
+        return Err("Hotspots have no weight to place users by".into());
+    }
+    Ok(())
+}
+
+/// A `min_coverage` for `users` against `sats`: what `solution_v` serves today, less two
+/// points and rounded down to a whole percent, so small solver changes don't fail it.
+pub fn suggest_min_coverage(users: &BTreeMap<User, Vector3>, sats: &BTreeMap<Sat, Vector3>) -> f32 {
+    if users.is_empty() || sats.is_empty() {
+        return 0.0;
+    }
+    let served = solution_v::solve(users, sats).len() as f32 / users.len() as f32;
+    ((served * 100.0).floor() - 2.0).max(0.0) / 100.0
+}
+
+/// Writes a `min_coverage` line and then the users, in the format of the files in `test/`.
+pub fn write_users(
+    out: &mut impl Write,
+    users: &BTreeMap<User, Vector3>,
+    min_coverage: f32,
+) -> io::Result<()> {
+    writeln!(out, "min_coverage {:.2}", min_coverage)?;
+    for (user, pos) in users {
+        let [x, y, z] = pos.to_array();
+        writeln!(out, "user {} {:.4} {:.4} {:.4}", user, x, y, z)?;
+    }
+    Ok(())
+}
+
+fn uniform_lat_lon(rng: &mut Rng) -> (f64, f64) {
+    let z = 2.0 * rng.uniform() - 1.0;
+    (z.asin().to_degrees(), 360.0 * rng.uniform() - 180.0)
+}
+
+fn surface_point(latitude: f64, longitude: f64) -> Vector3 {
+    let (sin_lat, cos_lat) = latitude.to_radians().sin_cos();
+    let (sin_lon, cos_lon) = longitude.to_radians().sin_cos();
+    Vector3::new(
+        (EARTH_RADIUS * cos_lat * cos_lon) as f32,
+        (EARTH_RADIUS * cos_lat * sin_lon) as f32,
+        (EARTH_RADIUS * sin_lat) as f32,
+    )
+}
+
+// Walks a normally distributed distance from the hotspot's center in a uniformly random
+// direction along the surface.
+fn around(hotspot: &Hotspot, rng: &mut Rng) -> (f64, f64) {
+    let east = rng.gaussian() * hotspot.sigma / EARTH_RADIUS;
+    let north = rng.gaussian() * hotspot.sigma / EARTH_RADIUS;
+    let distance = east.hypot(north).min(PI);
+    let bearing = east.atan2(north);
+
+    let (sin_lat, cos_lat) = hotspot.latitude.to_radians().sin_cos();
+    let (sin_d, cos_d) = distance.sin_cos();
+    let latitude = (sin_lat * cos_d + cos_lat * sin_d * bearing.cos()).asin();
+    let longitude = hotspot.longitude.to_radians()
+        + (bearing.sin() * sin_d * cos_lat).atan2(cos_d - sin_lat * latitude.sin());
+    (
+        latitude.to_degrees(),
+        (longitude.to_degrees() + 540.0).rem_euclid(360.0) - 180.0,
+    )
+}
+
+fn cumulative(weights: impl Iterator<Item = f64>) -> Vec<f64> {
+    weights
+        .scan(0.0, |total, weight| {
+            *total += weight;
+            Some(*total)
+        })
+        .collect()
+}
+
+// Index of a random entry, with odds proportional to its weight.
+fn pick(cumulative: &[f64], rng: &mut Rng) -> usize {
+    let target = rng.uniform() * cumulative.last().unwrap();
+    cumulative
+        .partition_point(|total| *total <= target)
+        .min(cumulative.len() - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walker(pattern: WalkerPattern) -> Walker {
+        Walker {
+            pattern,
+            altitude: 550.0,
+            inclination: 53.0,
+            planes: 6,
+            sats_per_plane: 11,
+            phasing: 1,
+            epoch: Epoch(2460000.5),
+            first_id: 1,
+        }
+    }
+
+    #[test]
+    fn test_walker_geometry() {
+        let delta = walker(WalkerPattern::Delta).scenario().unwrap();
+        assert_eq!(delta.sats.len(), 66);
+        assert_eq!(*delta.sats.keys().next().unwrap(), Sat(1));
+        for pos in delta.sats.values() {
+            assert!((pos.length() - 6921.0).abs() < 0.01);
+            // Nobody gets further from the equator than the inclination allows.
+            let latitude = (pos.to_array()[2] / pos.length()).asin().to_degrees();
+            assert!(latitude.abs() <= 53.0 + 1e-3);
+        }
+
+        let elements = walker(WalkerPattern::Star).elements().unwrap();
+        let raans: Vec<f64> = elements.iter().step_by(11).map(|(_, k)| k.raan).collect();
+        assert_eq!(raans, vec![0.0, 30.0, 60.0, 90.0, 120.0, 150.0]);
+        // Plane 1 is offset by one 66th of a revolution against plane 0.
+        assert!((elements[11].1.mean_anomaly - 360.0 / 66.0).abs() < 1e-9);
+
+        let mut bad = walker(WalkerPattern::Delta);
+        bad.phasing = 6;
+        assert!(bad.scenario().is_err());
+    }
+
+    #[test]
+    fn test_bad_walkers() {
+        let mut broken = Vec::new();
+        for altitude in [f64::NAN, f64::INFINITY, 0.0] {
+            broken.push(Walker {
+                altitude,
+                ..walker(WalkerPattern::Delta)
+            });
+        }
+        for inclination in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            broken.push(Walker {
+                inclination,
+                ..walker(WalkerPattern::Delta)
+            });
+        }
+        // 2^32 × 2^32 satellites, and ids past u64::MAX.
+        broken.push(Walker {
+            planes: 1 << 32,
+            sats_per_plane: 1 << 32,
+            ..walker(WalkerPattern::Delta)
+        });
+        broken.push(Walker {
+            first_id: u64::MAX - 10,
+            ..walker(WalkerPattern::Delta)
+        });
+        for bad in broken {
+            assert!(
+                matches!(bad.elements(), Err(OrbitError::InvalidElements(_))),
+                "{:?}",
+                bad
+            );
+        }
+
+        let last = Walker {
+            first_id: u64::MAX - 65,
+            ..walker(WalkerPattern::Delta)
+        };
+        assert_eq!(last.elements().unwrap().last().unwrap().0, Sat(u64::MAX));
+    }
+
+    fn latitude(pos: &Vector3) -> f32 {
+        (pos.to_array()[2] / pos.length()).asin().to_degrees()
+    }
+
+    fn longitude(pos: &Vector3) -> f32 {
+        let [x, y, _] = pos.to_array();
+        y.atan2(x).to_degrees()
+    }
+
+    #[test]
+    fn test_uniform_users() {
+        let generated = users(&Population::Uniform, 20000, 7, 1).unwrap();
+        assert_eq!(generated.len(), 20000);
+        assert_eq!(*generated.keys().next().unwrap(), User(1));
+        assert_eq!(generated, users(&Population::Uniform, 20000, 7, 1).unwrap());
+        assert_ne!(generated, users(&Population::Uniform, 20000, 8, 1).unwrap());
+
+        // On the surface, and evenly split between hemispheres and between the tropics
+        // (half the sphere's area lies within 30° of the equator) and the rest.
+        let mut north = 0;
+        let mut tropics = 0;
+        for pos in generated.values() {
+            assert!((pos.length() - 6371.0).abs() < 0.01);
+            north += (latitude(pos) > 0.0) as usize;
+            tropics += (latitude(pos).abs() < 30.0) as usize;
+        }
+        assert!((9600..10400).contains(&north), "{north}");
+        assert!((9600..10400).contains(&tropics), "{tropics}");
+    }
+
+    #[test]
+    fn test_raster_users() {
+        // Land only in the north-east quadrant.
+        let mask = Raster::parse_mask("..#\n...\n").unwrap();
+        for pos in users(&Population::Raster(mask), 1000, 1, 1)
+            .unwrap()
+            .values()
+        {
+            assert!(latitude(pos) >= 0.0);
+            assert!(longitude(pos) >= 60.0 - 1e-3);
+        }
+
+        // Three times the weight in the southern row as in the northern one.
+        let density = Raster::parse_density("1 1\n3 3\n").unwrap();
+        let south = users(&Population::Raster(density), 4000, 1, 1)
+            .unwrap()
+            .values()
+            .filter(|pos| latitude(pos) < 0.0)
+            .count();
+        assert!((2850..3150).contains(&south), "{south}");
+
+        assert!(Raster::parse_density("1 2\n3\n").is_err());
+        assert!(Raster::parse_density("0 0\n").is_err());
+        assert!(Raster::parse_density("1 x\n").is_err());
+        assert!(Raster::parse_mask("#.\n#\n").is_err());
+    }
+
+    #[test]
+    fn test_hotspot_users() {
+        let hotspots = vec![
+            Hotspot {
+                latitude: 48.0,
+                longitude: 179.0,
+                sigma: 50.0,
+                weight: 1.0,
+            },
+            Hotspot {
+                latitude: -10.0,
+                longitude: 20.0,
+                sigma: 50.0,
+                weight: 3.0,
+            },
+        ];
+        let centers: Vec<Vector3> = hotspots
+            .iter()
+            .map(|h| surface_point(h.latitude, h.longitude))
+            .collect();
+        let generated = users(&Population::Hotspots(hotspots), 2000, 3, 1).unwrap();
+        let mut near = [0, 0];
+        for pos in generated.values() {
+            assert!((pos.length() - 6371.0).abs() < 0.01);
+            // Nearest center, within six sigma along the surface.
+            let (i, distance) = centers
+                .iter()
+                .map(|c| c.unit().dot(pos.unit()).min(1.0).acos() * 6371.0)
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+            assert!(distance < 300.0, "{distance}");
+            near[i] += 1;
+        }
+        assert!((400..600).contains(&near[0]), "{near:?}");
+    }
+
+    #[test]
+    fn test_bad_hotspots() {
+        let good = Hotspot {
+            latitude: 0.0,
+            longitude: 0.0,
+            sigma: 50.0,
+            weight: 1.0,
+        };
+        let bad = [
+            vec![],
+            vec![Hotspot {
+                sigma: -1.0,
+                ..good
+            }],
+            vec![Hotspot {
+                sigma: f64::NAN,
+                ..good
+            }],
+            vec![Hotspot {
+                weight: f64::INFINITY,
+                ..good
+            }],
+            vec![Hotspot {
+                latitude: f64::NAN,
+                ..good
+            }],
+            vec![Hotspot {
+                weight: 0.0,
+                ..good
+            }],
+        ];
+        for hotspots in bad {
+            assert!(
+                users(&Population::Hotspots(hotspots.clone()), 10, 1, 1).is_err(),
+                "{hotspots:?}"
+            );
+        }
+        assert_eq!(
+            users(&Population::Hotspots(vec![good]), 10, 1, 1)
+                .unwrap()
+                .len(),
+            10
+        );
+    }
+
+    #[test]
+    fn test_written_users_load_back() {
+        let generated = users(&Population::Uniform, 500, 1, 1).unwrap();
+        let sats = walker(WalkerPattern::Delta).scenario().unwrap().sats;
+        let min_coverage = suggest_min_coverage(&generated, &sats);
+        assert!((0.0..=1.0).contains(&min_coverage));
+        assert_eq!(min_coverage, suggest_min_coverage(&generated, &sats));
+        assert_eq!(suggest_min_coverage(&generated, &BTreeMap::new()), 0.0);
+
+        let path = std::env::temp_dir().join(format!("users_{}.txt", std::process::id()));
+        let mut out = Vec::new();
+        write_users(&mut out, &generated, min_coverage).unwrap();
+        std::fs::write(&path, out).unwrap();
+        let loaded = Scenario::new(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.min_coverage, min_coverage);
+        assert_eq!(loaded.users.len(), 500);
+        for (user, pos) in &generated {
+            assert!((loaded.users[user] - *pos).length() < 0.001);
+        }
+    }
+
+    #[test]
+    fn test_written_sats_load_back() {
+        let generated = walker(WalkerPattern::Delta).scenario().unwrap();
+        let dir = std::env::temp_dir();
+        for elements in [false, true] {
+            let path = dir.join(format!("walker_{}_{}.txt", std::process::id(), elements));
+            let mut out = Vec::new();
+            write_sats(&mut out, &generated, elements).unwrap();
+            std::fs::write(&path, out).unwrap();
+            let loaded = Scenario::new(path.to_str().unwrap()).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(loaded.orbits.len(), if elements { 66 } else { 0 });
+            assert_eq!(loaded.sats.len(), 66);
+            for (sat, pos) in &generated.sats {
+                assert!((loaded.sats[sat] - *pos).length() < 0.01);
+            }
+        }
+    }
+}
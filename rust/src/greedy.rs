@@ -0,0 +1,546 @@
+use crate::trace;
+use crate::util::{Color, Sat, User, MAX_ALLOWED_USERS, MAX_COLOR_OPTIONS};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+};
+
+const ALL_COLORS_BLOCKED: u8 = (1 << MAX_COLOR_OPTIONS) - 1;
+
+/// Index of a candidate (user, satellite) link, in `0..Candidates::link_count()`.
+pub type Link = usize;
+
+/// The candidate links a solver hands to `assign`. Users and satellites are identified by
+/// dense slots (`0..user_count()`, `0..sat_count()`) so the greedy state can live in
+/// plain vectors; `ids` maps a link back to the real ids.
+///
+/// Link numbering decides ties between equal scores, so number links the same way for
+/// the same input to get the same result.
+pub trait Candidates {
+    fn link_count(&self) -> usize;
+    fn user_count(&self) -> usize;
+    fn sat_count(&self) -> usize;
+    /// User slot and satellite slot of `link`.
+    fn slots(&self, link: Link) -> (usize, usize);
+    fn ids(&self, link: Link) -> (User, Sat);
+    /// Every link of `link`'s user, including `link` itself.
+    fn for_each_user_link(&self, link: Link, f: impl FnMut(Link));
+    /// Every link of `link`'s satellite, including `link` itself.
+    fn for_each_sat_link(&self, link: Link, f: impl FnMut(Link));
+    /// Links on the same satellite whose users are too close to share a color with
+    /// `link`'s user. Must be symmetric.
+    fn for_each_conflict(&self, link: Link, f: impl FnMut(Link));
+}
+
+/// Live bookkeeping of a greedy run, readable by `Scoring` implementations.
+pub struct State {
+    live: Vec<bool>,
+    live_count: usize,
+    blocked_colors: Vec<u8>,
+    live_conflicts: Vec<u32>,
+    user_options: Vec<u32>,
+    sat_load: Vec<u32>,
+}
+
+impl State {
+    /// Satellites that can still take `link`'s user, `link` included.
+    pub fn options(&self, candidates: &impl Candidates, link: Link) -> u32 {
+        self.user_options[candidates.slots(link).0]
+    }
+
+    /// Conflicting links that are still live.
+    pub fn conflicts(&self, link: Link) -> u32 {
+        self.live_conflicts[link]
+    }
+
+    /// Colors `link` could still be assigned.
+    pub fn free_colors(&self, link: Link) -> u32 {
+        MAX_COLOR_OPTIONS as u32 - self.blocked_colors[link].count_ones()
+    }
+
+    /// Users already assigned to `link`'s satellite.
+    pub fn sat_load(&self, candidates: &impl Candidates, link: Link) -> u32 {
+        self.sat_load[candidates.slots(link).1]
+    }
+
+    /// Whether no conflicting link has taken `color` yet.
+    pub fn color_free(&self, link: Link, color: Color) -> bool {
+        self.blocked_colors[link] & color_mask(color) == 0
+    }
+}
+
+fn color_mask(color: Color) -> u8 {
+    (1 << (color as u8)) >> 1
+}
+
+/// Decides which live link `assign` takes next: the lowest score wins, and ties are
+/// broken by link order shuffled by the seed. Scores are recomputed whenever a link's
+/// user loses an option or a conflicting link dies or takes a color.
+pub trait Scoring {
+    fn score(&self, candidates: &impl Candidates, state: &State, link: Link) -> u64;
+
+    /// The color `link` should get if it's still free, instead of the lowest free one.
+    fn preferred_color(&self, _candidates: &impl Candidates, _link: Link) -> Option<Color> {
+        None
+    }
+}
+
+/// Serve the users with the fewest satellites left first, so well-covered users don't
+/// take the only satellite someone else can see.
+pub struct FewestOptions;
+
+impl Scoring for FewestOptions {
+    fn score(&self, candidates: &impl Candidates, state: &State, link: Link) -> u64 {
+        ((state.options(candidates, link) as u64) << 32) | state.conflicts(link) as u64
+    }
+}
+
+/// Serve the links that block the fewest others first.
+pub struct LeastInterference;
+
+impl Scoring for LeastInterference {
+    fn score(&self, candidates: &impl Candidates, state: &State, link: Link) -> u64 {
+        ((state.conflicts(link) as u64) << 32) | state.options(candidates, link) as u64
+    }
+}
+
+/// Wraps another scoring so previously served users stay where they were when they
+/// can. A handover is a user switching satellite or color: links to a different
+/// satellite, and links to the previous one whose previous color a conflict has taken,
+/// get `penalty` added, and links to the previous satellite prefer the previous color.
+/// With `FewestOptions`, a penalty of `1 << 32` weighs a handover like one more
+/// satellite option.
+pub struct HandoverPenalty<'a, S> {
+    pub inner: S,
+    pub previous: &'a BTreeMap<User, (Sat, Color)>,
+    pub penalty: u64,
+}
+
+impl<S: Scoring> Scoring for HandoverPenalty<'_, S> {
+    fn score(&self, candidates: &impl Candidates, state: &State, link: Link) -> u64 {
+        let score = self.inner.score(candidates, state, link);
+        let (user, sat) = candidates.ids(link);
+        match self.previous.get(&user) {
+            Some((previous_sat, _)) if *previous_sat != sat => score.saturating_add(self.penalty),
+            Some((_, color)) if !state.color_free(link, *color) => {
+                score.saturating_add(self.penalty)
+            }
+            _ => score,
+        }
+    }
+
+    fn preferred_color(&self, candidates: &impl Candidates, link: Link) -> Option<Color> {
+        let (user, sat) = candidates.ids(link);
+        match self.previous.get(&user) {
+            Some((previous_sat, color)) if *previous_sat == sat => Some(*color),
+            _ => None,
+        }
+    }
+}
+
+/// Min-priority queue keyed by link with lazy invalidation: updating or removing a key
+/// only records its current priority, and heap entries that no longer match it are
+/// skipped when popped. Equal priorities are ordered by `tiebreak`.
+struct IndexedQueue {
+    heap: BinaryHeap<Reverse<(u64, u64, Link)>>,
+    current: Vec<Option<u64>>,
+    seed: u64,
+}
+
+impl IndexedQueue {
+    fn new(len: usize, seed: u64) -> Self {
+        Self {
+            heap: BinaryHeap::with_capacity(len),
+            current: vec![None; len],
+            seed,
+        }
+    }
+
+    /// Seed 0 keeps link order; any other seed shuffles it with a SplitMix64 hash of the
+    /// link, which is the same on every run and every platform.
+    fn tiebreak(&self, link: Link) -> u64 {
+        if self.seed == 0 {
+            return link as u64;
+        }
+        let mut z = (link as u64 ^ self.seed).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn set(&mut self, link: Link, priority: u64) {
+        if self.current[link] != Some(priority) {
+            self.current[link] = Some(priority);
+            self.heap
+                .push(Reverse((priority, self.tiebreak(link), link)));
+        }
+    }
+
+    fn remove(&mut self, link: Link) {
+        self.current[link] = None;
+    }
+
+    fn pop(&mut self) -> Option<Link> {
+        while let Some(Reverse((priority, _, link))) = self.heap.pop() {
+            if self.current[link] == Some(priority) {
+                self.current[link] = None;
+                return Some(link);
+            }
+        }
+        None
+    }
+}
+
+struct Run<'a, C: Candidates, S: Scoring> {
+    candidates: &'a C,
+    scoring: &'a S,
+    state: State,
+    queue: IndexedQueue,
+}
+
+impl<C: Candidates, S: Scoring> Run<'_, C, S> {
+    fn rescore(&mut self, link: Link) {
+        if self.state.live[link] {
+            let score = self.scoring.score(self.candidates, &self.state, link);
+            self.queue.set(link, score);
+        }
+    }
+
+    /// Drops `links` from the run, then rescores the live links that lost an option or a
+    /// conflict. Killing a batch at once avoids rescoring links that die in the same batch.
+    fn kill(&mut self, links: &[Link]) {
+        let mut dead = Vec::with_capacity(links.len());
+        for &link in links {
+            if self.state.live[link] {
+                self.state.live[link] = false;
+                self.state.live_count -= 1;
+                self.queue.remove(link);
+                self.state.user_options[self.candidates.slots(link).0] -= 1;
+                dead.push(link);
+            }
+        }
+
+        let mut touched = Vec::new();
+        let live = &self.state.live;
+        let live_conflicts = &mut self.state.live_conflicts;
+        for link in dead {
+            self.candidates.for_each_user_link(link, |other| {
+                if live[other] {
+                    touched.push(other);
+                }
+            });
+            self.candidates.for_each_conflict(link, |other| {
+                if live[other] {
+                    live_conflicts[other] -= 1;
+                    touched.push(other);
+                }
+            });
+        }
+        touched.sort_unstable();
+        touched.dedup();
+        for other in touched {
+            self.rescore(other);
+        }
+    }
+
+    /// The color `link` would get: the scoring's preferred color if it's free, otherwise
+    /// the lowest one its conflicts leave free.
+    fn free_color(&self, link: Link) -> u8 {
+        match self.scoring.preferred_color(self.candidates, link) {
+            Some(color) if self.state.color_free(link, color) => color as u8 - 1,
+            _ => self.state.blocked_colors[link].trailing_ones() as u8,
+        }
+    }
+
+    fn take(&mut self, link: Link, color_bit: u8) -> Color {
+        let color = Color::from_id(color_bit as i32 + 1);
+
+        let (_, sat) = self.candidates.slots(link);
+        self.state.sat_load[sat] += 1;
+
+        // don't reconnect the same user
+        let mut dead = Vec::new();
+        self.candidates
+            .for_each_user_link(link, |other| dead.push(other));
+        // if satellite is at capacity, drop its remaining possible connections
+        if self.state.sat_load[sat] as usize >= MAX_ALLOWED_USERS {
+            self.candidates
+                .for_each_sat_link(link, |other| dead.push(other));
+        }
+        self.kill(&dead);
+
+        // dont add interfering connections
+        let mut conflicts = Vec::new();
+        self.candidates
+            .for_each_conflict(link, |other| conflicts.push(other));
+        conflicts.retain(|other| self.state.live[*other]);
+        let mut dead = Vec::new();
+        for other in conflicts {
+            self.state.blocked_colors[other] |= 1 << color_bit;
+            if self.state.blocked_colors[other] == ALL_COLORS_BLOCKED {
+                dead.push(other);
+            } else {
+                self.rescore(other);
+            }
+        }
+        self.kill(&dead);
+
+        color
+    }
+}
+
+/// Greedily assigns links in `scoring` order, giving each the scoring's preferred color
+/// or else the lowest color its conflicts leave free, until no link can be added.
+/// Returns `(user, sat, color)` in the order they were assigned. The result depends
+/// only on `candidates` (including its link numbering), `scoring` and `seed`.
+pub fn assign(
+    candidates: &impl Candidates,
+    scoring: &impl Scoring,
+    seed: u64,
+) -> Vec<(User, Sat, Color)> {
+    assign_from(candidates, scoring, seed, &[])
+}
+
+/// Like `assign`, but first takes the `keep` links in order with their given colors,
+/// skipping any that an earlier one has made impossible (user already served,
+/// satellite full, or color taken by a conflicting link). The greedy pass then fills
+/// in the rest.
+pub fn assign_from(
+    candidates: &impl Candidates,
+    scoring: &impl Scoring,
+    seed: u64,
+    keep: &[(Link, Color)],
+) -> Vec<(User, Sat, Color)> {
+    let mut span = trace::span("greedy");
+    let links = candidates.link_count();
+    span.count("links", links);
+    let scores = trace::span("initial_scores");
+    let mut state = State {
+        live: vec![true; links],
+        live_count: links,
+        blocked_colors: vec![0; links],
+        live_conflicts: vec![0; links],
+        user_options: vec![0; candidates.user_count()],
+        sat_load: vec![0; candidates.sat_count()],
+    };
+    for link in 0..links {
+        state.user_options[candidates.slots(link).0] += 1;
+        let mut conflicts = 0;
+        candidates.for_each_conflict(link, |_| conflicts += 1);
+        state.live_conflicts[link] = conflicts;
+    }
+
+    let mut run = Run {
+        candidates,
+        scoring,
+        state,
+        queue: IndexedQueue::new(links, seed),
+    };
+    for link in 0..links {
+        run.rescore(link);
+    }
+    drop(scores);
+
+    let mut assignments = Vec::new();
+    for &(link, color) in keep {
+        let Some(color_bit) = (color as u8).checked_sub(1) else {
+            continue;
+        };
+        if run.state.live[link] && run.state.blocked_colors[link] & (1 << color_bit) == 0 {
+            run.queue.remove(link);
+            let color = run.take(link, color_bit);
+            let (user, sat) = candidates.ids(link);
+            assignments.push((user, sat, color));
+        }
+    }
+    span.count("kept", assignments.len());
+    // Once every link is dead the heap holds only stale entries; don't bother popping them.
+    while run.state.live_count > 0 {
+        let link = run.queue.pop().unwrap();
+        let color = run.take(link, run.free_color(link));
+        let (user, sat) = candidates.ids(link);
+        assignments.push((user, sat, color));
+    }
+    span.count("assignments", assignments.len());
+    assignments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Links given as (user, sat) slots with an explicit conflict list.
+    struct Fixture {
+        links: Vec<(usize, usize)>,
+        conflicts: Vec<(Link, Link)>,
+    }
+
+    impl Candidates for Fixture {
+        fn link_count(&self) -> usize {
+            self.links.len()
+        }
+        fn user_count(&self) -> usize {
+            self.links.iter().map(|l| l.0 + 1).max().unwrap_or(0)
+        }
+        fn sat_count(&self) -> usize {
+            self.links.iter().map(|l| l.1 + 1).max().unwrap_or(0)
+        }
+        fn slots(&self, link: Link) -> (usize, usize) {
+            self.links[link]
+        }
+        fn ids(&self, link: Link) -> (User, Sat) {
+            let (user, sat) = self.links[link];
+            (User(user as u64), Sat(sat as u64))
+        }
+        fn for_each_user_link(&self, link: Link, f: impl FnMut(Link)) {
+            let user = self.links[link].0;
+            (0..self.links.len())
+                .filter(|other| self.links[*other].0 == user)
+                .for_each(f);
+        }
+        fn for_each_sat_link(&self, link: Link, f: impl FnMut(Link)) {
+            let sat = self.links[link].1;
+            (0..self.links.len())
+                .filter(|other| self.links[*other].1 == sat)
+                .for_each(f);
+        }
+        fn for_each_conflict(&self, link: Link, f: impl FnMut(Link)) {
+            self.conflicts
+                .iter()
+                .filter_map(|&(a, b)| match link {
+                    _ if a == link => Some(b),
+                    _ if b == link => Some(a),
+                    _ => None,
+                })
+                .for_each(f);
+        }
+    }
+
+    #[test]
+    fn test_fewest_options_serves_constrained_user_first() {
+        // User 0 sees sats 0 and 1, user 1 only sees sat 0, and they conflict there.
+        // Three mutually conflicting users on sat 0 leave the pair a single color.
+        let mut links = vec![(0, 0), (0, 1), (1, 0)];
+        let mut conflicts = vec![(0, 2)];
+        for filler in 3..6 {
+            links.push((filler - 1, 0));
+            conflicts.push((0, filler));
+            conflicts.push((2, filler));
+            for other in 3..filler {
+                conflicts.push((other, filler));
+            }
+        }
+        let fixture = Fixture { links, conflicts };
+
+        let assignments = assign(&fixture, &FewestOptions, 0);
+        let served: Vec<User> = assignments.iter().map(|a| a.0).collect();
+        assert!(served.contains(&User(0)));
+        assert!(served.contains(&User(1)));
+        assert_eq!(
+            assignments.iter().find(|a| a.0 == User(0)).unwrap().1,
+            Sat(1)
+        );
+    }
+
+    #[test]
+    fn test_colors_and_capacity() {
+        // Five users on one satellite, all in conflict: four colors, then nothing left.
+        let links: Vec<(usize, usize)> = (0..5).map(|user| (user, 0)).collect();
+        let mut conflicts = Vec::new();
+        for a in 0..5 {
+            for b in a + 1..5 {
+                conflicts.push((a, b));
+            }
+        }
+        let assignments = assign(&Fixture { links, conflicts }, &LeastInterference, 0);
+        let mut colors: Vec<Color> = assignments.iter().map(|a| a.2).collect();
+        colors.sort();
+        assert_eq!(colors, vec![Color::A, Color::B, Color::C, Color::D]);
+
+        // Forty users without conflicts: the satellite stops at capacity.
+        let links: Vec<(usize, usize)> = (0..40).map(|user| (user, 0)).collect();
+        let fixture = Fixture {
+            links,
+            conflicts: Vec::new(),
+        };
+        assert_eq!(assign(&fixture, &FewestOptions, 0).len(), MAX_ALLOWED_USERS);
+    }
+
+    #[test]
+    fn test_assign_from_keeps_valid_links() {
+        // Users 0 and 1 conflict on sat 0; user 1 can also use sat 1.
+        let fixture = Fixture {
+            links: vec![(0, 0), (1, 0), (1, 1)],
+            conflicts: vec![(0, 1)],
+        };
+        // Both kept on sat 0 with the same color: the second one can't stay.
+        let assignments = assign_from(&fixture, &FewestOptions, 0, &[(0, Color::C), (1, Color::C)]);
+        assert_eq!(assignments[0], (User(0), Sat(0), Color::C));
+        assert_eq!(assignments.len(), 2);
+        assert_eq!(assignments[1].0, User(1));
+        assert_ne!(assignments[1], (User(1), Sat(0), Color::C));
+
+        // The penalty moves user 1 back to its previous satellite.
+        let previous = BTreeMap::from([(User(1), (Sat(0), Color::A))]);
+        let sticky = HandoverPenalty {
+            inner: FewestOptions,
+            previous: &previous,
+            penalty: 1 << 32,
+        };
+        let assignments = assign_from(&fixture, &sticky, 0, &[(0, Color::A)]);
+        assert_eq!(assignments[1], (User(1), Sat(0), Color::B));
+    }
+
+    #[test]
+    fn test_handover_penalty_counts_color_switches() {
+        // Users 0 and 1 conflict on sat 0; user 2 has sat 0 to itself.
+        let fixture = Fixture {
+            links: vec![(0, 0), (1, 0), (2, 0)],
+            conflicts: vec![(0, 1)],
+        };
+        // User 1 was on sat 0 with color C and gets it back rather than the lowest free.
+        let previous = BTreeMap::from([(User(1), (Sat(0), Color::C))]);
+        let sticky = HandoverPenalty {
+            inner: FewestOptions,
+            previous: &previous,
+            penalty: 1 << 32,
+        };
+        let assignments = assign(&fixture, &sticky, 0);
+        assert!(assignments.contains(&(User(1), Sat(0), Color::C)));
+
+        // Keeping user 0 on color A leaves user 1 nothing but a color switch on its own
+        // satellite. That is a handover too, so it goes after user 2.
+        let previous = BTreeMap::from([(User(1), (Sat(0), Color::A))]);
+        let sticky = HandoverPenalty {
+            previous: &previous,
+            ..sticky
+        };
+        let plain: Vec<User> = assign_from(&fixture, &FewestOptions, 0, &[(0, Color::A)])
+            .iter()
+            .map(|a| a.0)
+            .collect();
+        assert_eq!(plain, [User(0), User(1), User(2)]);
+        let assignments = assign_from(&fixture, &sticky, 0, &[(0, Color::A)]);
+        assert_eq!(
+            assignments,
+            [
+                (User(0), Sat(0), Color::A),
+                (User(2), Sat(0), Color::A),
+                (User(1), Sat(0), Color::B)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_queue_skips_stale_entries() {
+        let mut queue = IndexedQueue::new(3, 0);
+        queue.set(0, 5);
+        queue.set(1, 3);
+        queue.set(2, 4);
+        queue.set(1, 9);
+        queue.remove(2);
+        assert_eq!(queue.pop(), Some(0));
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), None);
+    }
+}
@@ -0,0 +1,405 @@
+//! The JSON reader and writer behind the JSON scenario and solution formats, the run
+//! report, traces and `--json` output.
+//!
+//! This is deliberately not serde_json. The default build has no dependency beyond
+//! rayon, and `serde` is an optional feature. The reader keeps each value's byte offset,
+//! so format errors get the same line and column a text file would. It also keeps
+//! numbers as text, so ids stay exact. `Json` writes members in insertion order, which
+//! keeps the reports stable to diff. With the `serde` feature, `Scenario` uses the same
+//! schema as `format`'s JSON; a test in `format` checks the two agree.
+use std::fmt::Write;
+
+/// A parsed JSON value. Numbers keep their text so ids parse exactly and floats round
+/// to `f32` the same way the text format does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Node>),
+    Object(Vec<(String, Node)>),
+}
+
+/// A value and the byte offset it starts at, for error positions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub value: Value,
+    pub offset: usize,
+}
+
+impl Node {
+    pub fn get(&self, key: &str) -> Option<&Node> {
+        match &self.value {
+            Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+/// Byte offset and description of a syntax error.
+pub type SyntaxError = (usize, String);
+
+/// Parses a complete JSON document.
+pub fn parse(text: &str) -> Result<Node, SyntaxError> {
+    let mut parser = Parser {
+        text: text.as_bytes(),
+        at: 0,
+    };
+    let node = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.at < text.len() {
+        return Err((parser.at, "trailing characters".to_string()));
+    }
+    Ok(node)
+}
+
+// Deeper nesting than any file we write is an error rather than a stack overflow.
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    text: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.text.get(self.at) {
+            self.at += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), SyntaxError> {
+        self.skip_whitespace();
+        if self.text.get(self.at) == Some(&byte) {
+            self.at += 1;
+            Ok(())
+        } else {
+            Err((self.at, format!("expected `{}`", byte as char)))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, SyntaxError> {
+        if self.text[self.at..].starts_with(word.as_bytes()) {
+            self.at += word.len();
+            Ok(value)
+        } else {
+            Err((self.at, "expected a value".to_string()))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Node, SyntaxError> {
+        self.skip_whitespace();
+        let offset = self.at;
+        if depth > MAX_DEPTH {
+            return Err((offset, "nested too deeply".to_string()));
+        }
+        let value = match self.text.get(self.at) {
+            None => return Err((offset, "unexpected end of input".to_string())),
+            Some(b'n') => self.literal("null", Value::Null)?,
+            Some(b't') => self.literal("true", Value::Bool(true))?,
+            Some(b'f') => self.literal("false", Value::Bool(false))?,
+            Some(b'"') => Value::String(self.string()?),
+            Some(b'-' | b'0'..=b'9') => self.number()?,
+            Some(b'[') => {
+                self.at += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.text.get(self.at) == Some(&b']') {
+                    self.at += 1;
+                } else {
+                    loop {
+                        items.push(self.value(depth + 1)?);
+                        if self.separator(b']')? {
+                            break;
+                        }
+                    }
+                }
+                Value::Array(items)
+            }
+            Some(b'{') => {
+                self.at += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.text.get(self.at) == Some(&b'}') {
+                    self.at += 1;
+                } else {
+                    loop {
+                        self.skip_whitespace();
+                        if self.text.get(self.at) != Some(&b'"') {
+                            return Err((self.at, "expected a string key".to_string()));
+                        }
+                        let key = self.string()?;
+                        self.expect(b':')?;
+                        members.push((key, self.value(depth + 1)?));
+                        if self.separator(b'}')? {
+                            break;
+                        }
+                    }
+                }
+                Value::Object(members)
+            }
+            Some(_) => return Err((offset, "expected a value".to_string())),
+        };
+        Ok(Node { value, offset })
+    }
+
+    // After an item: `true` at the closing bracket, `false` at a comma.
+    fn separator(&mut self, close: u8) -> Result<bool, SyntaxError> {
+        self.skip_whitespace();
+        match self.text.get(self.at) {
+            Some(b',') => {
+                self.at += 1;
+                Ok(false)
+            }
+            Some(&byte) if byte == close => {
+                self.at += 1;
+                Ok(true)
+            }
+            _ => Err((self.at, format!("expected `,` or `{}`", close as char))),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, SyntaxError> {
+        let start = self.at;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.text.get(self.at) {
+            self.at += 1;
+        }
+        let text = std::str::from_utf8(&self.text[start..self.at]).unwrap();
+        // JSON numbers are a subset of what `f64` parses, apart from a leading `+`.
+        if text.starts_with('+') || text.parse::<f64>().is_err() {
+            return Err((start, format!("bad number {:?}", text)));
+        }
+        Ok(Value::Number(text.to_string()))
+    }
+
+    fn string(&mut self) -> Result<String, SyntaxError> {
+        let start = self.at;
+        self.at += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let Some(&byte) = self.text.get(self.at) else {
+                return Err((start, "unterminated string".to_string()));
+            };
+            self.at += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = match self.text.get(self.at) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let hex = self.text.get(self.at + 1..self.at + 5);
+                            let code = hex
+                                .and_then(|hex| std::str::from_utf8(hex).ok())
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .ok_or((self.at - 1, "bad \\u escape".to_string()))?;
+                            self.at += 4;
+                            // Surrogate pairs aren't needed for anything we read.
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return Err((self.at - 1, "bad escape".to_string())),
+                    };
+                    self.at += 1;
+                    bytes.extend_from_slice(escaped.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                _ => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| (start, "string is not UTF-8".to_string()))
+    }
+}
+
+/// A JSON value to write. `Display` gives compact JSON with members in the order they
+/// were added.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    /// Already formatted, so integers stay exact and floats print in their shortest
+    /// exact form.
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object() -> Self {
+        Self::Object(Vec::new())
+    }
+
+    /// Adds a member to an object.
+    pub fn with(mut self, key: &str, value: impl Into<Json>) -> Self {
+        if let Self::Object(members) = &mut self {
+            members.push((key.to_string(), value.into()));
+        }
+        self
+    }
+}
+
+impl std::fmt::Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Bool(value) => write!(f, "{}", value),
+            Self::Number(text) => write!(f, "{}", text),
+            Self::String(text) => write!(f, "{}", quote(text)),
+            Self::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    write!(f, "{}{}", if i > 0 { "," } else { "" }, item)?;
+                }
+                write!(f, "]")
+            }
+            Self::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    write!(
+                        f,
+                        "{}{}:{}",
+                        if i > 0 { "," } else { "" },
+                        quote(key),
+                        value
+                    )?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+macro_rules! json_from_number {
+    ($($t:ty),*) => {$(
+        impl From<$t> for Json {
+            // Non-finite floats have no JSON form.
+            #[allow(clippy::unnecessary_cast)]
+            fn from(value: $t) -> Self {
+                if (value as f64).is_finite() {
+                    Self::Number(value.to_string())
+                } else {
+                    Self::Null
+                }
+            }
+        }
+    )*};
+}
+
+json_from_number!(f32, f64, u32, u64, usize, i32, i64);
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Null, Into::into)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(items: Vec<T>) -> Self {
+        Self::Array(items.into_iter().map(Into::into).collect())
+    }
+}
+
+/// `text` as a quoted JSON string.
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(text: &str) -> Value {
+        Value::Number(text.to_string())
+    }
+
+    #[test]
+    fn test_parses_nested_values() {
+        let node = parse(r#" {"a": [1, -2.5e3, true, null], "b": {"c": "x\"A"}} "#).unwrap();
+        let Value::Array(items) = &node.get("a").unwrap().value else {
+            panic!("not an array");
+        };
+        let values: Vec<&Value> = items.iter().map(|item| &item.value).collect();
+        assert_eq!(
+            values,
+            [
+                &number("1"),
+                &number("-2.5e3"),
+                &Value::Bool(true),
+                &Value::Null
+            ]
+        );
+        assert_eq!(items[1].offset, 11);
+        assert_eq!(
+            node.get("b").unwrap().get("c").unwrap().value,
+            Value::String("x\"A".to_string())
+        );
+        assert_eq!(parse("[]").unwrap().value, Value::Array(Vec::new()));
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert_eq!(parse("[1 2]").unwrap_err().0, 3);
+        assert_eq!(parse("{\"a\" 1}").unwrap_err().0, 5);
+        assert_eq!(parse("[1] x").unwrap_err().0, 4);
+        assert!(parse("\"abc").is_err());
+        assert!(parse("+1").is_err());
+        assert!(parse("1.2.3").is_err());
+        assert!(parse(&"[".repeat(1000)).is_err());
+        assert_eq!(quote("a\"b\n\u{1}"), r#""a\"b\n\u0001""#);
+    }
+
+    #[test]
+    fn test_writes_json() {
+        let json = Json::object()
+            .with("id", u64::MAX)
+            .with("coverage", 0.1f32)
+            .with("nan", f64::NAN)
+            .with("epoch", None::<f64>)
+            .with("names", vec!["a", "b\""])
+            .with("empty", Json::object());
+        let text = json.to_string();
+        assert_eq!(
+            text,
+            r#"{"id":18446744073709551615,"coverage":0.1,"nan":null,"epoch":null,"names":["a","b\""],"empty":{}}"#
+        );
+        assert!(parse(&text).is_ok());
+    }
+}
@@ -0,0 +1,65 @@
+//! Assigns ground users to satellite beams.
+//!
+//! A [`Scenario`] holds satellites and users as ECEF positions in km, and the share of
+//! users a solution must serve. A solution maps each served user to a satellite and one
+//! of four beam [`Color`]s, subject to these rules:
+//!
+//! - The satellite is within 45° of the user's vertical.
+//! - A satellite serves at most 32 users.
+//! - Two users on the same satellite and color are at least 10° apart as seen from the
+//!   satellite.
+//!
+//! [`solution_v`] is the fast, parallel solver; [`solution_e`] is a simpler sequential
+//! one. [`verify`] checks a solution against the rules, [`explain`] says why a user
+//! went unserved, and [`format`](mod@format) reads and writes scenarios and solutions
+//! as text, JSON or CSV.
+//!
+//! The library also builds as a C `cdylib`; see [`ffi`] and `include/beam_planner.h`.
+//!
+//! With the `serde` feature, the ids, [`Vector3`] (as `[x, y, z]`), [`Scenario`], solutions,
+//! plans, violations and explanations implement `Serialize` and `Deserialize`.
+//!
+//! ```
+//! use beam_planner::{solution_v, Scenario};
+//!
+//! let scenario = Scenario::new("../test/02_five_users.txt").unwrap();
+//! let solution = solution_v::solve(&scenario.users, &scenario.sats);
+//! assert!(scenario.verify(&solution).is_empty());
+//! ```
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+
+pub mod cli;
+pub mod config;
+pub mod explain;
+pub mod ffi;
+pub mod format;
+pub mod generate;
+pub mod greedy;
+pub mod json;
+pub mod orbit;
+pub mod parse;
+pub mod planner;
+pub mod report;
+pub mod scenario;
+pub mod solution_e;
+pub mod solution_v;
+mod spatial;
+pub mod stats;
+mod test_util;
+pub mod trace;
+pub mod util;
+pub mod validate;
+pub mod verify;
+pub mod visibility;
+
+pub use config::{Solver, SolverConfig};
+pub use format::Format;
+pub use parse::ParseError;
+pub use scenario::{Scenario, TIMEOUT};
+pub use util::{Color, Sat, User, Vector3};
+pub use verify::Violation;
+
+use std::collections::BTreeMap;
+
+/// Served users and the satellite and color serving each.
+pub type Solution = BTreeMap<User, (Sat, Color)>;
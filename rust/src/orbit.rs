@@ -0,0 +1,990 @@
+use crate::util::Vector3;
+use std::{
+    error::Error,
+    f64::consts::{PI, TAU},
+    fmt::{self, Display, Formatter},
+};
+
+const MINUTES_PER_DAY: f64 = 1440.0;
+const SECONDS_PER_DAY: f64 = 86400.0;
+const J2000: f64 = 2451545.0;
+const UNIX_EPOCH: f64 = 2440587.5;
+
+// Earth's gravitational parameter for two-body propagation (WGS-84), km³/s².
+const MU_WGS84: f64 = 398600.4418;
+
+// WGS-72 constants. TLEs are fitted against these, so SGP4 must use them too.
+const MU_WGS72: f64 = 398600.8;
+const RADIUS_WGS72: f64 = 6378.135;
+const J2: f64 = 0.001082616;
+const J3: f64 = -0.00000253881;
+const J4: f64 = -0.00000165597;
+
+// SGP4 only covers near-Earth orbits; periods of 225 minutes or more need the
+// deep-space (SDP4) terms.
+const DEEP_SPACE_PERIOD_MINUTES: f64 = 225.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrbitError {
+    /// A TLE line is missing, too short or has an unreadable field.
+    InvalidTle(String),
+    /// An epoch string is neither a finite Julian date nor a valid
+    /// `YYYY-MM-DDTHH:MM:SS[.fff][Z]` timestamp.
+    InvalidEpoch(String),
+    /// Eccentricity outside `[0, 1)`, a non-positive semi-major axis or mean motion, or a
+    /// non-finite value.
+    InvalidElements(String),
+    /// The orbit has a period of 225 minutes or more, which needs SDP4.
+    DeepSpace { period_minutes: f64 },
+    /// Propagation drove the orbit below the Earth's surface or made it unbound.
+    Decayed { minutes_since_epoch: f64 },
+}
+
+impl Display for OrbitError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidTle(message) => write!(f, "Invalid TLE: {}", message),
+            Self::InvalidEpoch(epoch) => write!(f, "Invalid epoch: {}", epoch),
+            Self::InvalidElements(message) => write!(f, "Invalid orbital elements: {}", message),
+            Self::DeepSpace { period_minutes } => write!(
+                f,
+                "Deep-space orbit ({:.1} minute period) is not supported",
+                period_minutes
+            ),
+            Self::Decayed {
+                minutes_since_epoch,
+            } => write!(
+                f,
+                "Orbit decayed {:.1} minutes after its epoch",
+                minutes_since_epoch
+            ),
+        }
+    }
+}
+
+impl Error for OrbitError {}
+
+/// A UTC instant as a Julian date. UT1 - UTC (under a second) is ignored, which moves
+/// ECEF positions by at most a few hundred meters along the equator.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Epoch(pub f64);
+
+impl Epoch {
+    pub fn from_calendar(
+        year: i32,
+        month: u32,
+        day: u32,
+        hour: u32,
+        minute: u32,
+        second: f64,
+    ) -> Self {
+        let (year, month) = (year as f64, month as f64);
+        let date = 367.0 * year - (7.0 * (year + ((month + 9.0) / 12.0).floor()) * 0.25).floor()
+            + (275.0 * month / 9.0).floor()
+            + day as f64
+            + 1721013.5;
+        let time = ((second / 60.0 + minute as f64) / 60.0 + hour as f64) / 24.0;
+        Self(date + time)
+    }
+
+    pub fn from_unix_seconds(seconds: f64) -> Self {
+        Self(UNIX_EPOCH + seconds / SECONDS_PER_DAY)
+    }
+
+    /// Parses a Julian date (`2460000.5`) or an ISO 8601 UTC timestamp
+    /// (`2024-03-01T12:00:00Z`, fractional seconds and the `Z` are optional).
+    pub fn parse(text: &str) -> Result<Self, OrbitError> {
+        let invalid = || OrbitError::InvalidEpoch(text.to_string());
+        if let Ok(julian_date) = text.parse::<f64>() {
+            return if julian_date.is_finite() {
+                Ok(Self(julian_date))
+            } else {
+                Err(invalid())
+            };
+        }
+        let (date, time) = text
+            .trim_end_matches('Z')
+            .split_once('T')
+            .ok_or_else(invalid)?;
+        let date: Vec<&str> = date.split('-').collect();
+        let time: Vec<&str> = time.split(':').collect();
+        if date.len() != 3 || time.len() != 3 {
+            return Err(invalid());
+        }
+        let year: i32 = date[0].parse().map_err(|_| invalid())?;
+        let month: u32 = date[1].parse().map_err(|_| invalid())?;
+        let day: u32 = date[2].parse().map_err(|_| invalid())?;
+        let hour: u32 = time[0].parse().map_err(|_| invalid())?;
+        let minute: u32 = time[1].parse().map_err(|_| invalid())?;
+        let second: f64 = time[2].parse().map_err(|_| invalid())?;
+        // `from_calendar` rolls out-of-range fields over into the next month or day,
+        // so they are rejected here. Second 60 is a leap second.
+        if !(1..=12).contains(&month)
+            || !(1..=days_in_month(year, month)).contains(&day)
+            || hour >= 24
+            || minute >= 60
+            || !(0.0..61.0).contains(&second)
+        {
+            return Err(invalid());
+        }
+        Ok(Self::from_calendar(year, month, day, hour, minute, second))
+    }
+
+    pub fn plus_seconds(self, seconds: f64) -> Self {
+        Self(self.0 + seconds / SECONDS_PER_DAY)
+    }
+
+    pub fn seconds_since(self, other: Self) -> f64 {
+        (self.0 - other.0) * SECONDS_PER_DAY
+    }
+
+    pub fn minutes_since(self, other: Self) -> f64 {
+        (self.0 - other.0) * MINUTES_PER_DAY
+    }
+
+    /// Greenwich mean sidereal time in radians (IAU 1982 model).
+    pub fn gmst(self) -> f64 {
+        let centuries = (self.0 - J2000) / 36525.0;
+        let seconds = -6.2e-6 * centuries.powi(3)
+            + 0.093104 * centuries.powi(2)
+            + (876600.0 * 3600.0 + 8640184.812866) * centuries
+            + 67310.54841;
+        (seconds.to_radians() / 240.0).rem_euclid(TAU)
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl Display for Epoch {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Rotates an inertial (TEME) position into the Earth-fixed frame at `epoch`, about the
+/// z axis by GMST. Polar motion is ignored.
+pub fn inertial_to_ecef(position: [f64; 3], epoch: Epoch) -> [f64; 3] {
+    let (sin, cos) = epoch.gmst().sin_cos();
+    [
+        cos * position[0] + sin * position[1],
+        -sin * position[0] + cos * position[1],
+        position[2],
+    ]
+}
+
+/// Classical orbital elements for two-body propagation. Angles are in degrees and the
+/// semi-major axis in km.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct KeplerianElements {
+    pub semi_major_axis: f64,
+    pub eccentricity: f64,
+    pub inclination: f64,
+    pub raan: f64,
+    pub arg_of_perigee: f64,
+    pub mean_anomaly: f64,
+    pub epoch: Epoch,
+}
+
+impl KeplerianElements {
+    pub fn validate(&self) -> Result<(), OrbitError> {
+        for (name, angle) in [
+            ("inclination", self.inclination),
+            ("RAAN", self.raan),
+            ("argument of perigee", self.arg_of_perigee),
+            ("mean anomaly", self.mean_anomaly),
+        ] {
+            if !angle.is_finite() {
+                return Err(OrbitError::InvalidElements(format!(
+                    "{} {} is not a finite angle",
+                    name, angle
+                )));
+            }
+        }
+        if !(0.0..1.0).contains(&self.eccentricity) {
+            return Err(OrbitError::InvalidElements(format!(
+                "eccentricity {} is outside [0, 1)",
+                self.eccentricity
+            )));
+        }
+        if !(self.semi_major_axis > 0.0 && self.semi_major_axis.is_finite()) {
+            return Err(OrbitError::InvalidElements(format!(
+                "semi-major axis {} km is not positive and finite",
+                self.semi_major_axis
+            )));
+        }
+        Ok(())
+    }
+
+    /// Mean motion in radians per second.
+    pub fn mean_motion(&self) -> f64 {
+        (MU_WGS84 / self.semi_major_axis.powi(3)).sqrt()
+    }
+
+    /// Inertial position in km at `at`.
+    pub fn position(&self, at: Epoch) -> Result<[f64; 3], OrbitError> {
+        self.validate()?;
+        let e = self.eccentricity;
+        let mean_anomaly = (self.mean_anomaly.to_radians()
+            + self.mean_motion() * at.seconds_since(self.epoch))
+        .rem_euclid(TAU);
+
+        // Newton's method on Kepler's equation M = E - e sin E.
+        let mut eccentric_anomaly = if e < 0.8 { mean_anomaly } else { PI };
+        for _ in 0..50 {
+            let step = (eccentric_anomaly - e * eccentric_anomaly.sin() - mean_anomaly)
+                / (1.0 - e * eccentric_anomaly.cos());
+            eccentric_anomaly -= step;
+            if step.abs() < 1e-14 {
+                break;
+            }
+        }
+
+        // Position in the orbital plane, perigee along x.
+        let (sin_e, cos_e) = eccentric_anomaly.sin_cos();
+        let a = self.semi_major_axis;
+        let px = a * (cos_e - e);
+        let py = a * (1.0 - e * e).sqrt() * sin_e;
+
+        let (sin_w, cos_w) = self.arg_of_perigee.to_radians().sin_cos();
+        let (sin_o, cos_o) = self.raan.to_radians().sin_cos();
+        let (sin_i, cos_i) = self.inclination.to_radians().sin_cos();
+        Ok([
+            (cos_o * cos_w - sin_o * sin_w * cos_i) * px
+                + (-cos_o * sin_w - sin_o * cos_w * cos_i) * py,
+            (sin_o * cos_w + cos_o * sin_w * cos_i) * px
+                + (-sin_o * sin_w + cos_o * cos_w * cos_i) * py,
+            (sin_w * sin_i) * px + (cos_w * sin_i) * py,
+        ])
+    }
+}
+
+/// The fields of a two-line element set that SGP4 uses. Angles are in degrees.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tle {
+    pub catalog_number: u32,
+    pub epoch: Epoch,
+    pub bstar: f64,
+    pub inclination: f64,
+    pub raan: f64,
+    pub eccentricity: f64,
+    pub arg_of_perigee: f64,
+    pub mean_anomaly: f64,
+    /// Revolutions per day.
+    pub mean_motion: f64,
+}
+
+impl Tle {
+    pub fn parse(line_1: &str, line_2: &str) -> Result<Self, OrbitError> {
+        let (line_1, line_2) = (line_1.trim_end(), line_2.trim_end());
+        if !line_1.starts_with('1') || !line_2.starts_with('2') {
+            return Err(OrbitError::InvalidTle(
+                "expected line 1 and line 2 of a TLE".to_string(),
+            ));
+        }
+
+        let year: i32 = field(line_1, 1, 19, 20)?;
+        // Two-digit years: 57-99 are 1957-1999, 00-56 are 2000-2056.
+        let year = if year < 57 { 2000 + year } else { 1900 + year };
+        let day_of_year: f64 = field(line_1, 1, 21, 32)?;
+        let epoch = Epoch(Epoch::from_calendar(year, 1, 1, 0, 0, 0.0).0 - 1.0 + day_of_year);
+
+        Ok(Self {
+            catalog_number: field(line_1, 1, 3, 7)?,
+            epoch,
+            bstar: implied_decimal(line_1, 54, 61)?,
+            inclination: field(line_2, 2, 9, 16)?,
+            raan: field(line_2, 2, 18, 25)?,
+            eccentricity: field::<f64>(line_2, 2, 27, 33)? * 1e-7,
+            arg_of_perigee: field(line_2, 2, 35, 42)?,
+            mean_anomaly: field(line_2, 2, 44, 51)?,
+            mean_motion: field(line_2, 2, 53, 63)?,
+        })
+    }
+}
+
+// Reads the 1-based, inclusive column range `first..=last` of a TLE line.
+fn field<T: std::str::FromStr>(
+    line: &str,
+    number: u8,
+    first: usize,
+    last: usize,
+) -> Result<T, OrbitError> {
+    let text = line
+        .get(first - 1..last.min(line.len()))
+        .unwrap_or("")
+        .trim();
+    text.parse().map_err(|_| {
+        OrbitError::InvalidTle(format!(
+            "line {} columns {}-{} ({:?}) are not a number",
+            number, first, last, text
+        ))
+    })
+}
+
+// Fields like " 28098-4" mean 0.28098e-4.
+fn implied_decimal(line: &str, first: usize, last: usize) -> Result<f64, OrbitError> {
+    let text = line
+        .get(first - 1..last.min(line.len()))
+        .unwrap_or("")
+        .trim();
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, text.trim_start_matches('+')),
+    };
+    let split = digits.rfind(['-', '+']).unwrap_or(digits.len());
+    let (mantissa, exponent) = digits.split_at(split);
+    let invalid = || {
+        OrbitError::InvalidTle(format!(
+            "line 1 columns {}-{} ({:?}) are not a number",
+            first, last, text
+        ))
+    };
+    let mantissa: f64 = format!("0.{}", mantissa).parse().map_err(|_| invalid())?;
+    let exponent: i32 = if exponent.is_empty() {
+        0
+    } else {
+        exponent.parse().map_err(|_| invalid())?
+    };
+    Ok(sign * mantissa * 10f64.powi(exponent))
+}
+
+/// Near-Earth SGP4 propagator (Hoots & Roehrich, Spacetrack Report #3, with Vallado's
+/// 2006 corrections) initialized from one TLE. Outputs are TEME positions in km and
+/// velocities in km/s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sgp4 {
+    epoch: Epoch,
+    bstar: f64,
+    ecco: f64,
+    inclo: f64,
+    nodeo: f64,
+    argpo: f64,
+    mo: f64,
+    no_unkozai: f64,
+    isimp: bool,
+    aycof: f64,
+    con41: f64,
+    cc1: f64,
+    cc4: f64,
+    cc5: f64,
+    d2: f64,
+    d3: f64,
+    d4: f64,
+    delmo: f64,
+    eta: f64,
+    argpdot: f64,
+    omgcof: f64,
+    sinmao: f64,
+    t2cof: f64,
+    t3cof: f64,
+    t4cof: f64,
+    t5cof: f64,
+    x1mth2: f64,
+    x7thm1: f64,
+    mdot: f64,
+    nodedot: f64,
+    xlcof: f64,
+    xmcof: f64,
+    nodecf: f64,
+}
+
+fn xke() -> f64 {
+    60.0 / (RADIUS_WGS72.powi(3) / MU_WGS72).sqrt()
+}
+
+impl Sgp4 {
+    pub fn new(tle: &Tle) -> Result<Self, OrbitError> {
+        let xke = xke();
+        let j3oj2 = J3 / J2;
+        let x2o3 = 2.0 / 3.0;
+
+        let ecco = tle.eccentricity;
+        let inclo = tle.inclination.to_radians();
+        let no_kozai = tle.mean_motion * TAU / MINUTES_PER_DAY;
+        if !(0.0..1.0).contains(&ecco) || no_kozai <= 0.0 {
+            return Err(OrbitError::InvalidElements(format!(
+                "eccentricity {} and mean motion {} rev/day are not a bound orbit",
+                ecco, tle.mean_motion
+            )));
+        }
+
+        // Recover the original mean motion and semi-major axis from the Kozai mean motion.
+        let eccsq = ecco * ecco;
+        let omeosq = 1.0 - eccsq;
+        let rteosq = omeosq.sqrt();
+        let cosio = inclo.cos();
+        let cosio2 = cosio * cosio;
+        let ak = (xke / no_kozai).powf(x2o3);
+        let d1 = 0.75 * J2 * (3.0 * cosio2 - 1.0) / (rteosq * omeosq);
+        let del = d1 / (ak * ak);
+        let adel = ak * (1.0 - del * del - del * (1.0 / 3.0 + 134.0 * del * del / 81.0));
+        let del = d1 / (adel * adel);
+        let no_unkozai = no_kozai / (1.0 + del);
+
+        let period_minutes = TAU / no_unkozai;
+        if period_minutes >= DEEP_SPACE_PERIOD_MINUTES {
+            return Err(OrbitError::DeepSpace { period_minutes });
+        }
+
+        let ao = (xke / no_unkozai).powf(x2o3);
+        let sinio = inclo.sin();
+        let po = ao * omeosq;
+        let con42 = 1.0 - 5.0 * cosio2;
+        let con41 = -con42 - cosio2 - cosio2;
+        let posq = po * po;
+        let rp = ao * (1.0 - ecco);
+
+        let ss = 78.0 / RADIUS_WGS72 + 1.0;
+        let qzms2t = ((120.0 - 78.0) / RADIUS_WGS72).powi(4);
+        // Low perigees get the simplified drag model.
+        let isimp = rp < 220.0 / RADIUS_WGS72 + 1.0;
+
+        let mut sfour = ss;
+        let mut qzms24 = qzms2t;
+        let perigee = (rp - 1.0) * RADIUS_WGS72;
+        if perigee < 156.0 {
+            sfour = if perigee < 98.0 { 20.0 } else { perigee - 78.0 };
+            qzms24 = ((120.0 - sfour) / RADIUS_WGS72).powi(4);
+            sfour = sfour / RADIUS_WGS72 + 1.0;
+        }
+
+        let pinvsq = 1.0 / posq;
+        let tsi = 1.0 / (ao - sfour);
+        let eta = ao * ecco * tsi;
+        let etasq = eta * eta;
+        let eeta = ecco * eta;
+        let psisq = (1.0 - etasq).abs();
+        let coef = qzms24 * tsi.powi(4);
+        let coef1 = coef / psisq.powf(3.5);
+        let cc2 = coef1
+            * no_unkozai
+            * (ao * (1.0 + 1.5 * etasq + eeta * (4.0 + etasq))
+                + 0.375 * J2 * tsi / psisq * con41 * (8.0 + 3.0 * etasq * (8.0 + etasq)));
+        let cc1 = tle.bstar * cc2;
+        let cc3 = if ecco > 1.0e-4 {
+            -2.0 * coef * tsi * j3oj2 * no_unkozai * sinio / ecco
+        } else {
+            0.0
+        };
+        let x1mth2 = 1.0 - cosio2;
+        let argpo = tle.arg_of_perigee.to_radians();
+        let cc4 = 2.0
+            * no_unkozai
+            * coef1
+            * ao
+            * omeosq
+            * (eta * (2.0 + 0.5 * etasq) + ecco * (0.5 + 2.0 * etasq)
+                - J2 * tsi / (ao * psisq)
+                    * (-3.0 * con41 * (1.0 - 2.0 * eeta + etasq * (1.5 - 0.5 * eeta))
+                        + 0.75
+                            * x1mth2
+                            * (2.0 * etasq - eeta * (1.0 + etasq))
+                            * (2.0 * argpo).cos()));
+        let cc5 = 2.0 * coef1 * ao * omeosq * (1.0 + 2.75 * (etasq + eeta) + eeta * etasq);
+
+        let cosio4 = cosio2 * cosio2;
+        let temp1 = 1.5 * J2 * pinvsq * no_unkozai;
+        let temp2 = 0.5 * temp1 * J2 * pinvsq;
+        let temp3 = -0.46875 * J4 * pinvsq * pinvsq * no_unkozai;
+        let mdot = no_unkozai
+            + 0.5 * temp1 * rteosq * con41
+            + 0.0625 * temp2 * rteosq * (13.0 - 78.0 * cosio2 + 137.0 * cosio4);
+        let argpdot = -0.5 * temp1 * con42
+            + 0.0625 * temp2 * (7.0 - 114.0 * cosio2 + 395.0 * cosio4)
+            + temp3 * (3.0 - 36.0 * cosio2 + 49.0 * cosio4);
+        let xhdot1 = -temp1 * cosio;
+        let nodedot = xhdot1
+            + (0.5 * temp2 * (4.0 - 19.0 * cosio2) + 2.0 * temp3 * (3.0 - 7.0 * cosio2)) * cosio;
+        let omgcof = tle.bstar * cc3 * argpo.cos();
+        let xmcof = if ecco > 1.0e-4 {
+            -x2o3 * coef * tle.bstar / eeta
+        } else {
+            0.0
+        };
+        let nodecf = 3.5 * omeosq * xhdot1 * cc1;
+        let t2cof = 1.5 * cc1;
+        // Avoid dividing by zero for retrograde equatorial orbits.
+        let xlcof_divisor = if (cosio + 1.0).abs() > 1.5e-12 {
+            1.0 + cosio
+        } else {
+            1.5e-12
+        };
+        let xlcof = -0.25 * j3oj2 * sinio * (3.0 + 5.0 * cosio) / xlcof_divisor;
+        let aycof = -0.5 * j3oj2 * sinio;
+        let mo = tle.mean_anomaly.to_radians();
+        let delmo = (1.0 + eta * mo.cos()).powi(3);
+        let sinmao = mo.sin();
+        let x7thm1 = 7.0 * cosio2 - 1.0;
+
+        let (mut d2, mut d3, mut d4) = (0.0, 0.0, 0.0);
+        let (mut t3cof, mut t4cof, mut t5cof) = (0.0, 0.0, 0.0);
+        if !isimp {
+            let cc1sq = cc1 * cc1;
+            d2 = 4.0 * ao * tsi * cc1sq;
+            let temp = d2 * tsi * cc1 / 3.0;
+            d3 = (17.0 * ao + sfour) * temp;
+            d4 = 0.5 * temp * ao * tsi * (221.0 * ao + 31.0 * sfour) * cc1;
+            t3cof = d2 + 2.0 * cc1sq;
+            t4cof = 0.25 * (3.0 * d3 + cc1 * (12.0 * d2 + 10.0 * cc1sq));
+            t5cof = 0.2
+                * (3.0 * d4 + 12.0 * cc1 * d3 + 6.0 * d2 * d2 + 15.0 * cc1sq * (2.0 * d2 + cc1sq));
+        }
+
+        Ok(Self {
+            epoch: tle.epoch,
+            bstar: tle.bstar,
+            ecco,
+            inclo,
+            nodeo: tle.raan.to_radians(),
+            argpo,
+            mo,
+            no_unkozai,
+            isimp,
+            aycof,
+            con41,
+            cc1,
+            cc4,
+            cc5,
+            d2,
+            d3,
+            d4,
+            delmo,
+            eta,
+            argpdot,
+            omgcof,
+            sinmao,
+            t2cof,
+            t3cof,
+            t4cof,
+            t5cof,
+            x1mth2,
+            x7thm1,
+            mdot,
+            nodedot,
+            xlcof,
+            xmcof,
+            nodecf,
+        })
+    }
+
+    pub fn epoch(&self) -> Epoch {
+        self.epoch
+    }
+
+    /// TEME position (km) and velocity (km/s) `minutes` after the TLE epoch.
+    pub fn propagate(&self, minutes: f64) -> Result<([f64; 3], [f64; 3]), OrbitError> {
+        let xke = xke();
+        let x2o3 = 2.0 / 3.0;
+        let t = minutes;
+        let decayed = || OrbitError::Decayed {
+            minutes_since_epoch: minutes,
+        };
+
+        // Secular gravity and atmospheric drag.
+        let xmdf = self.mo + self.mdot * t;
+        let argpdf = self.argpo + self.argpdot * t;
+        let nodedf = self.nodeo + self.nodedot * t;
+        let mut argpm = argpdf;
+        let mut mm = xmdf;
+        let t2 = t * t;
+        let mut nodem = nodedf + self.nodecf * t2;
+        let mut tempa = 1.0 - self.cc1 * t;
+        let mut tempe = self.bstar * self.cc4 * t;
+        let mut templ = self.t2cof * t2;
+
+        if !self.isimp {
+            let delomg = self.omgcof * t;
+            let delm = self.xmcof * ((1.0 + self.eta * xmdf.cos()).powi(3) - self.delmo);
+            let temp = delomg + delm;
+            mm = xmdf + temp;
+            argpm = argpdf - temp;
+            let t3 = t2 * t;
+            let t4 = t3 * t;
+            tempa = tempa - self.d2 * t2 - self.d3 * t3 - self.d4 * t4;
+            tempe += self.bstar * self.cc5 * (mm.sin() - self.sinmao);
+            templ += self.t3cof * t3 + t4 * (self.t4cof + t * self.t5cof);
+        }
+
+        let am = (xke / self.no_unkozai).powf(x2o3) * tempa * tempa;
+        let nm = xke / am.powf(1.5);
+        let mut em = self.ecco - tempe;
+        if !(-0.001..1.0).contains(&em) {
+            return Err(decayed());
+        }
+        if em < 1.0e-6 {
+            em = 1.0e-6;
+        }
+        mm += self.no_unkozai * templ;
+        let xlm = (mm + argpm + nodem).rem_euclid(TAU);
+        nodem = nodem.rem_euclid(TAU);
+        argpm = argpm.rem_euclid(TAU);
+        mm = (xlm - argpm - nodem).rem_euclid(TAU);
+
+        let (sinip, cosip) = self.inclo.sin_cos();
+
+        // Long-period periodics.
+        let axnl = em * argpm.cos();
+        let temp = 1.0 / (am * (1.0 - em * em));
+        let aynl = em * argpm.sin() + temp * self.aycof;
+        let xl = mm + argpm + nodem + temp * self.xlcof * axnl;
+
+        // Kepler's equation, in the Vallado form with a clamped Newton step.
+        let u = (xl - nodem).rem_euclid(TAU);
+        let mut eo1 = u;
+        let (mut sineo1, mut coseo1) = (0.0, 0.0);
+        for _ in 0..10 {
+            (sineo1, coseo1) = eo1.sin_cos();
+            let mut step =
+                (u - aynl * coseo1 + axnl * sineo1 - eo1) / (1.0 - coseo1 * axnl - sineo1 * aynl);
+            if step.abs() >= 0.95 {
+                step = 0.95 * step.signum();
+            }
+            eo1 += step;
+            if step.abs() < 1.0e-12 {
+                break;
+            }
+        }
+
+        // Short-period preliminary quantities.
+        let ecose = axnl * coseo1 + aynl * sineo1;
+        let esine = axnl * sineo1 - aynl * coseo1;
+        let el2 = axnl * axnl + aynl * aynl;
+        let pl = am * (1.0 - el2);
+        if pl < 0.0 {
+            return Err(decayed());
+        }
+        let rl = am * (1.0 - ecose);
+        let rdotl = am.sqrt() * esine / rl;
+        let rvdotl = pl.sqrt() / rl;
+        let betal = (1.0 - el2).sqrt();
+        let temp = esine / (1.0 + betal);
+        let sinu = am / rl * (sineo1 - aynl - axnl * temp);
+        let cosu = am / rl * (coseo1 - axnl + aynl * temp);
+        let mut su = sinu.atan2(cosu);
+        let sin2u = (cosu + cosu) * sinu;
+        let cos2u = 1.0 - 2.0 * sinu * sinu;
+        let temp = 1.0 / pl;
+        let temp1 = 0.5 * J2 * temp;
+        let temp2 = temp1 * temp;
+
+        // Short-period periodics.
+        let mrt = rl * (1.0 - 1.5 * temp2 * betal * self.con41) + 0.5 * temp1 * self.x1mth2 * cos2u;
+        su -= 0.25 * temp2 * self.x7thm1 * sin2u;
+        let xnode = nodem + 1.5 * temp2 * cosip * sin2u;
+        let xinc = self.inclo + 1.5 * temp2 * cosip * sinip * cos2u;
+        let mvt = rdotl - nm * temp1 * self.x1mth2 * sin2u / xke;
+        let rvdot = rvdotl + nm * temp1 * (self.x1mth2 * cos2u + 1.5 * self.con41) / xke;
+        if mrt < 1.0 {
+            return Err(decayed());
+        }
+
+        // Orientation vectors.
+        let (sinsu, cossu) = su.sin_cos();
+        let (snod, cnod) = xnode.sin_cos();
+        let (sini, cosi) = xinc.sin_cos();
+        let xmx = -snod * cosi;
+        let xmy = cnod * cosi;
+        let ux = xmx * sinsu + cnod * cossu;
+        let uy = xmy * sinsu + snod * cossu;
+        let uz = sini * sinsu;
+        let vx = xmx * cossu - cnod * sinsu;
+        let vy = xmy * cossu - snod * sinsu;
+        let vz = sini * cossu;
+
+        let r = mrt * RADIUS_WGS72;
+        let v = RADIUS_WGS72 * xke / 60.0;
+        Ok((
+            [r * ux, r * uy, r * uz],
+            [
+                (mvt * ux + rvdot * vx) * v,
+                (mvt * uy + rvdot * vy) * v,
+                (mvt * uz + rvdot * vz) * v,
+            ],
+        ))
+    }
+}
+
+/// How a satellite moves: two-body Keplerian elements or a TLE propagated with SGP4.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Orbit {
+    Kepler(KeplerianElements),
+    /// The TLE's two lines as read, so they can be written back unchanged, and the
+    /// propagator built from them.
+    Sgp4 {
+        tle: [String; 2],
+        sgp4: Box<Sgp4>,
+    },
+}
+
+impl Orbit {
+    pub fn from_tle(line_1: &str, line_2: &str) -> Result<Self, OrbitError> {
+        let sgp4 = Sgp4::new(&Tle::parse(line_1, line_2)?)?;
+        Ok(Self::Sgp4 {
+            tle: [line_1.trim_end().to_string(), line_2.trim_end().to_string()],
+            sgp4: Box::new(sgp4),
+        })
+    }
+
+    pub fn epoch(&self) -> Epoch {
+        match self {
+            Self::Kepler(elements) => elements.epoch,
+            Self::Sgp4 { sgp4, .. } => sgp4.epoch(),
+        }
+    }
+
+    /// Inertial position in km at `at`.
+    pub fn position_inertial(&self, at: Epoch) -> Result<[f64; 3], OrbitError> {
+        match self {
+            Self::Kepler(elements) => elements.position(at),
+            Self::Sgp4 { sgp4, .. } => Ok(sgp4.propagate(at.minutes_since(sgp4.epoch()))?.0),
+        }
+    }
+
+    /// Earth-fixed position in km at `at`, in the frame scenario files use.
+    pub fn position_ecef(&self, at: Epoch) -> Result<Vector3, OrbitError> {
+        let [x, y, z] = self.position_ecef_f64(at)?;
+        Ok(Vector3::new(x as f32, y as f32, z as f32))
+    }
+
+    /// `position_ecef` at full precision.
+    pub fn position_ecef_f64(&self, at: Epoch) -> Result<[f64; 3], OrbitError> {
+        Ok(inertial_to_ecef(self.position_inertial(at)?, at))
+    }
+
+    /// Semi-major axis (km) and eccentricity of the mean orbit.
+    fn shape(&self) -> (f64, f64) {
+        match self {
+            Self::Kepler(elements) => (elements.semi_major_axis, elements.eccentricity),
+            Self::Sgp4 { sgp4, .. } => (
+                (xke() / sgp4.no_unkozai).powf(2.0 / 3.0) * RADIUS_WGS72,
+                sgp4.ecco,
+            ),
+        }
+    }
+
+    /// Lowest radius (km) the satellite reaches. SGP4 perturbations and drag move real
+    /// orbits a little, so this is padded by 2%.
+    pub fn min_radius(&self) -> f64 {
+        let (a, e) = self.shape();
+        a * (1.0 - e) * 0.98
+    }
+
+    /// Upper bound on the satellite's speed (km/s) in the Earth-fixed frame: perigee
+    /// speed of the padded orbit plus the frame's rotation at apogee.
+    pub fn max_speed(&self) -> f64 {
+        const EARTH_ROTATION: f64 = 7.292115e-5;
+        let (a, e) = self.shape();
+        let perigee = self.min_radius();
+        let apogee = a * (1.0 + e) * 1.02;
+        (MU_WGS72.max(MU_WGS84) * (2.0 / perigee - 1.0 / (a * 1.02))).sqrt()
+            + EARTH_ROTATION * apogee
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Vallado, "Revisiting Spacetrack Report #3" (AIAA 2006-6753), verification case 00005.
+    const TLE_00005: (&str, &str) = (
+        "1 00005U 58002B   00179.78495062  .00000023  00000-0  28098-4 0  4753",
+        "2 00005  34.2682 348.7242 1859667 331.7664  19.3264 10.82419157413667",
+    );
+
+    fn assert_close(actual: [f64; 3], expected: [f64; 3], tolerance: f64) {
+        for i in 0..3 {
+            assert!(
+                (actual[i] - expected[i]).abs() < tolerance,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_sgp4_reference_vectors() {
+        let sgp4 = Sgp4::new(&Tle::parse(TLE_00005.0, TLE_00005.1).unwrap()).unwrap();
+        // Minutes since epoch, TEME position (km), velocity (km/s), from tcppver.out.
+        let expected = [
+            (
+                0.0,
+                [7022.46529266, -1400.08296755, 0.03995155],
+                [1.893841015, 6.405893759, 4.534807250],
+            ),
+            (
+                360.0,
+                [-7154.03120202, -3783.17682504, -3536.19412294],
+                [4.741887409, -4.151817765, -2.093935425],
+            ),
+            (
+                720.0,
+                [-7134.59340119, 6531.68641334, 3260.27186483],
+                [-4.113793027, -2.911922039, -2.557327851],
+            ),
+        ];
+        for (minutes, position, velocity) in expected {
+            let (r, v) = sgp4.propagate(minutes).unwrap();
+            assert_close(r, position, 1e-6);
+            assert_close(v, velocity, 1e-8);
+        }
+    }
+
+    #[test]
+    fn test_sgp4_spacetrack_report_case() {
+        // Spacetrack Report #3 test case 88888, as recomputed by Vallado.
+        let sgp4 = Sgp4::new(
+            &Tle::parse(
+                "1 88888U          80275.98708465  .00073094  13844-3  66816-4 0    87",
+                "2 88888  72.8435 115.9689 0086731  52.6988 110.5714 16.05824518  1058",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let (r, v) = sgp4.propagate(0.0).unwrap();
+        assert_close(r, [2328.96975262, -5995.22051338, 1719.97297192], 1e-6);
+        assert_close(v, [2.912073281, -0.983417956, -7.090816210], 1e-8);
+    }
+
+    #[test]
+    fn test_tle_parsing() {
+        let tle = Tle::parse(TLE_00005.0, TLE_00005.1).unwrap();
+        assert_eq!(tle.catalog_number, 5);
+        assert!((tle.bstar - 0.28098e-4).abs() < 1e-15);
+        assert!((tle.eccentricity - 0.1859667).abs() < 1e-12);
+        assert!((tle.epoch.0 - 2451723.28495062).abs() < 1e-8);
+
+        assert!(matches!(
+            Tle::parse(TLE_00005.0, "2 00005  34.2682"),
+            Err(OrbitError::InvalidTle(_))
+        ));
+        // Geostationary: needs SDP4.
+        assert!(matches!(
+            Orbit::from_tle(
+                "1 28626U 05004A   08264.53564731 -.00000271  00000-0  10000-3 0  4458",
+                "2 28626   0.0223 305.6180 0002315 109.5880 247.6730  1.00271386 13247",
+            ),
+            Err(OrbitError::DeepSpace { .. })
+        ));
+    }
+
+    #[test]
+    fn test_epochs() {
+        // Vallado example 3-5: 1992-08-20 12:14 UT1 has GMST 152.578787810 degrees.
+        let epoch = Epoch::from_calendar(1992, 8, 20, 12, 14, 0.0);
+        assert!((epoch.0 - 2448855.009722).abs() < 1e-6);
+        assert!((epoch.gmst().to_degrees() - 152.578787810).abs() < 1e-6);
+
+        assert_eq!(Epoch::parse("2000-01-01T12:00:00Z").unwrap(), Epoch(J2000));
+        assert_eq!(Epoch::parse("2451545.0").unwrap(), Epoch(J2000));
+        assert_eq!(Epoch::from_unix_seconds(946728000.0), Epoch(J2000));
+        assert!(Epoch::parse("yesterday").is_err());
+        assert_eq!(
+            Epoch::parse("2024-02-29T23:59:60.5Z").unwrap(),
+            Epoch::from_calendar(2024, 2, 29, 23, 59, 60.5)
+        );
+        for bad in [
+            "NaN",
+            "inf",
+            "-inf",
+            "2024-13-45T99:99:99Z",
+            "2024-00-01T00:00:00Z",
+            "2024-13-01T00:00:00Z",
+            "2024-01-00T00:00:00Z",
+            "2024-04-31T00:00:00Z",
+            "2023-02-29T00:00:00Z",
+            "1900-02-29T00:00:00Z",
+            "2024-01-01T24:00:00Z",
+            "2024-01-01T00:60:00Z",
+            "2024-01-01T00:00:61Z",
+            "2024-01-01T00:00:-1Z",
+            "2024-01-01T00:00:NaNZ",
+        ] {
+            assert!(
+                matches!(Epoch::parse(bad), Err(OrbitError::InvalidEpoch(_))),
+                "{}",
+                bad
+            );
+        }
+        assert!((Epoch(J2000).plus_seconds(90.0).seconds_since(Epoch(J2000)) - 90.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_kepler_circular_orbit() {
+        let elements = KeplerianElements {
+            semi_major_axis: 6921.0,
+            eccentricity: 0.0,
+            inclination: 90.0,
+            raan: 0.0,
+            arg_of_perigee: 0.0,
+            mean_anomaly: 0.0,
+            epoch: Epoch(J2000),
+        };
+        assert_close(
+            elements.position(Epoch(J2000)).unwrap(),
+            [6921.0, 0.0, 0.0],
+            1e-9,
+        );
+        // A quarter period later a polar orbit with RAAN 0 is over the north pole.
+        let quarter = TAU / elements.mean_motion() / 4.0;
+        let position = elements
+            .position(Epoch(J2000).plus_seconds(quarter))
+            .unwrap();
+        assert_close(position, [0.0, 0.0, 6921.0], 1e-3);
+
+        for broken in [
+            KeplerianElements {
+                semi_major_axis: f64::NAN,
+                ..elements.clone()
+            },
+            KeplerianElements {
+                semi_major_axis: f64::INFINITY,
+                ..elements.clone()
+            },
+            KeplerianElements {
+                inclination: f64::NAN,
+                ..elements.clone()
+            },
+            KeplerianElements {
+                raan: f64::INFINITY,
+                ..elements.clone()
+            },
+            KeplerianElements {
+                arg_of_perigee: f64::NEG_INFINITY,
+                ..elements.clone()
+            },
+            KeplerianElements {
+                mean_anomaly: f64::NAN,
+                ..elements.clone()
+            },
+        ] {
+            assert!(matches!(
+                broken.validate(),
+                Err(OrbitError::InvalidElements(_))
+            ));
+        }
+
+        // ECEF keeps the radius and the z component, and only rotates about z.
+        let orbit = Orbit::Kepler(elements);
+        let ecef = orbit.position_ecef(Epoch(J2000)).unwrap();
+        assert!((ecef.length() - 6921.0).abs() < 1e-2);
+        assert_eq!(ecef.to_array()[2], 0.0);
+    }
+}
@@ -0,0 +1,732 @@
+use crate::orbit::{Epoch, KeplerianElements, Orbit, OrbitError};
+use crate::scenario::Scenario;
+use crate::util::{Color, Sat, User, Vector3};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    /// The file couldn't be read.
+    Io(String),
+    /// The line ended before `field`.
+    MissingField(&'static str),
+    /// `field` isn't a finite number.
+    BadFloat {
+        field: &'static str,
+        text: String,
+    },
+    /// `field` isn't a non-negative integer.
+    BadInteger {
+        field: &'static str,
+        text: String,
+    },
+    /// A satellite, user or weight id that `first_line` already defined. The
+    /// `*_lenient` readers record these in `Scenario::duplicates` instead.
+    DuplicateId {
+        kind: &'static str,
+        id: u64,
+        first_line: usize,
+    },
+    UnknownDirective(String),
+    /// More fields than the directive takes.
+    UnexpectedField(String),
+    /// Satellites have orbits but no line says when to place them.
+    MissingEpoch,
+    /// Unreadable epoch, invalid elements or TLE, or an orbit that can't be propagated
+    /// to the scenario's epoch.
+    Orbit(OrbitError),
+    /// Malformed JSON or CSV.
+    Syntax(String),
+    /// A solution color other than 1 to 4.
+    BadColor(String),
+    /// A solution assigns `user` again after `first_line`.
+    DuplicateAssignment {
+        user: u64,
+        first_line: usize,
+    },
+}
+
+/// A satellite, user or weight id that `line` defines again after `first_line`. The
+/// lenient readers keep the first definition and leave it to `Scenario::validate` to
+/// complain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicate {
+    pub kind: &'static str,
+    pub id: u64,
+    pub line: usize,
+    pub first_line: usize,
+}
+
+/// Where and why a scenario file failed to parse. Lines and columns count from 1, and a
+/// column of 0 means the whole line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Io(message) => write!(f, "{}", message),
+            Self::MissingField(field) => write!(f, "missing {}", field),
+            Self::BadFloat { field, text } => write!(f, "{} {:?} is not a number", field, text),
+            Self::BadInteger { field, text } => {
+                write!(f, "{} {:?} is not a non-negative integer", field, text)
+            }
+            Self::DuplicateId {
+                kind,
+                id,
+                first_line,
+            } => write!(
+                f,
+                "{} {} was already defined on line {}",
+                kind, id, first_line
+            ),
+            Self::UnknownDirective(directive) => write!(f, "unknown directive {:?}", directive),
+            Self::UnexpectedField(text) => write!(f, "unexpected field {:?}", text),
+            Self::MissingEpoch => write!(f, "satellites with orbits need an `epoch` line"),
+            Self::Orbit(error) => write!(f, "{}", error),
+            Self::Syntax(message) => write!(f, "{}", message),
+            Self::BadColor(text) => write!(f, "color {:?} is not 1, 2, 3 or 4", text),
+            Self::DuplicateAssignment { user, first_line } => {
+                write!(
+                    f,
+                    "user {} was already assigned on line {}",
+                    user, first_line
+                )
+            }
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.column > 0 {
+            write!(
+                f,
+                "{}:{}:{}: {}",
+                self.file, self.line, self.column, self.kind
+            )
+        } else {
+            write!(f, "{}:{}: {}", self.file, self.line, self.kind)
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Line, column and kind of an error, before the file name is attached.
+pub(crate) type Located = (usize, usize, ParseErrorKind);
+
+/// Attaches `file` to errors from a parser that doesn't know its name.
+pub(crate) fn in_file(file: &str) -> impl Fn(Located) -> ParseError + '_ {
+    move |(line, column, kind)| ParseError {
+        file: file.to_string(),
+        line,
+        column,
+        kind,
+    }
+}
+
+pub(crate) fn io_error(path: &str, error: std::io::Error) -> ParseError {
+    ParseError {
+        file: path.to_string(),
+        line: 0,
+        column: 0,
+        kind: ParseErrorKind::Io(error.to_string()),
+    }
+}
+
+/// The fields of one line, with the columns they start at.
+pub(crate) struct Fields<'a> {
+    line: &'a str,
+    number: usize,
+    rest: std::vec::IntoIter<&'a str>,
+    // Column just past the last field handed out, for errors about missing fields.
+    end: usize,
+}
+
+impl<'a> Fields<'a> {
+    /// Whitespace-separated fields, as in the text formats.
+    pub(crate) fn whitespace(line: &'a str, number: usize) -> Self {
+        Self {
+            line,
+            number,
+            rest: line.split_whitespace().collect::<Vec<_>>().into_iter(),
+            end: 0,
+        }
+    }
+
+    /// Comma-separated fields with the surrounding whitespace trimmed. There is no
+    /// quoting; none of our CSV fields need it.
+    pub(crate) fn csv(line: &'a str, number: usize) -> Self {
+        Self {
+            line,
+            number,
+            rest: line
+                .split(',')
+                .map(str::trim)
+                .collect::<Vec<_>>()
+                .into_iter(),
+            end: 0,
+        }
+    }
+
+    // Byte offset of `field`, which must be a slice of `line`.
+    fn offset(&self, field: &str) -> usize {
+        field.as_ptr() as usize - self.line.as_ptr() as usize
+    }
+
+    fn column(&self, field: &str) -> usize {
+        self.line[..self.offset(field)].chars().count() + 1
+    }
+
+    pub(crate) fn error(&self, column: usize, kind: ParseErrorKind) -> Located {
+        (self.number, column, kind)
+    }
+
+    pub(crate) fn next(&mut self, field: &'static str) -> Result<(&'a str, usize), Located> {
+        match self.rest.next() {
+            Some(text) => {
+                let column = self.column(text);
+                self.end = column + text.chars().count();
+                Ok((text, column))
+            }
+            None => Err(self.error(self.end + 1, ParseErrorKind::MissingField(field))),
+        }
+    }
+
+    pub(crate) fn float<T: FromStr + Into<f64> + Copy>(
+        &mut self,
+        field: &'static str,
+    ) -> Result<T, Located> {
+        let (text, column) = self.next(field)?;
+        match text.parse::<T>() {
+            Ok(value) if value.into().is_finite() => Ok(value),
+            _ => Err(self.error(
+                column,
+                ParseErrorKind::BadFloat {
+                    field,
+                    text: text.to_string(),
+                },
+            )),
+        }
+    }
+
+    pub(crate) fn id(&mut self, field: &'static str) -> Result<(u64, usize), Located> {
+        let (text, column) = self.next(field)?;
+        text.parse().map(|id| (id, column)).map_err(|_| {
+            self.error(
+                column,
+                ParseErrorKind::BadInteger {
+                    field,
+                    text: text.to_string(),
+                },
+            )
+        })
+    }
+
+    pub(crate) fn epoch(&mut self, field: &'static str) -> Result<Epoch, Located> {
+        let (text, column) = self.next(field)?;
+        Epoch::parse(text).map_err(|error| self.error(column, ParseErrorKind::Orbit(error)))
+    }
+
+    /// Fails on any field left over. Empty CSV fields don't count.
+    pub(crate) fn finish(&mut self) -> Result<(), Located> {
+        match self.rest.find(|text| !text.is_empty()) {
+            Some(text) => Err(self.error(
+                self.column(text),
+                ParseErrorKind::UnexpectedField(text.to_string()),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reads and parses the scenario file at `path`.
+pub fn read_scenario(path: &str) -> Result<Scenario, ParseError> {
+    read_scenario_lenient(path).and_then(|scenario| reject_duplicates(scenario, path))
+}
+
+/// Parses a scenario from `text`, using `file` in errors. Never panics: every malformed
+/// line, including one that defines an id again, comes back as a `ParseError`.
+pub fn parse_scenario(text: &str, file: &str) -> Result<Scenario, ParseError> {
+    parse_scenario_lenient(text, file).and_then(|scenario| reject_duplicates(scenario, file))
+}
+
+/// Like `read_scenario`, but see `parse_scenario_lenient`.
+pub fn read_scenario_lenient(path: &str) -> Result<Scenario, ParseError> {
+    let text = std::fs::read_to_string(path).map_err(|error| io_error(path, error))?;
+    parse_scenario_lenient(&text, path)
+}
+
+/// Like `parse_scenario`, but an id defined again keeps its first definition and is
+/// recorded in `Scenario::duplicates`, for `Scenario::validate` to report with the
+/// rest of the file's anomalies.
+pub fn parse_scenario_lenient(text: &str, file: &str) -> Result<Scenario, ParseError> {
+    parse_lines(text).map_err(in_file(file))
+}
+
+/// Fails on the first of `scenario`'s duplicates, if any.
+pub(crate) fn reject_duplicates(scenario: Scenario, file: &str) -> Result<Scenario, ParseError> {
+    match scenario.duplicates.first() {
+        Some(duplicate) => Err(ParseError {
+            file: file.to_string(),
+            line: duplicate.line,
+            column: 0,
+            kind: ParseErrorKind::DuplicateId {
+                kind: duplicate.kind,
+                id: duplicate.id,
+                first_line: duplicate.first_line,
+            },
+        }),
+        None => Ok(scenario),
+    }
+}
+
+/// Both lines of each TLE, and the line the first one was on.
+pub(crate) type TleLines<'a> = BTreeMap<Sat, (usize, Vec<&'a str>)>;
+
+/// Reads `ID A_KM E I_DEG RAAN_DEG ARGP_DEG M_DEG EPOCH`, the fields of a `kepler` line.
+pub(crate) fn kepler(fields: &mut Fields) -> Result<(u64, KeplerianElements), Located> {
+    let (id, column) = fields.id("satellite id")?;
+    let elements = KeplerianElements {
+        semi_major_axis: fields.float("semi-major axis")?,
+        eccentricity: fields.float("eccentricity")?,
+        inclination: fields.float("inclination")?,
+        raan: fields.float("right ascension of the ascending node")?,
+        arg_of_perigee: fields.float("argument of perigee")?,
+        mean_anomaly: fields.float("mean anomaly")?,
+        epoch: fields.epoch("element epoch")?,
+    };
+    fields.finish()?;
+    elements
+        .validate()
+        .map_err(|error| fields.error(column, ParseErrorKind::Orbit(error)))?;
+    Ok((id, elements))
+}
+
+/// Adds one line of satellite `id`'s TLE, which comes as two `tle` lines. `new_sat` is
+/// asked for the satellite on its first line, and on a third, which redefines it.
+/// Returns whether this line started a TLE.
+pub(crate) fn tle_line<'a>(
+    tle_lines: &mut TleLines<'a>,
+    id: u64,
+    text: &'a str,
+    number: usize,
+    new_sat: impl FnOnce(u64) -> Option<Sat>,
+) -> bool {
+    match tle_lines.get_mut(&Sat::new(id)) {
+        Some((_, lines)) if lines.len() == 1 => {
+            lines.push(text);
+            false
+        }
+        // A new satellite, or a third line for one that already has a TLE.
+        _ => match new_sat(id) {
+            Some(sat) => {
+                tle_lines.insert(sat, (number, vec![text]));
+                true
+            }
+            None => false,
+        },
+    }
+}
+
+/// Builds the TLE orbits and places every satellite with an orbit at the scenario's
+/// epoch, which is required once `first_orbit_line` has an orbit.
+pub(crate) fn place_orbits(
+    s: &mut Scenario,
+    tle_lines: TleLines,
+    first_orbit_line: Option<usize>,
+    sat_lines: &BTreeMap<Sat, usize>,
+) -> Result<(), Located> {
+    for (sat, (number, lines)) in tle_lines {
+        let orbit = match lines[..] {
+            [line_1, line_2] => Orbit::from_tle(line_1, line_2),
+            _ => Err(OrbitError::InvalidTle(format!(
+                "satellite {} has only one TLE line",
+                sat
+            ))),
+        };
+        let orbit = orbit.map_err(|error| (number, 0, ParseErrorKind::Orbit(error)))?;
+        s.orbits.insert(sat, orbit);
+    }
+    if let Some(number) = first_orbit_line {
+        let epoch = s.epoch.ok_or((number, 0, ParseErrorKind::MissingEpoch))?;
+        for (sat, orbit) in &s.orbits {
+            let pos = orbit
+                .position_ecef(epoch)
+                .map_err(|error| (sat_lines[sat], 0, ParseErrorKind::Orbit(error)))?;
+            s.sats.insert(*sat, pos);
+        }
+    }
+    Ok(())
+}
+
+fn parse_lines(text: &str) -> Result<Scenario, Located> {
+    let mut s = Scenario::default();
+    // Line each id was defined on, to report duplicates.
+    let mut sat_lines: BTreeMap<Sat, usize> = BTreeMap::new();
+    let mut user_lines: BTreeMap<User, usize> = BTreeMap::new();
+    let mut weight_lines: BTreeMap<User, usize> = BTreeMap::new();
+    let mut tle_lines = TleLines::new();
+    let mut first_orbit_line = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let mut fields = Fields::whitespace(line, number);
+        let Some(kind) = fields.rest.next() else {
+            continue;
+        };
+        fields.end = fields.column(kind) + kind.chars().count();
+
+        // `None` if an earlier line already defined the satellite.
+        let mut new_sat = |id: u64| {
+            let sat = Sat::new(id);
+            match sat_lines.get(&sat) {
+                Some(first_line) => {
+                    s.duplicates.push(Duplicate {
+                        kind: "sat",
+                        id,
+                        line: number,
+                        first_line: *first_line,
+                    });
+                    None
+                }
+                None => {
+                    sat_lines.insert(sat, number);
+                    Some(sat)
+                }
+            }
+        };
+
+        match kind {
+            "sat" => {
+                let (id, _) = fields.id("satellite id")?;
+                let x = fields.float("x")?;
+                let y = fields.float("y")?;
+                let z = fields.float("z")?;
+                fields.finish()?;
+                if let Some(sat) = new_sat(id) {
+                    s.sats.insert(sat, Vector3::new(x, y, z));
+                }
+            }
+            "user" => {
+                let (id, _) = fields.id("user id")?;
+                let x = fields.float("x")?;
+                let y = fields.float("y")?;
+                let z = fields.float("z")?;
+                fields.finish()?;
+                let user = User::new(id);
+                match user_lines.get(&user) {
+                    Some(first_line) => s.duplicates.push(Duplicate {
+                        kind: "user",
+                        id,
+                        line: number,
+                        first_line: *first_line,
+                    }),
+                    None => {
+                        user_lines.insert(user, number);
+                        s.users.insert(user, Vector3::new(x, y, z));
+                    }
+                }
+            }
+            "weight" => {
+                let (id, _) = fields.id("user id")?;
+                let weight = fields.float("weight")?;
+                fields.finish()?;
+                let user = User::new(id);
+                match weight_lines.get(&user) {
+                    Some(first_line) => s.duplicates.push(Duplicate {
+                        kind: "weight",
+                        id,
+                        line: number,
+                        first_line: *first_line,
+                    }),
+                    None => {
+                        weight_lines.insert(user, number);
+                        s.weights.insert(user, weight);
+                    }
+                }
+            }
+            "min_coverage" => {
+                s.min_coverage = fields.float("coverage")?;
+                fields.finish()?;
+            }
+            "epoch" => {
+                s.epoch = Some(fields.epoch("epoch")?);
+                fields.finish()?;
+            }
+            "kepler" => {
+                let (id, elements) = kepler(&mut fields)?;
+                if let Some(sat) = new_sat(id) {
+                    s.orbits.insert(sat, Orbit::Kepler(elements));
+                    first_orbit_line.get_or_insert(number);
+                }
+            }
+            "tle" => {
+                let (id, _) = fields.id("satellite id")?;
+                let (text, _) = fields.next("TLE line")?;
+                // TLE columns are fixed, so keep the rest of the line verbatim.
+                let rest = line[fields.offset(text)..].trim_end();
+                if tle_line(&mut tle_lines, id, rest, number, new_sat) {
+                    first_orbit_line.get_or_insert(number);
+                }
+            }
+            _ => {
+                let column = fields.column(kind);
+                return Err(
+                    fields.error(column, ParseErrorKind::UnknownDirective(kind.to_string()))
+                );
+            }
+        }
+    }
+
+    place_orbits(&mut s, tle_lines, first_orbit_line, &sat_lines)?;
+    Ok(s)
+}
+
+type SolutionMap = BTreeMap<User, (Sat, Color)>;
+
+/// Reads and parses the solution file at `path`.
+pub fn read_solution(path: &str) -> Result<SolutionMap, ParseError> {
+    let text = std::fs::read_to_string(path).map_err(|error| io_error(path, error))?;
+    parse_solution(&text, path)
+}
+
+/// Parses a solution: one `USER SAT COLOR` line per served user, with colors 1 to 4 and
+/// the same comments as scenarios.
+pub fn parse_solution(text: &str, file: &str) -> Result<SolutionMap, ParseError> {
+    let mut solution = SolutionMap::new();
+    let mut user_lines = BTreeMap::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = Fields::whitespace(line, index + 1);
+        let (user, sat, color) = assignment(&mut fields, &mut user_lines).map_err(in_file(file))?;
+        solution.insert(user, (sat, color));
+    }
+    Ok(solution)
+}
+
+/// Reads one `USER SAT COLOR` assignment, whatever separates the fields. `user_lines`
+/// holds the line each user was assigned on so far.
+pub(crate) fn assignment(
+    fields: &mut Fields,
+    user_lines: &mut BTreeMap<User, usize>,
+) -> Result<(User, Sat, Color), Located> {
+    let (user, column) = fields.id("user id")?;
+    let (sat, _) = fields.id("satellite id")?;
+    let (color, color_column) = fields.id("color")?;
+    fields.finish()?;
+    if !(1..=4).contains(&color) {
+        return Err(fields.error(color_column, ParseErrorKind::BadColor(color.to_string())));
+    }
+    if let Some(first_line) = user_lines.insert(User::new(user), fields.number) {
+        return Err(fields.error(
+            column,
+            ParseErrorKind::DuplicateAssignment { user, first_line },
+        ));
+    }
+    Ok((User::new(user), Sat::new(sat), Color::from_id(color as i32)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> ParseError {
+        parse_scenario(text, "bad.txt").unwrap_err()
+    }
+
+    #[test]
+    fn test_parses_every_directive() {
+        let text = "\
+# comment
+min_coverage 0.5
+epoch 2008-09-20T12:25:40Z
+sat 1 6921 0 0   # trailing comment
+user 1 6371 0 0
+weight 1 2.5
+kepler 2 6921 0.001 53 10 0 0 2008-09-20T12:00:00Z
+tle 3 1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927
+tle 3 2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537
+";
+        let s = parse_scenario(text, "good.txt").unwrap();
+        assert_eq!(s.min_coverage, 0.5);
+        assert_eq!(s.sats.len(), 3);
+        assert_eq!(s.orbits.len(), 2);
+        assert_eq!(s.users[&User(1)], Vector3::new(6371.0, 0.0, 0.0));
+        assert_eq!(s.weight(User(1)), 2.5);
+        assert!((s.sats[&Sat(3)].length() - 6720.0).abs() < 30.0);
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let e = error("user 1 6371 0 0\nsat 1 6921 0\n");
+        assert_eq!((e.line, e.column), (2, 14));
+        assert_eq!(e.kind, ParseErrorKind::MissingField("z"));
+        assert_eq!(e.to_string(), "bad.txt:2:14: missing z");
+
+        let e = error("  user 1 6371 O 0\n");
+        assert_eq!((e.line, e.column), (1, 15));
+        assert_eq!(
+            e.kind,
+            ParseErrorKind::BadFloat {
+                field: "y",
+                text: "O".to_string()
+            }
+        );
+        assert!(matches!(
+            error("user 1 NaN 0 0").kind,
+            ParseErrorKind::BadFloat { .. }
+        ));
+        assert!(matches!(
+            error("user -1 0 0 0").kind,
+            ParseErrorKind::BadInteger { .. }
+        ));
+
+        let e = error("min_coverage 1\n   satellite 1 2 3 4\n");
+        assert_eq!((e.line, e.column), (2, 4));
+        assert_eq!(
+            e.kind,
+            ParseErrorKind::UnknownDirective("satellite".to_string())
+        );
+
+        let e = error("user 1 6371 0 0 7\n");
+        assert_eq!((e.line, e.column), (1, 17));
+        assert_eq!(e.kind, ParseErrorKind::UnexpectedField("7".to_string()));
+    }
+
+    #[test]
+    fn test_duplicates_keep_the_first_definition() {
+        let text = "sat 4 1 2 3\nuser 4 1 2 3\n\nsat 4 5 6 7\nuser 4 5 6 7\n\
+                    kepler 4 6921 0 53 0 0 0 2460000.5\ntle 4 1 25544U\n";
+        let e = error(text);
+        assert_eq!((e.line, e.column), (4, 0));
+        assert_eq!(
+            e.kind,
+            ParseErrorKind::DuplicateId {
+                kind: "sat",
+                id: 4,
+                first_line: 1
+            }
+        );
+        assert_eq!(
+            e.to_string(),
+            "bad.txt:4: sat 4 was already defined on line 1"
+        );
+        assert!(matches!(
+            error("user 4 1 2 3\nuser 4 1 2 3\n").kind,
+            ParseErrorKind::DuplicateId { kind: "user", .. }
+        ));
+
+        let s = parse_scenario_lenient(text, "dup.txt").unwrap();
+        assert_eq!(s.sats[&Sat(4)], Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(s.users[&User(4)], Vector3::new(1.0, 2.0, 3.0));
+        assert!(s.orbits.is_empty());
+        let found: Vec<(&str, usize, usize)> = s
+            .duplicates
+            .iter()
+            .map(|d| (d.kind, d.line, d.first_line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("sat", 4, 1), ("user", 5, 2), ("sat", 6, 1), ("sat", 7, 1)]
+        );
+    }
+
+    #[test]
+    fn test_solutions() {
+        let text = "# user sat color\n1 10 1\n\n2 10 4  # comment\n";
+        let solution = parse_solution(text, "solution.txt").unwrap();
+        assert_eq!(
+            solution.into_iter().collect::<Vec<_>>(),
+            vec![
+                (User(1), (Sat(10), Color::A)),
+                (User(2), (Sat(10), Color::D))
+            ]
+        );
+
+        let e = parse_solution("1 10 5\n", "bad.txt").unwrap_err();
+        assert_eq!((e.line, e.column), (1, 6));
+        assert_eq!(e.kind, ParseErrorKind::BadColor("5".to_string()));
+        let e = parse_solution("1 10 1\n1 11 2\n", "bad.txt").unwrap_err();
+        assert_eq!(
+            (e.line, e.kind),
+            (
+                2,
+                ParseErrorKind::DuplicateAssignment {
+                    user: 1,
+                    first_line: 1
+                }
+            )
+        );
+        let e = parse_solution("1 10\n", "bad.txt").unwrap_err();
+        assert_eq!(e.kind, ParseErrorKind::MissingField("color"));
+    }
+
+    #[test]
+    fn test_orbit_errors() {
+        let e = error("min_coverage 1\nkepler 1 6921 0 53 0 0 0 2024-01-01T00:00:00Z\n");
+        assert_eq!((e.line, e.kind), (2, ParseErrorKind::MissingEpoch));
+
+        let e = error("epoch 2460000.5\nkepler 1 6921 1.5 53 0 0 0 2460000.5\n");
+        assert_eq!((e.line, e.column), (2, 8));
+        assert!(matches!(
+            e.kind,
+            ParseErrorKind::Orbit(OrbitError::InvalidElements(_))
+        ));
+
+        let e = error("epoch yesterday\n");
+        assert!(matches!(
+            e.kind,
+            ParseErrorKind::Orbit(OrbitError::InvalidEpoch(_))
+        ));
+
+        let e = error("epoch 2460000.5\ntle 7 1 25544U 98067A   08264.51782528\n");
+        assert_eq!(e.line, 2);
+        assert!(matches!(
+            e.kind,
+            ParseErrorKind::Orbit(OrbitError::InvalidTle(_))
+        ));
+    }
+
+    #[test]
+    fn test_never_panics_on_garbage() {
+        let inputs = [
+            "",
+            "\n\n",
+            "sat",
+            "user 1",
+            "tle",
+            "tle 1",
+            "kepler 1 2",
+            "epoch",
+            "min_coverage",
+            "\u{feff}user 1 2 3 4",
+            "user 1 2 3 4 # ü ö",
+            "user ü 2 3 4",
+            "tle 1 ü",
+            "tle 1 1\ntle 1 2\ntle 1 3\n",
+        ];
+        for input in inputs {
+            let _ = parse_scenario(input, "garbage.txt");
+            let _ = parse_solution(input, "garbage.txt");
+        }
+        assert!(matches!(
+            read_scenario("/nonexistent/scenario.txt").unwrap_err().kind,
+            ParseErrorKind::Io(_)
+        ));
+    }
+}
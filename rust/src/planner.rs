@@ -0,0 +1,291 @@
+use crate::config::SolverConfig;
+use crate::greedy::{FewestOptions, HandoverPenalty};
+use crate::orbit::{Epoch, OrbitError};
+use crate::scenario::Scenario;
+use crate::solution_v;
+use crate::util::{Color, Sat, User};
+use std::collections::BTreeMap;
+
+type SolutionMap = BTreeMap<User, (Sat, Color)>;
+
+/// With `FewestOptions` scoring, weighs a handover like one more satellite option for
+/// the user.
+pub const DEFAULT_HANDOVER_PENALTY: u64 = 1 << 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct PlannerConfig {
+    pub solver: SolverConfig,
+    /// Added to the greedy score of links that would hand a served user over: links to
+    /// another satellite, and links to its satellite whose color a conflict has taken.
+    pub handover_penalty: u64,
+}
+
+impl Default for PlannerConfig {
+    fn default() -> Self {
+        Self {
+            solver: SolverConfig::default(),
+            handover_penalty: DEFAULT_HANDOVER_PENALTY,
+        }
+    }
+}
+
+/// The plan for one timestamp and how it changed from the one before.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EpochPlan {
+    pub epoch: Epoch,
+    #[cfg_attr(feature = "serde", serde(with = "crate::format::schema::solution"))]
+    pub solution: SolutionMap,
+    /// Users served in both epochs who moved to another satellite.
+    pub sat_handovers: usize,
+    /// Users served in both epochs who stayed on their satellite but changed color.
+    pub color_handovers: usize,
+    /// Users served in the previous epoch and not in this one.
+    pub dropped: usize,
+}
+
+impl EpochPlan {
+    pub fn handovers(&self) -> usize {
+        self.sat_handovers + self.color_handovers
+    }
+}
+
+/// How one user was served across the whole plan.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Continuity {
+    pub served_epochs: usize,
+    pub handovers: usize,
+    /// Times service stopped after having started, including gaps that later resume.
+    pub interruptions: usize,
+    /// Most consecutive epochs served.
+    pub longest_streak: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Plan {
+    pub epochs: Vec<EpochPlan>,
+    pub continuity: BTreeMap<User, Continuity>,
+}
+
+impl Plan {
+    pub fn handovers(&self) -> usize {
+        self.epochs.iter().map(EpochPlan::handovers).sum()
+    }
+
+    /// Fraction of epochs `user` was served in.
+    pub fn availability(&self, user: User) -> f32 {
+        let served = self.continuity.get(&user).map_or(0, |c| c.served_epochs);
+        served as f32 / self.epochs.len().max(1) as f32
+    }
+}
+
+/// `count` timestamps `step_seconds` apart, starting at `start`.
+pub fn epochs(start: Epoch, step_seconds: f64, count: usize) -> Vec<Epoch> {
+    (0..count)
+        .map(|i| start.plus_seconds(step_seconds * i as f64))
+        .collect()
+}
+
+/// Solves `scenario` at each of `epochs` in order with `solution_v`, carrying forward
+/// every assignment that is still possible and penalizing satellite and color handovers.
+pub fn plan(
+    scenario: &Scenario,
+    epochs: &[Epoch],
+    config: &PlannerConfig,
+) -> Result<Plan, OrbitError> {
+    plan_with(scenario, epochs, |snapshot, previous| {
+        let scoring = HandoverPenalty {
+            inner: FewestOptions,
+            previous,
+            penalty: config.handover_penalty,
+        };
+        solution_v::solve_from(
+            &snapshot.users,
+            &snapshot.sats,
+            &config.solver,
+            &scoring,
+            previous,
+        )
+    })
+}
+
+/// Like `plan`, with any snapshot solver. `solve` gets the scenario at the epoch and the
+/// previous epoch's solution (empty for the first).
+pub fn plan_with(
+    scenario: &Scenario,
+    epochs: &[Epoch],
+    mut solve: impl FnMut(&Scenario, &SolutionMap) -> SolutionMap,
+) -> Result<Plan, OrbitError> {
+    let mut plans: Vec<EpochPlan> = Vec::with_capacity(epochs.len());
+    let mut continuity: BTreeMap<User, Continuity> = scenario
+        .users
+        .keys()
+        .map(|user| (*user, Continuity::default()))
+        .collect();
+    let mut streaks: BTreeMap<User, usize> = BTreeMap::new();
+    let empty = SolutionMap::new();
+
+    for epoch in epochs {
+        let snapshot = scenario.at(*epoch)?;
+        let previous = plans.last().map_or(&empty, |plan| &plan.solution);
+        let solution = solve(&snapshot, previous);
+
+        let mut plan = EpochPlan {
+            epoch: *epoch,
+            solution: SolutionMap::new(),
+            sat_handovers: 0,
+            color_handovers: 0,
+            dropped: 0,
+        };
+        for (user, (sat, color)) in &solution {
+            let entry = continuity.entry(*user).or_default();
+            entry.served_epochs += 1;
+            match previous.get(user) {
+                Some((previous_sat, _)) if previous_sat != sat => {
+                    plan.sat_handovers += 1;
+                    entry.handovers += 1;
+                }
+                Some((_, previous_color)) if previous_color != color => {
+                    plan.color_handovers += 1;
+                    entry.handovers += 1;
+                }
+                _ => {}
+            }
+            let streak = streaks.entry(*user).or_default();
+            *streak += 1;
+            entry.longest_streak = entry.longest_streak.max(*streak);
+        }
+        for user in previous.keys() {
+            if !solution.contains_key(user) {
+                plan.dropped += 1;
+                continuity.entry(*user).or_default().interruptions += 1;
+                streaks.remove(user);
+            }
+        }
+        plan.solution = solution;
+        plans.push(plan);
+    }
+
+    Ok(Plan {
+        epochs: plans,
+        continuity,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orbit::{KeplerianElements, Orbit};
+    use crate::util::Vector3;
+
+    // Three close 550 km polar planes of 72 satellites over users spread along the
+    // meridian they pass over, so every user sees a few satellites rising and setting.
+    fn moving_scenario() -> Scenario {
+        let mut scenario = Scenario {
+            min_coverage: 0.0,
+            ..Default::default()
+        };
+        let start = Epoch::parse("2024-01-01T00:00:00Z").unwrap();
+        for plane in 0..3 {
+            for i in 0..72 {
+                let elements = KeplerianElements {
+                    semi_major_axis: 6921.0,
+                    eccentricity: 0.0,
+                    inclination: 90.0,
+                    raan: 2.0 * plane as f64,
+                    arg_of_perigee: 0.0,
+                    mean_anomaly: 5.0 * i as f64 + 1.7 * plane as f64,
+                    epoch: start,
+                };
+                scenario
+                    .orbits
+                    .insert(Sat(plane * 72 + i), Orbit::Kepler(elements));
+            }
+        }
+        // The planes are inertial, so place users along the meridian they cover at `start`.
+        let gmst = start.gmst() as f32;
+        for i in 0..300 {
+            let latitude = (i as f32 * 0.5 - 75.0).to_radians();
+            let longitude = ((i % 5) as f32 * 0.8).to_radians() - gmst;
+            let (sin, cos) = latitude.sin_cos();
+            let position = Vector3::new(cos * longitude.cos(), cos * longitude.sin(), sin);
+            scenario.users.insert(User(i), position.scale(6371.0));
+        }
+        scenario.at(start).unwrap()
+    }
+
+    #[test]
+    fn test_plan_carries_assignments_forward() {
+        let scenario = moving_scenario();
+        let start = scenario.epoch.unwrap();
+        let epochs = epochs(start, 10.0, 30);
+
+        let sticky = plan(&scenario, &epochs, &PlannerConfig::default()).unwrap();
+        let fresh = plan_with(&scenario, &epochs, |snapshot, _| {
+            solution_v::solve(&snapshot.users, &snapshot.sats)
+        })
+        .unwrap();
+
+        assert_eq!(sticky.epochs.len(), 30);
+        assert!(sticky.epochs[0].handovers() == 0 && sticky.epochs[0].dropped == 0);
+        // Staying put must not cost coverage, and should avoid most handovers.
+        assert!(2 * sticky.handovers() < fresh.handovers());
+        for (a, b) in sticky.epochs.iter().zip(&fresh.epochs) {
+            assert!(a.solution.len() * 20 >= b.solution.len() * 19);
+        }
+        for plan in &sticky.epochs {
+            assert!(!plan.solution.is_empty());
+            assert!(scenario
+                .at(plan.epoch)
+                .unwrap()
+                .verify(&plan.solution)
+                .is_empty());
+        }
+
+        for (user, continuity) in &sticky.continuity {
+            assert!(continuity.longest_streak <= continuity.served_epochs);
+            assert!(continuity.served_epochs <= epochs.len());
+            let availability = sticky.availability(*user);
+            assert!((0.0..=1.0).contains(&availability));
+        }
+    }
+
+    #[test]
+    fn test_plan_counts_handovers_and_gaps() {
+        let mut scenario = Scenario::default();
+        scenario.users.insert(User(0), Vector3::zero());
+        let epochs = epochs(Epoch(2460000.5), 1.0, 4);
+        let scripted = [
+            SolutionMap::from([(User(0), (Sat(0), Color::A))]),
+            SolutionMap::from([(User(0), (Sat(0), Color::B))]),
+            SolutionMap::new(),
+            SolutionMap::from([(User(0), (Sat(1), Color::B))]),
+        ];
+        let mut step = scripted.iter();
+        let plan = plan_with(&scenario, &epochs, |_, _| step.next().unwrap().clone()).unwrap();
+
+        let counts: Vec<(usize, usize, usize)> = plan
+            .epochs
+            .iter()
+            .map(|e| (e.sat_handovers, e.color_handovers, e.dropped))
+            .collect();
+        assert_eq!(counts, vec![(0, 0, 0), (0, 1, 0), (0, 0, 1), (0, 0, 0)]);
+        assert_eq!(
+            plan.continuity[&User(0)],
+            Continuity {
+                served_epochs: 3,
+                handovers: 1,
+                interruptions: 1,
+                longest_streak: 2,
+            }
+        );
+        assert_eq!(plan.availability(User(0)), 0.75);
+    }
+}
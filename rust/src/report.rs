@@ -0,0 +1,251 @@
+use crate::config::SolverConfig;
+use crate::json::Json;
+use crate::scenario::Scenario;
+use crate::stats::Stats;
+use crate::trace::Span;
+use crate::util::{Color, Sat, User};
+use crate::verify::Violation;
+use std::{collections::BTreeMap, time::Duration};
+
+type SolutionMap = BTreeMap<User, (Sat, Color)>;
+
+/// Identifies run reports among other JSON files.
+pub const SCHEMA: &str = "beam_planner/run_report";
+/// Bumped whenever a field is removed, renamed or changes meaning. Adding a field
+/// doesn't change it, so readers should ignore members they don't know.
+pub const VERSION: u32 = 1;
+
+/// Everything worth knowing about one `solve` run, for dashboards to collect.
+///
+/// The JSON form is an object with these members:
+///
+/// - `schema` and `version`: `SCHEMA` and `VERSION`.
+/// - `scenario`: the path, the epoch as a Julian date or null, and the `Stats` members.
+/// - `solver`: `name` (such as `"v/fewest"`), `seed`, `threads` and `time_budget` in
+///   seconds.
+/// - `result`: `users`, `served`, `unserved`, `coverage`, `weighted_coverage`,
+///   `min_coverage`, `in_time` and `passed`.
+/// - `bounds`: `reachable_users`, `coverage` and `weighted_coverage`, the best any
+///   solution could do. See `Stats`.
+/// - `timings`: an array of `{"phase": name, "seconds": s}` in the order they ran.
+/// - `spans`: the solver's own phases with their counts. See `Span::to_json`.
+/// - `peak_memory_bytes`: the process's peak resident memory, or null where unknown.
+/// - `warnings`: validation warnings, as text.
+/// - `violations`: see `Violation::to_json`.
+#[derive(Debug, Clone)]
+pub struct RunReport<'a> {
+    pub scenario_path: &'a str,
+    pub scenario: &'a Scenario,
+    pub stats: Stats,
+    pub solver: String,
+    pub config: SolverConfig,
+    pub time_budget: Duration,
+    pub solution: &'a SolutionMap,
+    pub violations: &'a [Violation],
+    pub warnings: &'a [String],
+    /// How long each phase took, in the order they ran.
+    pub timings: Vec<(&'static str, Duration)>,
+    /// Spans recorded while solving, if tracing was on.
+    pub spans: Vec<Span>,
+    pub peak_memory: Option<u64>,
+}
+
+impl RunReport<'_> {
+    /// Whether the solve phase finished within the time budget.
+    pub fn in_time(&self) -> bool {
+        self.timings
+            .iter()
+            .filter(|(phase, _)| *phase == "solve")
+            .all(|(_, duration)| *duration <= self.time_budget)
+    }
+
+    pub fn passed(&self) -> bool {
+        self.violations.is_empty() && self.in_time()
+    }
+
+    pub fn to_json(&self) -> Json {
+        let users = self.scenario.users.len();
+        let served = self.solution.len();
+        let Json::Object(stats) = self.stats.to_json() else {
+            unreachable!("stats are an object")
+        };
+        let mut scenario = Json::object()
+            .with("path", self.scenario_path)
+            .with("epoch", self.scenario.epoch.map(|epoch| epoch.0));
+        if let Json::Object(members) = &mut scenario {
+            members.extend(stats);
+        }
+
+        let timings: Vec<Json> = self
+            .timings
+            .iter()
+            .map(|(phase, duration)| {
+                Json::object()
+                    .with("phase", *phase)
+                    .with("seconds", duration.as_secs_f64())
+            })
+            .collect();
+        let violations: Vec<Json> = self.violations.iter().map(Violation::to_json).collect();
+
+        Json::object()
+            .with("schema", SCHEMA)
+            .with("version", VERSION)
+            .with("scenario", scenario)
+            .with(
+                "solver",
+                Json::object()
+                    .with("name", self.solver.as_str())
+                    .with("seed", self.config.seed)
+                    .with("threads", self.config.threads)
+                    .with("time_budget", self.time_budget.as_secs_f64()),
+            )
+            .with(
+                "result",
+                Json::object()
+                    .with("users", users)
+                    .with("served", served)
+                    .with("unserved", users.saturating_sub(served))
+                    .with(
+                        "coverage",
+                        if users == 0 {
+                            1.0
+                        } else {
+                            served as f32 / users as f32
+                        },
+                    )
+                    .with(
+                        "weighted_coverage",
+                        self.scenario.weighted_coverage(self.solution),
+                    )
+                    .with("min_coverage", self.scenario.min_coverage)
+                    .with("in_time", self.in_time())
+                    .with("passed", self.passed()),
+            )
+            .with(
+                "bounds",
+                Json::object()
+                    .with("reachable_users", self.stats.reachable_users)
+                    .with("coverage", self.stats.coverage_bound)
+                    .with("weighted_coverage", self.stats.weighted_coverage_bound),
+            )
+            .with("timings", timings)
+            .with(
+                "spans",
+                self.spans.iter().map(Span::to_json).collect::<Vec<_>>(),
+            )
+            .with("peak_memory_bytes", self.peak_memory)
+            .with("warnings", self.warnings.to_vec())
+            .with("violations", violations)
+    }
+}
+
+/// The process's peak resident set size so far, from `VmHWM` in `/proc/self/status`.
+/// `None` on systems without it.
+pub fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: u64 = line["VmHWM:".len()..]
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::{self, Value};
+    use crate::solution_v;
+
+    #[test]
+    fn test_report_schema() {
+        let mut scenario = Scenario::new("../test/02_five_users.txt").unwrap();
+        // One user of the five can't be served; make it count for three.
+        let solution = solution_v::solve(&scenario.users, &scenario.sats);
+        let unserved = scenario.users.keys().find(|u| !solution.contains_key(u));
+        scenario.weights.insert(*unserved.unwrap(), 3.0);
+        let violations = scenario.verify(&solution);
+        let report = RunReport {
+            scenario_path: "02.txt",
+            scenario: &scenario,
+            stats: Stats::new(&scenario),
+            solver: "v/fewest".to_string(),
+            config: SolverConfig::default(),
+            time_budget: Duration::from_secs(60),
+            solution: &solution,
+            violations: &violations,
+            warnings: &[],
+            timings: vec![
+                ("load", Duration::from_millis(5)),
+                ("solve", Duration::from_millis(250)),
+            ],
+            spans: Vec::new(),
+            peak_memory: Some(1 << 20),
+        };
+        let text = report.to_json().to_string();
+        let root = json::parse(&text).unwrap();
+        let get = |path: &[&str]| {
+            let mut node = &root;
+            for key in path {
+                node = node.get(key).unwrap_or_else(|| panic!("no {:?}", path));
+            }
+            node.value.clone()
+        };
+        let number = |text: &str| Value::Number(text.to_string());
+        assert_eq!(get(&["schema"]), Value::String(SCHEMA.to_string()));
+        assert_eq!(get(&["version"]), number("1"));
+        assert_eq!(
+            get(&["scenario", "path"]),
+            Value::String("02.txt".to_string())
+        );
+        assert_eq!(get(&["scenario", "users"]), number("5"));
+        assert_eq!(
+            get(&["solver", "name"]),
+            Value::String("v/fewest".to_string())
+        );
+        assert_eq!(get(&["result", "served"]), number("4"));
+        assert_eq!(get(&["result", "unserved"]), number("1"));
+        assert_eq!(get(&["result", "coverage"]), number("0.8"));
+        assert_eq!(get(&["result", "weighted_coverage"]), number("0.5714286"));
+        assert_eq!(get(&["result", "passed"]), Value::Bool(true));
+        assert_eq!(get(&["bounds", "weighted_coverage"]), number("1"));
+        assert_eq!(get(&["peak_memory_bytes"]), number("1048576"));
+        let Value::Array(timings) = get(&["timings"]) else {
+            panic!("timings are not an array")
+        };
+        assert_eq!(
+            timings[1].get("phase").unwrap().value,
+            Value::String("solve".to_string())
+        );
+        assert_eq!(timings[1].get("seconds").unwrap().value, number("0.25"));
+
+        // Late and short of coverage.
+        let violations = [Violation::LowCoverage {
+            served: 4,
+            users: 5,
+            min_coverage: 0.9,
+        }];
+        let late = RunReport {
+            violations: &violations,
+            time_budget: Duration::from_millis(100),
+            ..report
+        };
+        assert!(!late.in_time());
+        let text = late.to_json().to_string();
+        assert!(
+            text.contains(r#""in_time":false,"passed":false"#),
+            "{}",
+            text
+        );
+        assert!(text.contains(r#""violations":[{"kind":"low_coverage","served":4"#));
+    }
+
+    #[test]
+    fn test_peak_memory() {
+        if std::path::Path::new("/proc/self/status").exists() {
+            assert!(peak_memory().unwrap() > 0);
+        }
+    }
+}
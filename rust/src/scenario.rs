@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::format::{self, Format};
+use crate::orbit::{Epoch, Orbit, OrbitError};
+use crate::parse::{Duplicate, ParseError};
+use crate::util::{Color, Sat, User, Vector3};
+use crate::validate::{self, Issue, ValidationConfig};
+use crate::verify::{self, Violation};
+
+pub const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// With the `serde` feature, scenarios serialize to the JSON that `Format::Json` reads
+/// and writes, orbits included; `duplicates` is left out.
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub sats: BTreeMap<Sat, Vector3>,
+    pub users: BTreeMap<User, Vector3>,
+    pub min_coverage: f32,
+    /// When `sats` positions hold for. Required if any satellite has an orbit.
+    pub epoch: Option<Epoch>,
+    /// Satellites given by `kepler` or `tle` lines. Their `sats` entries are the ECEF
+    /// positions at `epoch`; use `at` to move them to another time.
+    pub orbits: BTreeMap<Sat, Orbit>,
+    /// How much serving each user is worth, from `weight` lines. Users without one
+    /// weigh 1.
+    pub weights: BTreeMap<User, f32>,
+    /// Ids the file defined more than once, when read with a `*_lenient` reader. Only the
+    /// first definition is used.
+    pub duplicates: Vec<Duplicate>,
+}
+
+impl Scenario {
+    /// Reads the scenario file at `path`, in the format its extension implies. See
+    /// `parse::parse_scenario` for the text format and `Format` for the others.
+    pub fn new(path: &str) -> Result<Self, ParseError> {
+        format::read_scenario(path, Format::from_path(path))
+    }
+
+    /// Checks for duplicate ids, users off the surface and satellites that are too low.
+    /// See `ValidationConfig` for the tolerances.
+    pub fn validate(&self, config: &ValidationConfig) -> Vec<Issue> {
+        validate::validate(self, config)
+    }
+
+    /// The scenario at `epoch`: satellites with orbits are propagated and rotated into
+    /// ECEF, static satellites and users stay where they are.
+    pub fn at(&self, epoch: Epoch) -> Result<Self, OrbitError> {
+        let mut s = self.clone();
+        for (sat, orbit) in &self.orbits {
+            s.sats.insert(*sat, orbit.position_ecef(epoch)?);
+        }
+        s.epoch = Some(epoch);
+        Ok(s)
+    }
+
+    pub fn weight(&self, user: User) -> f32 {
+        self.weights.get(&user).copied().unwrap_or(1.0)
+    }
+
+    /// The share of the scenario's total user weight that `solution` serves. Equal to
+    /// plain coverage when no user has a weight.
+    pub fn weighted_coverage(&self, solution: &BTreeMap<User, (Sat, Color)>) -> f32 {
+        let total: f64 = self.users.keys().map(|&u| self.weight(u) as f64).sum();
+        if total <= 0.0 {
+            return 1.0;
+        }
+        let served: f64 = solution
+            .keys()
+            .filter(|u| self.users.contains_key(u))
+            .map(|&u| self.weight(u) as f64)
+            .sum();
+        (served / total) as f32
+    }
+
+    /// Every rule `solution` breaks. See `verify::verify_fast`.
+    pub fn verify(&self, solution: &BTreeMap<User, (Sat, Color)>) -> Vec<Violation> {
+        verify::verify_fast(self, solution)
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            sats: Default::default(),
+            users: Default::default(),
+            min_coverage: 1.0,
+            epoch: None,
+            orbits: Default::default(),
+            weights: Default::default(),
+            duplicates: Vec::new(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_round_trip() {
+        let mut scenario = Scenario::new("../test/02_five_users.txt").unwrap();
+        scenario.weights.insert(User(1), 0.25);
+        let json = serde_json::to_string(&scenario).unwrap();
+        let back: Scenario = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.sats, scenario.sats);
+        assert_eq!(back.users, scenario.users);
+        assert_eq!(back.weights, scenario.weights);
+        assert_eq!(back.min_coverage, scenario.min_coverage);
+
+        // Missing members take their defaults.
+        let back: Scenario =
+            serde_json::from_str(r#"{"users":[{"id":1,"position":[1.0,0.0,0.0]}]}"#).unwrap();
+        assert_eq!(back.users[&User(1)], Vector3::new(1.0, 0.0, 0.0));
+        assert_eq!(back.min_coverage, 1.0);
+        assert!(back.sats.is_empty());
+    }
+}
@@ -1,17 +1,12 @@
-use crate::util::{Color, Sat, User, Vector3};
-use std::{
-    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
-    hash::Hash,
-};
+use crate::config::SolverConfig;
+use crate::greedy::{self, Candidates, FewestOptions, Link, Scoring};
+use crate::spatial::{max_central_angle, SphereIndex};
+use crate::trace;
+use crate::util::{Color, Sat, User, Vector3, MAX_ALLOWABLE_BEAM_ANGLE, MINIMUM_BEAM_ANGLE};
+use std::collections::{BTreeMap, BTreeSet};
 
-// In degrees
-const MINIMUM_BEAM_ANGLE: f32 = 10.0;
-const MAX_ALLOWABLE_BEAM_ANGLE: f32 = 45.0;
-const MAX_ALLOWED_USERS: usize = 32;
-const MAX_COLOR_OPTIONS: usize = 4;
-
-type Map<K, V> = HashMap<K, V>;
-type Set<K> = HashSet<K>;
+type Map<K, V> = BTreeMap<K, V>;
+type Set<K> = BTreeSet<K>;
 
 type Users = Map<User, Vector3>;
 type Sats = Map<Sat, Vector3>;
@@ -22,29 +17,34 @@ type SatsUsersMap = Map<Sat, Set<User>>;
 type UserUserMap = Map<User, Set<User>>;
 type SatUserInterferenceMap = Map<Sat, UserUserMap>;
 
-type SolutionMap = Map<User, (Sat, Color)>;
-
-type AvailaibleConnections = Set<(Color, User, Sat)>;
+type SolutionMap = BTreeMap<User, (Sat, Color)>;
 
 fn possible_connections(users: &Users, sats: &Sats) -> (UserSatsMap, SatsUsersMap) {
+    let mut span = trace::span("possible_connections");
+    let cos_max_beam_angle = MAX_ALLOWABLE_BEAM_ANGLE.to_radians().cos();
     let mut by_user: UserSatsMap = Default::default();
     let mut by_sat: SatsUsersMap = Default::default();
+
+    let min_user_radius = users
+        .values()
+        .map(Vector3::length)
+        .filter(|radius| *radius > 0.0)
+        .fold(f32::INFINITY, f32::min);
+    let index = SphereIndex::new(users.iter().map(|(user_id, pos)| (*pos, *user_id)));
+
     for (sat_id, sat_pos) in sats.iter() {
-        for (user_id, user_pos) in users.iter() {
-            let angle = Vector3::zero().angle_between(user_pos, &(sat_pos - user_pos));
-            if angle <= MAX_ALLOWABLE_BEAM_ANGLE {
-                by_user
-                    .entry(*user_id)
-                    .or_insert_with(Default::default)
-                    .insert(*sat_id);
-                by_sat
-                    .entry(*sat_id)
-                    .or_insert_with(Default::default)
-                    .insert(*user_id);
+        let central_angle =
+            max_central_angle(sat_pos.length(), min_user_radius, MAX_ALLOWABLE_BEAM_ANGLE);
+        index.for_each_within(sat_pos, central_angle.cos(), |user_id| {
+            let user_pos = users.get(&user_id).unwrap();
+            if Vector3::zero().within_angle(user_pos, &(sat_pos - user_pos), cos_max_beam_angle) {
+                by_user.entry(user_id).or_default().insert(*sat_id);
+                by_sat.entry(*sat_id).or_default().insert(user_id);
             }
-        }
+        });
     }
 
+    span.count("candidate_links", by_sat.values().map(Set::len).sum());
     (by_user, by_sat)
 }
 
@@ -53,125 +53,189 @@ fn get_interferences(
     sats: &Sats,
     conns_by_sat: &SatsUsersMap,
 ) -> SatUserInterferenceMap {
+    let mut span = trace::span("get_interferences");
+    let cos_min_beam_angle = MINIMUM_BEAM_ANGLE.to_radians().cos();
+    let cos_candidate_angle = (MINIMUM_BEAM_ANGLE + 0.01).to_radians().cos();
     let mut by_sat_user: SatUserInterferenceMap = Default::default();
 
     for (sat_id, sat_users) in conns_by_sat.iter() {
+        let sat_pos = sats.get(sat_id).unwrap();
+        let index = SphereIndex::new(
+            sat_users
+                .iter()
+                .map(|user_id| (users.get(user_id).unwrap() - sat_pos, *user_id)),
+        );
         let mut interferences: UserUserMap = Default::default();
         for user_id in sat_users {
             let user_pos = users.get(user_id).unwrap();
-            for other_user_id in sat_users {
-                if user_id != other_user_id {
-                    let other_user_pos = users.get(other_user_id).unwrap();
-                    let angle = sats
-                        .get(sat_id)
-                        .unwrap()
-                        .angle_between(user_pos, other_user_pos);
-                    // TODO: Hoist this outside the loop to avoid a branch during parallel computation
-                    if angle < MINIMUM_BEAM_ANGLE {
-                        interferences
-                            .entry(*user_id)
-                            .or_insert_with(Default::default)
-                            .insert(*other_user_id);
-                        // interferences
-                        //     .entry(*other_user_id)
-                        //     .or_insert_with(Default::default)
-                        //     .insert(*user_id);
+            index.for_each_within(
+                &(user_pos - sat_pos),
+                cos_candidate_angle,
+                |other_user_id| {
+                    if *user_id != other_user_id {
+                        let other_user_pos = users.get(&other_user_id).unwrap();
+                        if sat_pos.within_angle(user_pos, other_user_pos, cos_min_beam_angle) {
+                            interferences
+                                .entry(*user_id)
+                                .or_default()
+                                .insert(other_user_id);
+                        }
                     }
-                }
-            }
-        }
-        for user_id in sat_users {
-            let user_pos = users.get(user_id).unwrap();
-            for other_user_id in sat_users {
-                if user_id != other_user_id {
-                    let other_user_pos = users.get(other_user_id).unwrap();
-                    let angle = sats
-                        .get(sat_id)
-                        .unwrap()
-                        .angle_between(user_pos, other_user_pos);
-                    // TODO: Hoist this outside the loop to avoid a branch during parallel computation
-                    if angle < MINIMUM_BEAM_ANGLE {
-                        interferences
-                            .entry(*user_id)
-                            .or_insert_with(Default::default)
-                            .insert(*other_user_id);
-                        // interferences
-                        //     .entry(*other_user_id)
-                        //     .or_insert_with(Default::default)
-                        //     .insert(*user_id);
-                    }
-                }
-            }
+                },
+            );
         }
-        // Hoist the angle check here
         by_sat_user.insert(*sat_id, interferences);
     }
 
+    // Each conflict is stored once per direction.
+    let edges: usize = by_sat_user
+        .values()
+        .flat_map(Map::values)
+        .map(Set::len)
+        .sum();
+    span.count("interference_edges", edges / 2);
     by_sat_user
 }
 
-pub fn solve(users: &HashMap<User, Vector3>, sats: &HashMap<Sat, Vector3>) -> SolutionMap {
-    let mut solution: SolutionMap = Default::default();
-
-    // let users = HashMap::from_iter(users.iter().map(|(k, v)| (*k, *v)));
-    // let sats = HashMap::from_iter(sats.iter().map(|(k, v)| (*k, *v)));
-
-    let (conns_by_user, conns_by_sat) = possible_connections(&users, &sats);
-    let interference_by_sat_user = get_interferences(&users, &sats, &conns_by_sat);
+/// Candidate links for `greedy::assign`, numbered by satellite id and then user id.
+struct LinkCandidates<'a> {
+    conns_by_user: &'a UserSatsMap,
+    interference_by_sat_user: &'a SatUserInterferenceMap,
+    links: Vec<(User, Sat)>,
+    slots: Vec<(usize, usize)>,
+    link_index: Map<(User, Sat), Link>,
+    first_link: Vec<Link>,
+    user_count: usize,
+}
 
-    let mut available_conns: AvailaibleConnections = Default::default();
-    for (sat_id, sat_users) in conns_by_sat.iter() {
-        for user_id in sat_users {
-            for i in 0..MAX_COLOR_OPTIONS {
-                available_conns.insert((Color::from_id(i as i32 + 1), *user_id, *sat_id));
+impl<'a> LinkCandidates<'a> {
+    fn new(
+        conns_by_user: &'a UserSatsMap,
+        conns_by_sat: &SatsUsersMap,
+        interference_by_sat_user: &'a SatUserInterferenceMap,
+    ) -> Self {
+        let user_slots: Map<User, usize> = conns_by_user.keys().copied().zip(0..).collect();
+
+        let mut links = Vec::new();
+        let mut slots = Vec::new();
+        let mut first_link = Vec::with_capacity(conns_by_sat.len() + 1);
+        for (sat_slot, (sat_id, sat_users)) in conns_by_sat.iter().enumerate() {
+            first_link.push(links.len());
+            for user_id in sat_users {
+                links.push((*user_id, *sat_id));
+                slots.push((user_slots[user_id], sat_slot));
             }
         }
+        first_link.push(links.len());
+        let link_index = links.iter().copied().zip(0..).collect();
+
+        Self {
+            conns_by_user,
+            interference_by_sat_user,
+            links,
+            slots,
+            link_index,
+            first_link,
+            user_count: user_slots.len(),
+        }
+    }
+}
+
+impl Candidates for LinkCandidates<'_> {
+    fn link_count(&self) -> usize {
+        self.links.len()
     }
 
-    let mut solution_by_sat: SatsUsersMap = Default::default();
+    fn user_count(&self) -> usize {
+        self.user_count
+    }
 
-    while available_conns.len() > 0 {
-        let (color, user_id, sat_id) = *available_conns.iter().next().unwrap();
-        available_conns.remove(&(color, user_id, sat_id));
+    fn sat_count(&self) -> usize {
+        self.first_link.len() - 1
+    }
 
-        solution_by_sat
-            .entry(sat_id)
-            .or_insert_with(Default::default)
-            .insert(user_id);
+    fn slots(&self, link: Link) -> (usize, usize) {
+        self.slots[link]
+    }
 
-        solution
-            .entry(user_id)
-            .and_modify(|e| {
-                e.0 = sat_id;
-                e.1 = color;
-            })
-            .or_insert((sat_id, color));
+    fn ids(&self, link: Link) -> (User, Sat) {
+        self.links[link]
+    }
 
-        // dont add interfering connections
-        for user2_id in interference_by_sat_user
-            .get(&sat_id)
-            .unwrap()
-            .get(&user_id)
-            .unwrap_or(&Default::default())
-        {
-            available_conns.remove(&(color, *user2_id, sat_id));
+    fn for_each_user_link(&self, link: Link, mut f: impl FnMut(Link)) {
+        let user_id = self.links[link].0;
+        for sat_id in self.conns_by_user.get(&user_id).unwrap() {
+            f(self.link_index[&(user_id, *sat_id)]);
         }
+    }
 
-        // don't reconnect the same user
-        for sat_id in conns_by_user.get(&user_id).unwrap() {
-            for i in 0..MAX_COLOR_OPTIONS {
-                available_conns.remove(&(Color::from_id(i as i32 + 1), user_id, *sat_id));
-            }
-        }
+    fn for_each_sat_link(&self, link: Link, f: impl FnMut(Link)) {
+        let sat_slot = self.slots[link].1;
+        (self.first_link[sat_slot]..self.first_link[sat_slot + 1]).for_each(f);
+    }
 
-        // if satellite is at capacity, drop its remaining possible connections
-        if solution_by_sat.get(&sat_id).unwrap().len() >= MAX_ALLOWED_USERS {
-            for user_id in conns_by_sat.get(&sat_id).unwrap() {
-                for i in 0..MAX_COLOR_OPTIONS {
-                    available_conns.remove(&(Color::from_id(i as i32 + 1), *user_id, sat_id));
-                }
-            }
+    fn for_each_conflict(&self, link: Link, mut f: impl FnMut(Link)) {
+        let (user_id, sat_id) = self.links[link];
+        let conflicts = self
+            .interference_by_sat_user
+            .get(&sat_id)
+            .and_then(|interferences| interferences.get(&user_id));
+        for user2_id in conflicts.into_iter().flatten() {
+            f(self.link_index[&(*user2_id, sat_id)]);
         }
     }
-    solution
+}
+
+pub fn solve(users: &BTreeMap<User, Vector3>, sats: &BTreeMap<Sat, Vector3>) -> SolutionMap {
+    solve_with(users, sats, &SolverConfig::default(), &FewestOptions)
+}
+
+pub fn solve_with(
+    users: &BTreeMap<User, Vector3>,
+    sats: &BTreeMap<Sat, Vector3>,
+    config: &SolverConfig,
+    scoring: &(impl Scoring + Sync),
+) -> SolutionMap {
+    solve_from(users, sats, config, scoring, &SolutionMap::new())
+}
+
+/// Like `solve_with`, but first keeps every assignment in `previous` that is still
+/// possible at these positions, with its color, and only then fills in the rest.
+pub fn solve_from(
+    users: &BTreeMap<User, Vector3>,
+    sats: &BTreeMap<Sat, Vector3>,
+    config: &SolverConfig,
+    scoring: &(impl Scoring + Sync),
+    previous: &SolutionMap,
+) -> SolutionMap {
+    config.install(|| solve_inner(users, sats, config.seed, scoring, previous))
+}
+
+fn solve_inner(
+    users: &BTreeMap<User, Vector3>,
+    sats: &BTreeMap<Sat, Vector3>,
+    seed: u64,
+    scoring: &impl Scoring,
+    previous: &SolutionMap,
+) -> SolutionMap {
+    let mut span = trace::span("solve");
+    span.count("users", users.len());
+    span.count("sats", sats.len());
+    // let users = HashMap::from_iter(users.iter().map(|(k, v)| (*k, *v)));
+    // let sats = HashMap::from_iter(sats.iter().map(|(k, v)| (*k, *v)));
+
+    let (conns_by_user, conns_by_sat) = possible_connections(users, sats);
+    let interference_by_sat_user = get_interferences(users, sats, &conns_by_sat);
+
+    let candidates = LinkCandidates::new(&conns_by_user, &conns_by_sat, &interference_by_sat_user);
+    let keep: Vec<(Link, Color)> = previous
+        .iter()
+        .filter_map(|(user, (sat, color))| {
+            Some((*candidates.link_index.get(&(*user, *sat))?, *color))
+        })
+        .collect();
+    greedy::assign_from(&candidates, scoring, seed, &keep)
+        .into_iter()
+        .map(|(user, sat, color)| (user, (sat, color)))
+        .collect()
 }
@@ -1,61 +1,60 @@
-use crate::util::{Color, Sat, User, Vector3};
-use std::{
-    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
-    hash::Hash,
-    vec,
-};
+use crate::config::SolverConfig;
+use crate::greedy::{self, Candidates, FewestOptions, Link, Scoring};
+use crate::spatial::{max_central_angle, SphereIndex};
+use crate::trace;
+use crate::util::{Color, Sat, User, Vector3, MAX_ALLOWABLE_BEAM_ANGLE, MINIMUM_BEAM_ANGLE};
+use std::{collections::BTreeMap, vec};
 
 use rayon::prelude::*;
 
-// In degrees
-const MINIMUM_BEAM_ANGLE: f32 = 10.0;
-const MAX_ALLOWABLE_BEAM_ANGLE: f32 = 45.0;
-const MAX_ALLOWED_USERS: usize = 32;
-const MAX_COLOR_OPTIONS: usize = 4;
-
-type Map<K, V> = HashMap<K, V>;
-type Set<K> = HashSet<K>;
-
 type Users = Vec<Vector3>;
 type Sats = Vec<Vector3>;
 
 type UserSatsMap = Vec<Vec<Sat>>;
 type SatsUsersMap = Vec<Vec<User>>;
 
-type UserUserMap = Vec<Vec<User>>;
-type SatUserInterferenceMap = Vec<Vec<Vec<User>>>;
-
-type SolutionMap = Map<User, (Sat, Color)>;
+type SatUserInterferenceMap = Vec<InterferenceGraph>;
 
-type AvailaibleConnections = Set<(Color, User, Sat)>;
+type SolutionMap = BTreeMap<User, (Sat, Color)>;
 
 fn possible_connections(users: &Users, sats: &Sats) -> (UserSatsMap, SatsUsersMap) {
-    let mut by_user: UserSatsMap = vec![Vec::with_capacity(sats.len()); users.len() + 1];
-    let mut by_sat: SatsUsersMap = vec![Vec::with_capacity(users.len()); sats.len() + 1];
+    let mut span = trace::span("possible_connections");
+    let cos_max_beam_angle = MAX_ALLOWABLE_BEAM_ANGLE.to_radians().cos();
+    let mut by_user: UserSatsMap = vec![Vec::new(); users.len()];
+    let mut by_sat: SatsUsersMap = vec![Vec::new(); sats.len()];
+
+    let min_user_radius = users
+        .iter()
+        .map(Vector3::length)
+        .filter(|radius| *radius > 0.0)
+        .fold(f32::INFINITY, f32::min);
+    let index = SphereIndex::new(users.iter().copied().zip(0..));
 
     let users_n_sats = sats
         .par_iter()
         .enumerate()
         .map(|(sat_id, sat_pos)| {
-            users
-                .par_iter()
-                .enumerate()
-                .filter_map(|(user_id, user_pos)| {
-                    if user_pos.dot(*sat_pos) < 0.0 {
-                        return None;
-                    }
-                    let angle = Vector3::zero().angle_between(user_pos, &(sat_pos - user_pos));
-                    if angle <= MAX_ALLOWABLE_BEAM_ANGLE {
-                        Some((sat_id, user_id))
-                    } else {
-                        None
-                    }
+            let cos_central_angle =
+                max_central_angle(sat_pos.length(), min_user_radius, MAX_ALLOWABLE_BEAM_ANGLE)
+                    .cos();
+            index
+                .within(sat_pos, cos_central_angle)
+                .into_iter()
+                .filter(|user_id: &usize| {
+                    let user_pos = &users[*user_id];
+                    Vector3::zero().within_angle(
+                        user_pos,
+                        &(sat_pos - user_pos),
+                        cos_max_beam_angle,
+                    )
                 })
+                .map(|user_id| (sat_id, user_id))
                 .collect::<Vec<_>>()
         })
         .flatten()
         .collect::<Vec<_>>();
 
+    span.count("candidate_links", users_n_sats.len());
     for (sat, user) in users_n_sats {
         by_user[user].push(Sat(sat as u64));
         by_sat[sat].push(User(user as u64));
@@ -64,132 +63,240 @@ fn possible_connections(users: &Users, sats: &Sats) -> (UserSatsMap, SatsUsersMa
     (by_user, by_sat)
 }
 
+/// Same-color conflicts among the users one satellite can see, in compressed sparse row
+/// form. Visible users get local indices by their position in the sorted `users`, and
+/// the neighbors of local user `i` are `neighbors[offsets[i]..offsets[i + 1]]`. Memory
+/// is one `u32` per directed edge plus one entry per visible user.
+#[derive(Debug, Default)]
+struct InterferenceGraph {
+    users: Vec<User>,
+    offsets: Vec<usize>,
+    neighbors: Vec<u32>,
+}
+
+impl InterferenceGraph {
+    /// Users exactly `MINIMUM_BEAM_ANGLE` apart count as conflicting. The verifier only
+    /// rejects closer pairs, so rounding at the boundary can't yield a plan it rejects.
+    fn new(users: &Users, sat_pos: &Vector3, sat_users: &[User]) -> Self {
+        let cos_min_beam_angle = MINIMUM_BEAM_ANGLE.to_radians().cos();
+        // Candidates come from an index over directions as seen from the satellite,
+        // queried slightly wider than the beam spacing; `within_angle` makes the final call.
+        let cos_candidate_angle = (MINIMUM_BEAM_ANGLE + 0.01).to_radians().cos();
+
+        let mut sat_users = sat_users.to_vec();
+        sat_users.sort_unstable();
+        let index = SphereIndex::new(
+            sat_users
+                .iter()
+                .zip(0u32..)
+                .map(|(user_id, local)| (users[user_id.0 as usize] - *sat_pos, local)),
+        );
+
+        let mut offsets = Vec::with_capacity(sat_users.len() + 1);
+        let mut neighbors = Vec::new();
+        offsets.push(0);
+        for (local, user_id) in sat_users.iter().enumerate() {
+            let user_pos = &users[user_id.0 as usize];
+            let row_start = neighbors.len();
+            index.for_each_within(&(user_pos - sat_pos), cos_candidate_angle, |other| {
+                let other_user_pos = &users[sat_users[other as usize].0 as usize];
+                if other as usize != local
+                    && sat_pos.within_angle(user_pos, other_user_pos, cos_min_beam_angle)
+                {
+                    neighbors.push(other);
+                }
+            });
+            neighbors[row_start..].sort_unstable();
+            offsets.push(neighbors.len());
+        }
+        neighbors.shrink_to_fit();
+
+        Self {
+            users: sat_users,
+            offsets,
+            neighbors,
+        }
+    }
+
+    /// Local index of `user`, if this satellite can see them.
+    fn local(&self, user: User) -> Option<usize> {
+        self.users.binary_search(&user).ok()
+    }
+
+    /// Local indices of the users that may not share a color with local user `local`.
+    fn row(&self, local: usize) -> &[u32] {
+        &self.neighbors[self.offsets[local]..self.offsets[local + 1]]
+    }
+
+    /// Users that may not share a color with `user` on this satellite. Empty if the
+    /// satellite can't see `user`.
+    #[cfg(test)]
+    fn neighbors(&self, user: User) -> impl Iterator<Item = User> + '_ {
+        let row = match self.local(user) {
+            Some(local) => self.row(local),
+            None => &[],
+        };
+        row.iter().map(|other| self.users[*other as usize])
+    }
+
+    fn edge_count(&self) -> usize {
+        self.neighbors.len()
+    }
+}
+
 fn get_interferences(
     users: &Users,
     sats: &Sats,
     conns_by_sat: &SatsUsersMap,
 ) -> SatUserInterferenceMap {
-    // let mut by_sat_user: SatUserInterferenceMap = Default::default();
-    let mut by_sat_user: Vec<Vec<Vec<User>>> =
-        vec![vec![Vec::with_capacity(users.len()); users.len() + 1]; sats.len() + 1];
-
-    for (sat_id, sat_users) in conns_by_sat.iter().enumerate() {
-        // let mut angle_scratchpad: Vec<(usize, usize, usize, f32)> =
-        //     Vec::with_capacity(sat_users.len() * sat_users.len());
-        let angles: Vec<(&User, &User, f32)> = sat_users
-            .par_iter()
-            .flat_map(|user_id| {
-                let user_pos = users.get(user_id.0 as usize).unwrap();
-                let angles = sat_users
-                    .par_iter()
-                    .map(|other_user_id| {
-                        let other_user_pos = users.get(other_user_id.0 as usize).unwrap();
-                        let angle = sats
-                            .get(sat_id)
-                            .unwrap()
-                            .angle_between(user_pos, other_user_pos);
-                        (user_id, other_user_id, angle)
-                    })
-                    .collect::<Vec<_>>();
-                angles
-            })
-            .collect();
-        for angle in angles {
-            if angle.2 < MINIMUM_BEAM_ANGLE {
-                by_sat_user[sat_id][angle.1 .0 as usize].push(*angle.1);
-            }
+    let mut span = trace::span("get_interferences");
+    let graphs: SatUserInterferenceMap = conns_by_sat
+        .par_iter()
+        .zip(sats)
+        .map(|(sat_users, sat_pos)| InterferenceGraph::new(users, sat_pos, sat_users))
+        .collect();
+    // Each conflict is stored once per direction.
+    span.count(
+        "interference_edges",
+        graphs
+            .iter()
+            .map(InterferenceGraph::edge_count)
+            .sum::<usize>()
+            / 2,
+    );
+    graphs
+}
+
+/// Candidate links for `greedy::assign`, numbered satellite by satellite in the order of
+/// each satellite's `InterferenceGraph`, so conflicts map straight onto link indices.
+struct LinkCandidates<'a> {
+    conns_by_user: &'a UserSatsMap,
+    graphs: &'a SatUserInterferenceMap,
+    first_link: Vec<usize>,
+    links: Vec<(User, Sat)>,
+}
+
+impl<'a> LinkCandidates<'a> {
+    fn new(conns_by_user: &'a UserSatsMap, graphs: &'a SatUserInterferenceMap) -> Self {
+        let mut first_link = Vec::with_capacity(graphs.len() + 1);
+        let mut links = Vec::new();
+        for (sat_id, graph) in graphs.iter().enumerate() {
+            first_link.push(links.len());
+            links.extend(graph.users.iter().map(|user| (*user, Sat(sat_id as u64))));
+        }
+        first_link.push(links.len());
+        Self {
+            conns_by_user,
+            graphs,
+            first_link,
+            links,
         }
     }
-
-    by_sat_user
 }
 
-pub fn solve(users: &HashMap<User, Vector3>, sats: &HashMap<Sat, Vector3>) -> SolutionMap {
-    let mut users_vec = vec![Vector3::zero(); users.len() + 1];
-    for (user, pos) in users.iter() {
-        users_vec[user.0 as usize] = *pos;
+impl Candidates for LinkCandidates<'_> {
+    fn link_count(&self) -> usize {
+        self.links.len()
     }
-    let mut sats_vec = vec![Vector3::zero(); sats.len() + 1];
-    for (sat, pos) in sats.iter() {
-        sats_vec[sat.0 as usize] = *pos;
+
+    fn user_count(&self) -> usize {
+        self.conns_by_user.len()
     }
 
-    let (conns_by_user, conns_by_sat) = possible_connections(&users_vec, &sats_vec);
-    let interference_by_sat_user = get_interferences(&users_vec, &sats_vec, &conns_by_sat);
+    fn sat_count(&self) -> usize {
+        self.graphs.len()
+    }
 
-    let mut available_conns: AvailaibleConnections = Default::default();
-    for (sat_id, sat_users) in conns_by_sat.iter().enumerate() {
-        for user_id in sat_users {
-            for i in 0..MAX_COLOR_OPTIONS {
-                available_conns.insert((
-                    Color::from_id(i as i32 + 1),
-                    *user_id,
-                    Sat(sat_id as u64),
-                ));
-            }
-        }
+    fn slots(&self, link: Link) -> (usize, usize) {
+        let (user, sat) = self.links[link];
+        (user.0 as usize, sat.0 as usize)
     }
 
-    // Parallel Iterator version of the above
-    // let mut available_conns: AvailaibleConnections = conns_by_sat
-    //     .par_iter()
-    //     .enumerate()
-    //     .flat_map(|(sat_id, sat_users)| {
-    //         sat_users
-    //             .par_iter()
-    //             .flat_map(|user_id| {
-    //                 (1..=MAX_COLOR_OPTIONS)
-    //                     .map(|i| (Color::from_id(i as i32), *user_id, Sat(sat_id as u64)))
-    //                     .collect::<Vec<_>>()
-    //             })
-    //             .collect::<Vec<_>>()
-    //     })
-    //     .collect();
-
-    let mut sat_conn_count = vec![0; sats.len() + 1];
-    let mut solution: SolutionMap = Default::default();
-
-    while available_conns.len() > 0 {
-        let (color, user_id, sat_id) = *available_conns.iter().next().unwrap();
-        available_conns.remove(&(color, user_id, sat_id));
-
-        *sat_conn_count.get_mut(sat_id.0 as usize).unwrap() += 1;
-
-        solution
-            .entry(user_id)
-            .and_modify(|e| {
-                e.0 = sat_id;
-                e.1 = color;
-            })
-            .or_insert((sat_id, color));
-
-        // dont add interfering connections
-        for user2_id in interference_by_sat_user
-            .get(sat_id.0 as usize)
-            .unwrap()
-            .get(user_id.0 as usize)
-            .unwrap_or(&Default::default())
-        {
-            available_conns.remove(&(color, *user2_id, sat_id));
-        }
+    fn ids(&self, link: Link) -> (User, Sat) {
+        self.links[link]
+    }
 
-        // don't reconnect the same user
-        for sat_id in conns_by_user.get(user_id.0 as usize).unwrap() {
-            for i in 0..MAX_COLOR_OPTIONS {
-                available_conns.remove(&(Color::from_id(i as i32 + 1), user_id, *sat_id));
-            }
+    fn for_each_user_link(&self, link: Link, mut f: impl FnMut(Link)) {
+        let user = self.links[link].0;
+        for sat in &self.conns_by_user[user.0 as usize] {
+            let sat = sat.0 as usize;
+            f(self.first_link[sat] + self.graphs[sat].local(user).unwrap());
         }
+    }
 
-        // if satellite is at capacity, drop its remaining possible connections
-        if *sat_conn_count.get(sat_id.0 as usize).unwrap() >= MAX_ALLOWED_USERS {
-            for user_id in conns_by_sat.get(sat_id.0 as usize).unwrap() {
-                for i in 0..MAX_COLOR_OPTIONS {
-                    available_conns.remove(&(Color::from_id(i as i32 + 1), *user_id, sat_id));
-                }
-            }
+    fn for_each_sat_link(&self, link: Link, f: impl FnMut(Link)) {
+        let sat = self.links[link].1 .0 as usize;
+        (self.first_link[sat]..self.first_link[sat + 1]).for_each(f);
+    }
+
+    fn for_each_conflict(&self, link: Link, mut f: impl FnMut(Link)) {
+        let sat = self.links[link].1 .0 as usize;
+        let first = self.first_link[sat];
+        for other in self.graphs[sat].row(link - first) {
+            f(first + *other as usize);
         }
     }
-    solution
+}
+
+pub fn solve(users: &BTreeMap<User, Vector3>, sats: &BTreeMap<Sat, Vector3>) -> SolutionMap {
+    solve_with(users, sats, &SolverConfig::default(), &FewestOptions)
+}
+
+pub fn solve_with(
+    users: &BTreeMap<User, Vector3>,
+    sats: &BTreeMap<Sat, Vector3>,
+    config: &SolverConfig,
+    scoring: &(impl Scoring + Sync),
+) -> SolutionMap {
+    solve_from(users, sats, config, scoring, &SolutionMap::new())
+}
+
+/// Like `solve_with`, but first keeps every assignment in `previous` that is still
+/// possible at these positions, with its color, and only then fills in the rest.
+pub fn solve_from(
+    users: &BTreeMap<User, Vector3>,
+    sats: &BTreeMap<Sat, Vector3>,
+    config: &SolverConfig,
+    scoring: &(impl Scoring + Sync),
+    previous: &SolutionMap,
+) -> SolutionMap {
+    config.install(|| solve_inner(users, sats, config.seed, scoring, previous))
+}
+
+fn solve_inner(
+    users: &BTreeMap<User, Vector3>,
+    sats: &BTreeMap<Sat, Vector3>,
+    seed: u64,
+    scoring: &impl Scoring,
+    previous: &SolutionMap,
+) -> SolutionMap {
+    let mut span = trace::span("solve");
+    span.count("users", users.len());
+    span.count("sats", sats.len());
+    // The solver indexes users and satellites by id, so it works on dense ids 0, 1,
+    // 2... in id order and maps them back at the end.
+    let user_ids: Vec<User> = users.keys().copied().collect();
+    let sat_ids: Vec<Sat> = sats.keys().copied().collect();
+    let users_vec: Users = users.values().copied().collect();
+    let sats_vec: Sats = sats.values().copied().collect();
+
+    let (conns_by_user, conns_by_sat) = possible_connections(&users_vec, &sats_vec);
+    let interference_by_sat_user = get_interferences(&users_vec, &sats_vec, &conns_by_sat);
+
+    let candidates = LinkCandidates::new(&conns_by_user, &interference_by_sat_user);
+    let keep: Vec<(Link, Color)> = previous
+        .iter()
+        .filter_map(|(user, (sat, color))| {
+            let user = User(user_ids.binary_search(user).ok()? as u64);
+            let sat = sat_ids.binary_search(sat).ok()?;
+            let graph = &interference_by_sat_user[sat];
+            Some((candidates.first_link[sat] + graph.local(user)?, *color))
+        })
+        .collect();
+    greedy::assign_from(&candidates, scoring, seed, &keep)
+        .into_iter()
+        .map(|(user, sat, color)| (user_ids[user.0 as usize], (sat_ids[sat.0 as usize], color)))
+        .collect()
 }
 
 // #[cfg(test)]
@@ -253,3 +360,137 @@ pub fn solve(users: &HashMap<User, Vector3>, sats: &HashMap<Sat, Vector3>) -> So
 //         assert_eq!(solution.len(), MAX_ALLOWED_USERS);
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::Scenario;
+
+    fn positions(scenario: &Scenario) -> (Users, Sats) {
+        (
+            scenario.users.values().copied().collect(),
+            scenario.sats.values().copied().collect(),
+        )
+    }
+
+    #[test]
+    fn test_interference_graph_matches_brute_force() {
+        for path in [
+            "../test/03_equatorial_band.txt",
+            "../test/06_ten_thousand.txt",
+        ] {
+            let scenario = Scenario::new(path).unwrap();
+            let (users, sats) = positions(&scenario);
+            let (conns_by_user, conns_by_sat) = possible_connections(&users, &sats);
+            assert_eq!(conns_by_user.len(), users.len());
+            assert_eq!(conns_by_sat.len(), sats.len());
+            let graphs = get_interferences(&users, &sats, &conns_by_sat);
+            let cos_min_beam_angle = MINIMUM_BEAM_ANGLE.to_radians().cos();
+
+            let mut edges = 0;
+            for (sat_id, (sat_users, sat_pos)) in conns_by_sat.iter().zip(&sats).enumerate() {
+                for user in sat_users {
+                    let mut expected: Vec<User> = sat_users
+                        .iter()
+                        .copied()
+                        .filter(|other| {
+                            // The graph's rule: at most `MINIMUM_BEAM_ANGLE` apart.
+                            other != user
+                                && sat_pos.within_angle(
+                                    &users[user.0 as usize],
+                                    &users[other.0 as usize],
+                                    cos_min_beam_angle,
+                                )
+                        })
+                        .collect();
+                    expected.sort();
+                    let found: Vec<User> = graphs[sat_id].neighbors(*user).collect();
+                    assert_eq!(found, expected, "{path}: sat {sat_id}, user {user}");
+                    edges += expected.len();
+                }
+            }
+            assert_eq!(graphs.iter().map(|g| g.edge_count()).sum::<usize>(), edges);
+            assert!(edges > 0, "{path} has no interference to check");
+        }
+    }
+
+    // Each solver must give byte-identical plans for the same input and seed, whatever
+    // the thread count. The two solvers aren't compared with each other.
+    #[test]
+    fn test_solvers_are_deterministic() {
+        type Solve = fn(&Scenario, &SolverConfig) -> SolutionMap;
+        fn render(solution: &SolutionMap) -> String {
+            solution
+                .iter()
+                .map(|(user, (sat, color))| format!("{user} {sat} {color}\n"))
+                .collect()
+        }
+
+        let scenario = Scenario::new("../test/04_five_thousand.txt").unwrap();
+        let solvers: [(&str, Solve); 2] = [
+            ("solution_v", |scenario, config| {
+                solve_with(&scenario.users, &scenario.sats, config, &FewestOptions)
+            }),
+            ("solution_e", |scenario, config| {
+                crate::solution_e::solve_with(
+                    &scenario.users,
+                    &scenario.sats,
+                    config,
+                    &FewestOptions,
+                )
+            }),
+        ];
+        for (name, solve) in solvers {
+            for seed in [0, 42] {
+                let mut outputs = Vec::new();
+                for threads in [1, 4] {
+                    for _ in 0..2 {
+                        outputs.push(render(&solve(&scenario, &SolverConfig { seed, threads })));
+                    }
+                }
+                assert!(!outputs[0].is_empty());
+                for output in &outputs[1..] {
+                    assert!(*output == outputs[0], "{name}, seed {seed}: plans differ");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_interference_graph_unknown_user() {
+        let users = vec![
+            Vector3::new(6371.0, 0.0, 0.0),
+            Vector3::new(6371.0, 1.0, 0.0),
+        ];
+        let sat_pos = Vector3::new(6921.0, 0.0, 0.0);
+        let graph = InterferenceGraph::new(&users, &sat_pos, &[User(1), User(0)]);
+        assert_eq!(graph.neighbors(User(0)).collect::<Vec<_>>(), vec![User(1)]);
+        assert_eq!(graph.neighbors(User(1)).collect::<Vec<_>>(), vec![User(0)]);
+        assert_eq!(graph.neighbors(User(7)).count(), 0);
+    }
+
+    #[test]
+    fn test_interference_graph_boundary() {
+        // Users 1, 2 and 3 are 9.99°, 10° and 10.01° from user 0 as seen from the
+        // satellite. Exactly 10° conflicts; the others fall either side.
+        let sat_pos = Vector3::new(6921.0, 0.0, 0.0);
+        let direction = |degrees: f32| {
+            let (sin, cos) = degrees.to_radians().sin_cos();
+            sat_pos + Vector3::new(-cos, sin, 0.0).scale(500.0)
+        };
+        let users = vec![
+            direction(0.0),
+            direction(MINIMUM_BEAM_ANGLE - 0.01),
+            direction(MINIMUM_BEAM_ANGLE),
+            direction(MINIMUM_BEAM_ANGLE + 0.01),
+        ];
+        let cos_min_beam_angle = MINIMUM_BEAM_ANGLE.to_radians().cos();
+        assert!(sat_pos.within_angle(&users[0], &users[2], cos_min_beam_angle));
+
+        let graph = InterferenceGraph::new(&users, &sat_pos, &[User(0), User(1), User(2), User(3)]);
+        assert_eq!(
+            graph.neighbors(User(0)).collect::<Vec<_>>(),
+            vec![User(1), User(2)]
+        );
+    }
+}
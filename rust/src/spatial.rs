@@ -0,0 +1,184 @@
+use crate::util::Vector3;
+
+/// Spatial index over directions (unit vectors) for "everything within angle θ of
+/// direction d" queries.
+///
+/// This is an implicit k-d tree: the entries are reordered in place so that the
+/// median of every range is the splitting node and its halves are the subtrees.
+/// There are no node allocations, so memory is one `([f32; 3], T)` per entry.
+pub struct SphereIndex<T> {
+    entries: Vec<([f32; 3], T)>,
+}
+
+impl<T: Copy + Send> SphereIndex<T> {
+    /// Builds the index from `(position, item)` pairs. Positions are normalized, so
+    /// any vector pointing in the right direction works. Zero-length positions have
+    /// no direction and are left out.
+    pub fn new(items: impl IntoIterator<Item = (Vector3, T)>) -> Self {
+        let mut entries: Vec<([f32; 3], T)> = items
+            .into_iter()
+            .filter(|(pos, _)| pos.length() > 0.0)
+            .map(|(pos, item)| (pos.unit().to_array(), item))
+            .collect();
+        build(&mut entries, 0);
+        Self { entries }
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Calls `f` for every item whose direction is within the angle whose cosine is
+    /// `cos_threshold` of `direction`. Items come out in index order, not sorted.
+    pub fn for_each_within(&self, direction: &Vector3, cos_threshold: f32, mut f: impl FnMut(T)) {
+        if direction.length() <= 0.0 {
+            return;
+        }
+        let query = Query {
+            direction: direction.unit().to_array(),
+            cos_threshold,
+            // Points on the unit sphere within angle θ lie within chord 2 sin(θ / 2),
+            // and |a - b|² = 2 - 2 cos θ for unit a and b. The slack covers rounding in
+            // the normalized entries so pruning never drops a point the dot test keeps.
+            chord_squared: 2.0 - 2.0 * cos_threshold + 1e-5,
+        };
+        query.visit(&self.entries, 0, &mut f);
+    }
+
+    /// Collects the items `for_each_within` would visit.
+    pub fn within(&self, direction: &Vector3, cos_threshold: f32) -> Vec<T> {
+        let mut found = Vec::new();
+        self.for_each_within(direction, cos_threshold, |item| found.push(item));
+        found
+    }
+}
+
+/// Widest geocentric angle between a user and the point under a satellite at which the
+/// user still sees the satellite within `max_zenith_angle` degrees of vertical. Lower
+/// users see further, so bounding with the lowest user's radius covers everyone. The
+/// result is padded, so use it to pick candidates and then check them exactly.
+pub fn max_central_angle(sat_radius: f32, min_user_radius: f32, max_zenith_angle: f32) -> f32 {
+    let zenith = (max_zenith_angle as f64).to_radians();
+    let sin_nadir = min_user_radius as f64 * zenith.sin() / sat_radius as f64;
+    if sat_radius <= min_user_radius || sin_nadir >= 1.0 {
+        // Satellite at or below some user: no useful bound, so consider everyone.
+        return std::f32::consts::PI;
+    }
+    (zenith - sin_nadir.asin() + 1e-3) as f32
+}
+
+fn build<T>(entries: &mut [([f32; 3], T)], depth: usize) {
+    if entries.len() <= 1 {
+        return;
+    }
+    let axis = depth % 3;
+    let mid = entries.len() / 2;
+    entries.select_nth_unstable_by(mid, |a, b| a.0[axis].total_cmp(&b.0[axis]));
+    let (left, right) = entries.split_at_mut(mid);
+    build(left, depth + 1);
+    build(&mut right[1..], depth + 1);
+}
+
+struct Query {
+    direction: [f32; 3],
+    cos_threshold: f32,
+    chord_squared: f32,
+}
+
+impl Query {
+    fn visit<T: Copy>(&self, entries: &[([f32; 3], T)], depth: usize, f: &mut impl FnMut(T)) {
+        if entries.is_empty() {
+            return;
+        }
+        let axis = depth % 3;
+        let mid = entries.len() / 2;
+        let (point, item) = entries[mid];
+
+        let dot = point[0] * self.direction[0]
+            + point[1] * self.direction[1]
+            + point[2] * self.direction[2];
+        if dot >= self.cos_threshold {
+            f(item);
+        }
+
+        let offset = self.direction[axis] - point[axis];
+        let (near, far) = if offset < 0.0 {
+            (&entries[..mid], &entries[mid + 1..])
+        } else {
+            (&entries[mid + 1..], &entries[..mid])
+        };
+        self.visit(near, depth + 1, f);
+        if offset * offset <= self.chord_squared {
+            self.visit(far, depth + 1, f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deterministic points spread over the sphere (Fibonacci lattice).
+    fn lattice(n: usize) -> Vec<Vector3> {
+        let golden = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+        (0..n)
+            .map(|i| {
+                let z = 1.0 - 2.0 * (i as f32 + 0.5) / n as f32;
+                let r = (1.0 - z * z).sqrt();
+                let theta = golden * i as f32;
+                Vector3::new(r * theta.cos(), r * theta.sin(), z).scale(6371.0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let points = lattice(5000);
+        let index = SphereIndex::new(points.iter().copied().zip(0..));
+        assert_eq!(index.len(), points.len());
+
+        for degrees in [0.5f32, 5.0, 10.0, 45.0, 120.0, 180.0] {
+            let cos_threshold = degrees.to_radians().cos();
+            for direction in lattice(37) {
+                let d = direction.unit();
+                let mut expected: Vec<usize> = (0..points.len())
+                    .filter(|&i| points[i].unit().dot(d) >= cos_threshold)
+                    .collect();
+                let mut found = index.within(&direction, cos_threshold);
+                expected.sort();
+                found.sort();
+                assert_eq!(found, expected, "{degrees} degrees");
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_central_angle_covers_visible_users() {
+        // A satellite 550 km up straight above the x axis, and users on a great circle
+        // walking away from it. Everyone who sees it within 45° must be inside the bound.
+        let sat = Vector3::new(6921.0, 0.0, 0.0);
+        let bound = max_central_angle(sat.length(), 6371.0, 45.0);
+        let cos_45 = 45f32.to_radians().cos();
+        for i in 0..1000 {
+            let t = i as f32 * 0.001;
+            let user = Vector3::new(6371.0 * t.cos(), 6371.0 * t.sin(), 0.0);
+            if Vector3::zero().within_angle(&user, &(sat - user), cos_45) {
+                assert!(t <= bound, "{t} > {bound}");
+            }
+        }
+        assert!(bound < 0.1);
+        assert_eq!(
+            max_central_angle(6000.0, 6371.0, 45.0),
+            std::f32::consts::PI
+        );
+    }
+
+    #[test]
+    fn test_skips_zero_vectors() {
+        let index = SphereIndex::new([(Vector3::zero(), 0), (Vector3::new(1.0, 0.0, 0.0), 1)]);
+        assert_eq!(index.len(), 1);
+        assert_eq!(index.within(&Vector3::new(1.0, 0.0, 0.0), 0.0), vec![1]);
+        assert!(index.within(&Vector3::zero(), -1.0).is_empty());
+    }
+}
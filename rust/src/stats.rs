@@ -0,0 +1,169 @@
+use crate::json::Json;
+use crate::scenario::Scenario;
+use crate::spatial::{max_central_angle, SphereIndex};
+use crate::util::{User, Vector3, MAX_ALLOWABLE_BEAM_ANGLE, MAX_ALLOWED_USERS};
+
+use rayon::prelude::*;
+
+/// A summary of a scenario's size, geometry and how well its satellites can cover its
+/// users. Distances are in km.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub users: usize,
+    pub sats: usize,
+    /// Satellites with orbits rather than fixed positions.
+    pub orbits: usize,
+    pub min_coverage: f32,
+    /// Smallest and largest distance of a user from the Earth's center.
+    pub user_radius: Option<(f32, f32)>,
+    /// Smallest and largest satellite distance from the Earth's center.
+    pub sat_radius: Option<(f32, f32)>,
+    /// Users that see at least one satellite within 45° of vertical.
+    pub reachable_users: usize,
+    /// Visible satellites per user, on average and at most.
+    pub mean_sats_per_user: f64,
+    pub max_sats_per_user: usize,
+    /// Visible users per satellite, at most.
+    pub max_users_per_sat: usize,
+    /// The best coverage any solution could reach, ignoring interference: reachable
+    /// users, capped by 32 per satellite.
+    pub coverage_bound: f32,
+    /// The share of user weight reachable at all. Capacity isn't taken into account,
+    /// so this is looser than `coverage_bound`.
+    pub weighted_coverage_bound: f32,
+}
+
+impl Stats {
+    pub fn new(scenario: &Scenario) -> Self {
+        let radius_range = |positions: Vec<f32>| {
+            let min = positions.iter().copied().reduce(f32::min)?;
+            let max = positions.iter().copied().reduce(f32::max)?;
+            Some((min, max))
+        };
+        let user_radius = radius_range(scenario.users.values().map(Vector3::length).collect());
+        let sat_radius = radius_range(scenario.sats.values().map(Vector3::length).collect());
+
+        let users: Vec<(&User, &Vector3)> = scenario.users.iter().collect();
+        let index = SphereIndex::new(users.iter().enumerate().map(|(i, (_, pos))| (**pos, i)));
+        let min_user_radius = user_radius.map_or(0.0, |(min, _)| min);
+        let cos_beam = MAX_ALLOWABLE_BEAM_ANGLE.to_radians().cos();
+        let visible: Vec<Vec<usize>> = scenario
+            .sats
+            .values()
+            .collect::<Vec<_>>()
+            .par_iter()
+            .map(|sat_pos| {
+                let cos_central =
+                    max_central_angle(sat_pos.length(), min_user_radius, MAX_ALLOWABLE_BEAM_ANGLE)
+                        .cos();
+                index
+                    .within(sat_pos, cos_central)
+                    .into_iter()
+                    .filter(|&i| {
+                        let user_pos = users[i].1;
+                        Vector3::zero().within_angle(user_pos, &(*sat_pos - user_pos), cos_beam)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut sats_per_user = vec![0usize; users.len()];
+        for seen in &visible {
+            for &i in seen {
+                sats_per_user[i] += 1;
+            }
+        }
+        let reachable_users = sats_per_user.iter().filter(|&&n| n > 0).count();
+        let served_bound = reachable_users.min(MAX_ALLOWED_USERS * scenario.sats.len());
+        let weight = |reachable_only: bool| -> f64 {
+            users
+                .iter()
+                .zip(&sats_per_user)
+                .filter(|(_, &n)| n > 0 || !reachable_only)
+                .map(|((user, _), _)| scenario.weight(**user) as f64)
+                .sum()
+        };
+        let total_weight = weight(false);
+        Self {
+            users: users.len(),
+            sats: scenario.sats.len(),
+            orbits: scenario.orbits.len(),
+            min_coverage: scenario.min_coverage,
+            user_radius,
+            sat_radius,
+            reachable_users,
+            mean_sats_per_user: if users.is_empty() {
+                0.0
+            } else {
+                sats_per_user.iter().sum::<usize>() as f64 / users.len() as f64
+            },
+            max_sats_per_user: sats_per_user.iter().copied().max().unwrap_or(0),
+            max_users_per_sat: visible.iter().map(Vec::len).max().unwrap_or(0),
+            coverage_bound: if users.is_empty() {
+                1.0
+            } else {
+                served_bound as f32 / users.len() as f32
+            },
+            weighted_coverage_bound: if total_weight <= 0.0 {
+                1.0
+            } else {
+                (weight(true) / total_weight) as f32
+            },
+        }
+    }
+
+    pub fn to_json(&self) -> Json {
+        let range = |range: Option<(f32, f32)>| {
+            range.map(|(min, max)| Json::object().with("min", min).with("max", max))
+        };
+        Json::object()
+            .with("users", self.users)
+            .with("sats", self.sats)
+            .with("orbits", self.orbits)
+            .with("min_coverage", self.min_coverage)
+            .with("user_radius", range(self.user_radius))
+            .with("sat_radius", range(self.sat_radius))
+            .with("reachable_users", self.reachable_users)
+            .with("mean_sats_per_user", self.mean_sats_per_user)
+            .with("max_sats_per_user", self.max_sats_per_user)
+            .with("max_users_per_sat", self.max_users_per_sat)
+            .with("coverage_bound", self.coverage_bound)
+            .with("weighted_coverage_bound", self.weighted_coverage_bound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let stats = Stats::new(&Scenario::new("../test/02_five_users.txt").unwrap());
+        assert_eq!((stats.users, stats.sats, stats.orbits), (5, 1, 0));
+        assert_eq!(stats.reachable_users, 5);
+        assert_eq!((stats.max_sats_per_user, stats.max_users_per_sat), (1, 5));
+        assert_eq!(stats.mean_sats_per_user, 1.0);
+        assert_eq!(stats.coverage_bound, 1.0);
+        assert_eq!(stats.sat_radius, Some((6921.0, 6921.0)));
+
+        // A user on the far side of the Earth that no satellite reaches.
+        let mut scenario = Scenario::new("../test/02_five_users.txt").unwrap();
+        let far = User::new(1000);
+        scenario.users.insert(far, Vector3::new(-6371.0, 0.0, 0.0));
+        scenario.weights.insert(far, 5.0);
+        let stats = Stats::new(&scenario);
+        assert_eq!(stats.reachable_users, 5);
+        assert_eq!(stats.weighted_coverage_bound, 0.5);
+
+        let stats = Stats::new(&Scenario::default());
+        assert_eq!(stats.user_radius, None);
+        assert_eq!(stats.coverage_bound, 1.0);
+        assert_eq!(
+            stats.to_json().to_string(),
+            "{\"users\":0,\"sats\":0,\"orbits\":0,\"min_coverage\":1,\"user_radius\":null,\
+             \"sat_radius\":null,\"reachable_users\":0,\"mean_sats_per_user\":0,\
+             \"max_sats_per_user\":0,\"max_users_per_sat\":0,\"coverage_bound\":1,\
+             \"weighted_coverage_bound\":1}"
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use crate::json::Json;
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// A finished phase of work, with whatever the code inside it counted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub name: &'static str,
+    /// When the span opened, relative to `start`.
+    pub start: Duration,
+    pub duration: Duration,
+    /// A small number per thread, in the order threads first opened a span.
+    pub thread: u64,
+    /// How many spans were already open on this thread when it opened.
+    pub depth: usize,
+    pub counts: Vec<(&'static str, u64)>,
+}
+
+// Spans are only recorded between `start` and `finish`; otherwise `span` is a single
+// atomic load.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static ORIGIN: Mutex<Option<Instant>> = Mutex::new(None);
+static SPANS: Mutex<Vec<Span>> = Mutex::new(Vec::new());
+static NEXT_THREAD: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD: u64 = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Starts recording spans from every thread, dropping any recorded before.
+pub fn start() {
+    *ORIGIN.lock().unwrap() = Some(Instant::now());
+    SPANS.lock().unwrap().clear();
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Stops recording and returns the spans recorded since `start`, thread by thread in
+/// the order they opened, so outer spans come before the ones inside them.
+pub fn finish() -> Vec<Span> {
+    ENABLED.store(false, Ordering::Release);
+    let mut spans = std::mem::take(&mut *SPANS.lock().unwrap());
+    spans.sort_by_key(|span| (span.thread, span.start, span.depth));
+    spans
+}
+
+/// Opens a span that closes when the guard is dropped.
+pub fn span(name: &'static str) -> Guard {
+    let opened = ENABLED.load(Ordering::Acquire).then(|| {
+        let depth = DEPTH.with(|depth| depth.replace(depth.get() + 1));
+        (Instant::now(), depth)
+    });
+    Guard {
+        name,
+        opened,
+        counts: Vec::new(),
+    }
+}
+
+/// An open span. See `span`.
+#[must_use = "the span closes as soon as the guard is dropped"]
+pub struct Guard {
+    name: &'static str,
+    opened: Option<(Instant, usize)>,
+    counts: Vec<(&'static str, u64)>,
+}
+
+impl Guard {
+    /// Records `value` under `key`, replacing any earlier value.
+    pub fn count(&mut self, key: &'static str, value: usize) {
+        if self.opened.is_none() {
+            return;
+        }
+        let value = value as u64;
+        match self.counts.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.counts.push((key, value)),
+        }
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let Some((opened, depth)) = self.opened else {
+            return;
+        };
+        let duration = opened.elapsed();
+        DEPTH.with(|d| d.set(depth));
+        // `finish` may have run while the span was open.
+        let Some(origin) = *ORIGIN.lock().unwrap() else {
+            return;
+        };
+        if !ENABLED.load(Ordering::Acquire) {
+            return;
+        }
+        SPANS.lock().unwrap().push(Span {
+            name: self.name,
+            start: opened.saturating_duration_since(origin),
+            duration,
+            thread: THREAD.with(|thread| *thread),
+            depth,
+            counts: std::mem::take(&mut self.counts),
+        });
+    }
+}
+
+fn counts_json(counts: &[(&'static str, u64)]) -> Json {
+    counts
+        .iter()
+        .fold(Json::object(), |json, (key, value)| json.with(key, *value))
+}
+
+impl Span {
+    pub fn to_json(&self) -> Json {
+        Json::object()
+            .with("name", self.name)
+            .with("start", self.start.as_secs_f64())
+            .with("seconds", self.duration.as_secs_f64())
+            .with("thread", self.thread)
+            .with("depth", self.depth)
+            .with("counts", counts_json(&self.counts))
+    }
+}
+
+/// `spans` in the Chrome trace event format, which `chrome://tracing`, Perfetto and
+/// speedscope open as a flame chart.
+pub fn chrome_trace(spans: &[Span]) -> Json {
+    let events: Vec<Json> = spans
+        .iter()
+        .map(|span| {
+            Json::object()
+                .with("name", span.name)
+                .with("cat", "beam_planner")
+                .with("ph", "X")
+                .with("ts", span.start.as_secs_f64() * 1e6)
+                .with("dur", span.duration.as_secs_f64() * 1e6)
+                .with("pid", 1)
+                .with("tid", span.thread)
+                .with("args", counts_json(&span.counts))
+        })
+        .collect();
+    Json::object()
+        .with("traceEvents", events)
+        .with("displayTimeUnit", "ms")
+}
+
+/// One line per span, indented by depth, such as
+/// `  possible_connections 12.3 ms candidate_links=51234`.
+pub fn summary(spans: &[Span]) -> Vec<String> {
+    spans
+        .iter()
+        .map(|span| {
+            let mut line = format!(
+                "{}{} {:.1} ms",
+                "  ".repeat(span.depth),
+                span.name,
+                span.duration.as_secs_f64() * 1e3
+            );
+            for (key, value) in &span.counts {
+                line += &format!(" {}={}", key, value);
+            }
+            line
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::Scenario;
+    use crate::{solution_e, solution_v};
+
+    // Other tests may solve while this one records, so it only looks at spans from
+    // this scenario's solves.
+    #[test]
+    fn test_solver_phases_are_traced() {
+        let scenario = Scenario::new("../test/03_equatorial_band.txt").unwrap();
+        let users = scenario.users.len() as u64;
+        start();
+        let solution = solution_v::solve(&scenario.users, &scenario.sats);
+        solution_e::solve(&scenario.users, &scenario.sats);
+        let spans = finish();
+        let _ = span("after");
+        assert!(finish().is_empty());
+
+        let solves: Vec<&Span> = spans
+            .iter()
+            .filter(|span| span.name == "solve" && span.counts.contains(&("users", users)))
+            .collect();
+        assert_eq!(solves.len(), 2);
+        for solve in solves {
+            let inside: Vec<&Span> = spans
+                .iter()
+                .filter(|span| {
+                    span.thread == solve.thread
+                        && span.depth > solve.depth
+                        && span.start >= solve.start
+                        && span.start + span.duration <= solve.start + solve.duration
+                })
+                .collect();
+            let count = |name: &str, key: &str| {
+                let span = inside.iter().find(|span| span.name == name).unwrap();
+                span.counts.iter().find(|(k, _)| *k == key).unwrap().1
+            };
+            assert!(count("possible_connections", "candidate_links") >= users);
+            assert!(count("get_interferences", "interference_edges") > 0);
+            assert_eq!(
+                count("greedy", "assignments"),
+                solution.len() as u64,
+                "{:?}",
+                inside
+            );
+        }
+
+        let lines = summary(&spans);
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with("  possible_connections ")));
+        let trace = chrome_trace(&spans).to_string();
+        assert!(trace.starts_with(r#"{"traceEvents":[{"name":"#));
+        assert!(trace.contains(r#""ph":"X""#));
+        assert!(crate::json::parse(&trace).is_ok());
+    }
+}
@@ -1,31 +1,104 @@
 use core::fmt;
 use std::{
     fmt::{Display, Formatter},
-    ops::{Add, Deref, Div, Sub},
-    simd::{num::SimdFloat, StdFloat},
+    ops::{Add, Div, Sub},
 };
 
-use std::simd::f32x4;
+#[cfg(feature = "simd")]
+use std::simd::{f32x4, num::SimdFloat};
 
+// With the `simd` feature the components live in a single `f32x4` lane register
+// (the fourth lane is always zero). Without it we fall back to plain scalar math,
+// which needs no nightly features. Both backends sum the components in x, y, z
+// order, so they produce bit-identical results.
+#[cfg(feature = "simd")]
 #[derive(Debug, Clone, Copy, PartialEq)]
-
 pub struct Vector3(f32x4);
+
+#[cfg(not(feature = "simd"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vector3([f32; 3]);
 // pub struct Vector3 {
 //     pub x: f32,
 //     pub y: f32,
 //     pub z: f32,
 // }
 
+#[cfg(feature = "simd")]
 impl Vector3 {
     pub const fn new(x: f32, y: f32, z: f32) -> Self {
         Self(f32x4::from_array([x, y, z, 0.0]))
     }
 
+    pub fn to_array(self) -> [f32; 3] {
+        let [x, y, z, _] = self.0.to_array();
+        [x, y, z]
+    }
+
     pub fn dot(&self, other: Self) -> f32 {
         let a = self.0 * other.0;
         a.reduce_sum()
     }
 
+    fn _add(&self, other: &Self) -> Self {
+        Self(self.0 + other.0)
+    }
+
+    fn _sub(&self, other: &Self) -> Self {
+        Self(self.0 - other.0)
+    }
+
+    pub fn vec_division(&self, other: &Self) -> Self {
+        Self(self.0 / other.0)
+    }
+
+    pub fn scale(&self, scalar: f32) -> Self {
+        Self(self.0 * f32x4::splat(scalar))
+    }
+
+    fn scale_inv(&self, divisor: f32) -> Self {
+        Self(self.0 / f32x4::splat(divisor))
+    }
+}
+
+#[cfg(not(feature = "simd"))]
+impl Vector3 {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self([x, y, z])
+    }
+
+    pub fn to_array(self) -> [f32; 3] {
+        self.0
+    }
+
+    pub fn dot(&self, other: Self) -> f32 {
+        let [x, y, z] = self.0;
+        let [ox, oy, oz] = other.0;
+        x * ox + y * oy + z * oz
+    }
+
+    fn _add(&self, other: &Self) -> Self {
+        Self(std::array::from_fn(|i| self.0[i] + other.0[i]))
+    }
+
+    fn _sub(&self, other: &Self) -> Self {
+        Self(std::array::from_fn(|i| self.0[i] - other.0[i]))
+    }
+
+    pub fn vec_division(&self, other: &Self) -> Self {
+        Self(std::array::from_fn(|i| self.0[i] / other.0[i]))
+    }
+
+    pub fn scale(&self, scalar: f32) -> Self {
+        Self(self.0.map(|c| c * scalar))
+    }
+
+    fn scale_inv(&self, divisor: f32) -> Self {
+        Self(self.0.map(|c| c / divisor))
+    }
+}
+
+impl Vector3 {
     //     pub const fn cross(&self, other: &Self) -> Self {
     //         Self {
     //             x: self.y * other.z - self.z * other.y,
@@ -35,30 +108,20 @@ impl Vector3 {
     //     }
 
     pub fn length(&self) -> f32 {
-        (self.0 * self.0).reduce_sum().sqrt()
+        self.dot(*self).sqrt()
         // (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
     }
 
     pub fn normalize(&self) -> Self {
-        let length = self.length();
-        Vector3(self.0 / f32x4::splat(length))
+        self.scale_inv(self.length())
     }
 
     pub fn unit(&self) -> Self {
-        let length = self.length();
-        Vector3(self.0 / f32x4::splat(length))
-    }
-
-    fn _add(&self, other: &Self) -> Self {
-        Self(self.0 + other.0)
-    }
-
-    fn _sub(&self, other: &Self) -> Self {
-        Self(self.0 - other.0)
+        self.scale_inv(self.length())
     }
 
     fn _sub_de(&self, other: Self) -> Self {
-        Self(self.0 - other.0)
+        self._sub(&other)
     }
 
     //     pub fn abs_difference(&self, other: &Self) -> Self {
@@ -69,24 +132,28 @@ impl Vector3 {
     //         }
     //     }
 
-    pub fn vec_division(&self, other: &Self) -> Self {
-        Self(self.0 / other.0)
-    }
-
-    pub fn scale(&self, scalar: f32) -> Self {
-        Self(self.0 * f32x4::splat(scalar))
-    }
-
     pub fn angle_between(&self, a: &Self, c: &Self) -> f32 {
-        let m = Vector3(a.0 - self.0).unit();
-        let n = Vector3(c.0 - self.0).unit();
-        let r = m.dot(n);
+        let m = (a - self).unit();
+        let n = (c - self).unit();
+        // Rounding can push the cosine of (anti)parallel vectors just past ±1.
+        let r = m.dot(n).clamp(-1.0, 1.0);
 
         r.acos().to_degrees()
     }
 
+    /// Whether the angle at `self` between `a` and `b` is at most the angle whose cosine
+    /// is `cos_threshold`. Equivalent to `self.angle_between(a, b) <= threshold` but
+    /// skips the two normalizations and the `acos`, so it is cheap enough for inner loops.
+    /// Like `angle_between` (which yields NaN), a zero-length side is never within range.
+    pub fn within_angle(&self, a: &Self, b: &Self, cos_threshold: f32) -> bool {
+        let m = a - self;
+        let n = b - self;
+        let lengths = (m.dot(m) * n.dot(n)).sqrt();
+        lengths > 0.0 && m.dot(n) >= cos_threshold * lengths
+    }
+
     pub fn zero() -> Self {
-        Self(f32x4::splat(0.0))
+        Self::new(0.0, 0.0, 0.0)
     }
     //     pub const fn one() -> Self {
     //         Self::new(1.0, 1.0, 1.0)
@@ -101,11 +168,11 @@ impl Vector3 {
 
 impl Default for Vector3 {
     fn default() -> Self {
-        Self(f32x4::splat(0.0))
+        Self::zero()
     }
 }
 
-impl<'a, 'b> Sub<&'b Vector3> for &'a Vector3 {
+impl<'b> Sub<&'b Vector3> for &Vector3 {
     type Output = Vector3;
 
     fn sub(self, rhs: &'b Vector3) -> Vector3 {
@@ -113,7 +180,7 @@ impl<'a, 'b> Sub<&'b Vector3> for &'a Vector3 {
     }
 }
 
-impl<'a, 'b> Sub<&'b mut Vector3> for &'a mut Vector3 {
+impl<'b> Sub<&'b mut Vector3> for &mut Vector3 {
     type Output = Vector3;
 
     fn sub(self, rhs: &'b mut Vector3) -> Vector3 {
@@ -145,6 +212,17 @@ impl Div for Vector3 {
     }
 }
 
+/// How far from a user's vertical a satellite may be to serve it, in degrees.
+pub const MAX_ALLOWABLE_BEAM_ANGLE: f32 = 45.0;
+/// How close two users on the same satellite and color may be, in degrees as seen from
+/// the satellite.
+pub const MINIMUM_BEAM_ANGLE: f32 = 10.0;
+/// How many users a satellite may serve.
+pub const MAX_ALLOWED_USERS: usize = 32;
+/// How many colors a satellite's beams may use.
+pub const MAX_COLOR_OPTIONS: usize = 4;
+
+/// Serializes as its id, 1 to 4.
 #[derive(Clone, Copy, Debug, PartialEq, Hash, Eq, PartialOrd, Ord)]
 pub enum Color {
     Init = 0,
@@ -181,7 +259,12 @@ impl Display for Color {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct Sat(pub u64);
 
 impl Sat {
@@ -190,19 +273,18 @@ impl Sat {
     }
 }
 
-impl Default for Sat {
-    fn default() -> Self {
-        Self(0)
-    }
-}
-
 impl Display for Sat {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(f, "{}", self.0)
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct User(pub u64);
 
 impl User {
@@ -217,8 +299,105 @@ impl Display for User {
     }
 }
 
-impl Default for User {
-    fn default() -> Self {
-        Self(0)
+// Vectors are `[x, y, z]` arrays whichever backend holds them.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Vector3 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_array().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Vector3 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let [x, y, z] = <[f32; 3]>::deserialize(deserializer)?;
+        Ok(Self::new(x, y, z))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Color {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Color {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match u8::deserialize(deserializer)? {
+            id @ 1..=4 => Ok(Self::from_id(id as i32)),
+            id => Err(serde::de::Error::invalid_value(
+                serde::de::Unexpected::Unsigned(id as u64),
+                &"a color from 1 to 4",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // These hold for both the scalar and the `simd` backend; run the suite with and
+    // without the feature to confirm they agree.
+    #[test]
+    fn test_vector3_arithmetic() {
+        let a = Vector3::new(1.0, 2.0, 3.0);
+        let b = Vector3::new(4.0, -5.0, 6.0);
+        assert_eq!(a.dot(b), 12.0);
+        assert_eq!((a + b).to_array(), [5.0, -3.0, 9.0]);
+        assert_eq!((a - b).to_array(), [-3.0, 7.0, -3.0]);
+        assert_eq!(a.scale(2.0).to_array(), [2.0, 4.0, 6.0]);
+        assert_eq!(Vector3::new(3.0, 0.0, 4.0).length(), 5.0);
+        assert_eq!(
+            Vector3::new(0.0, 3.0, 4.0).unit().to_array(),
+            [0.0, 0.6, 0.8]
+        );
+        assert_eq!(Vector3::default(), Vector3::zero());
+    }
+
+    #[test]
+    fn test_angle_between() {
+        let origin = Vector3::zero();
+        let x = Vector3::new(6371.0, 0.0, 0.0);
+        let y = Vector3::new(0.0, 6371.0, 0.0);
+        assert_eq!(origin.angle_between(&x, &y), 90.0);
+        assert_eq!(origin.angle_between(&x, &x), 0.0);
+        // Parallel vectors whose normalized dot product rounds past 1.
+        let a = Vector3::new(1234.5, -2345.6, 3456.7);
+        assert!(!origin.angle_between(&a, &a.scale(1.3)).is_nan());
+        assert!(!origin.within_angle(&x, &origin, 1.0f32.to_radians().cos()));
+    }
+
+    #[test]
+    fn test_within_angle_matches_angle_between() {
+        let apex = Vector3::new(6921.0, 0.0, 0.0);
+        let cos_10 = 10f32.to_radians().cos();
+        for i in 0..200 {
+            let t = i as f32 * 0.01;
+            let a = Vector3::new(6371.0, 0.0, 0.0);
+            let b = Vector3::new(6371.0 * t.cos(), 6371.0 * t.sin(), 0.0);
+            let angle = apex.angle_between(&a, &b);
+            // Stay clear of the boundary where rounding decides either way.
+            if (angle - 10.0).abs() > 1e-3 {
+                assert_eq!(apex.within_angle(&a, &b, cos_10), angle <= 10.0, "{angle}");
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let v = Vector3::new(1.5, -2.0, 3.0);
+        assert_eq!(serde_json::to_string(&v).unwrap(), "[1.5,-2.0,3.0]");
+        let back: Vector3 = serde_json::from_str("[1.5,-2.0,3.0]").unwrap();
+        assert_eq!(back, v);
+        assert!(serde_json::from_str::<Vector3>("[1.0,2.0]").is_err());
+
+        assert_eq!(serde_json::to_string(&Color::C).unwrap(), "3");
+        assert_eq!(serde_json::from_str::<Color>("4").unwrap(), Color::D);
+        assert!(serde_json::from_str::<Color>("0").is_err());
+        assert_eq!(serde_json::to_string(&(User(7), Sat(2))).unwrap(), "[7,2]");
     }
 }
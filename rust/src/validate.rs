@@ -0,0 +1,369 @@
+use crate::scenario::Scenario;
+use crate::util::{Sat, User};
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Ignore,
+    Warning,
+    Error,
+}
+
+/// Tolerances for `Scenario::validate`, in km, and how seriously to take each kind of
+/// anomaly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValidationConfig {
+    pub earth_radius: f32,
+    /// How far users may be from `earth_radius`. The default covers the difference
+    /// between the mean radius and the ellipsoid plus the highest mountains.
+    pub surface_tolerance: f32,
+    /// How high above `earth_radius` satellites must be.
+    pub min_sat_altitude: f32,
+    pub duplicate_ids: Severity,
+    pub users_off_surface: Severity,
+    pub low_sats: Severity,
+    pub bad_weights: Severity,
+    pub non_finite: Severity,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            earth_radius: 6371.0,
+            surface_tolerance: 25.0,
+            min_sat_altitude: 0.0,
+            duplicate_ids: Severity::Error,
+            users_off_surface: Severity::Warning,
+            low_sats: Severity::Error,
+            bad_weights: Severity::Error,
+            non_finite: Severity::Error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    /// Line `line` defines a satellite or user that line `first_line` already did.
+    DuplicateId {
+        kind: &'static str,
+        id: u64,
+        line: usize,
+        first_line: usize,
+    },
+    /// The user is `radius` km from the Earth's center.
+    UserOffSurface { user: User, radius: f32 },
+    /// The satellite is `altitude` km above the surface (negative when underground).
+    SatTooLow { sat: Sat, altitude: f32 },
+    /// A negative weight, or a weight for a user the scenario doesn't have.
+    BadWeight { user: User, weight: f32 },
+    /// A satellite or user position, or a user's weight, is NaN or infinite. None of
+    /// the other checks can catch these, since every comparison with NaN is false.
+    NonFinite {
+        kind: &'static str,
+        id: u64,
+        field: &'static str,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub kind: IssueKind,
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            _ => "warning",
+        };
+        match &self.kind {
+            IssueKind::DuplicateId {
+                kind,
+                id,
+                line,
+                first_line,
+            } => write!(
+                f,
+                "{}: line {} redefines {} {} from line {}",
+                severity, line, kind, id, first_line
+            ),
+            IssueKind::UserOffSurface { user, radius } => write!(
+                f,
+                "{}: user {} is {} km from the Earth's center",
+                severity, user, radius
+            ),
+            IssueKind::SatTooLow { sat, altitude } => write!(
+                f,
+                "{}: satellite {} is {} km above the surface",
+                severity, sat, altitude
+            ),
+            IssueKind::BadWeight { user, weight } if *weight < 0.0 => write!(
+                f,
+                "{}: user {} has negative weight {}",
+                severity, user, weight
+            ),
+            IssueKind::BadWeight { user, .. } => {
+                write!(f, "{}: weight for unknown user {}", severity, user)
+            }
+            IssueKind::NonFinite { kind, id, field } => write!(
+                f,
+                "{}: {} {} has a non-finite {}",
+                severity, kind, id, field
+            ),
+        }
+    }
+}
+
+/// Every anomaly in `scenario` whose severity `config` doesn't set to `Ignore`, in the
+/// order duplicates, users, satellites, weights.
+pub fn validate(scenario: &Scenario, config: &ValidationConfig) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut report = |severity: Severity, kind: IssueKind| {
+        if severity != Severity::Ignore {
+            issues.push(Issue { severity, kind });
+        }
+    };
+
+    for duplicate in &scenario.duplicates {
+        report(
+            config.duplicate_ids,
+            IssueKind::DuplicateId {
+                kind: duplicate.kind,
+                id: duplicate.id,
+                line: duplicate.line,
+                first_line: duplicate.first_line,
+            },
+        );
+    }
+    for (user, pos) in &scenario.users {
+        if !pos.to_array().iter().all(|x| x.is_finite()) {
+            report(
+                config.non_finite,
+                IssueKind::NonFinite {
+                    kind: "user",
+                    id: user.0,
+                    field: "position",
+                },
+            );
+            continue;
+        }
+        let radius = pos.length();
+        if (radius - config.earth_radius).abs() > config.surface_tolerance {
+            report(
+                config.users_off_surface,
+                IssueKind::UserOffSurface {
+                    user: *user,
+                    radius,
+                },
+            );
+        }
+    }
+    for (sat, pos) in &scenario.sats {
+        if !pos.to_array().iter().all(|x| x.is_finite()) {
+            report(
+                config.non_finite,
+                IssueKind::NonFinite {
+                    kind: "satellite",
+                    id: sat.0,
+                    field: "position",
+                },
+            );
+            continue;
+        }
+        let altitude = pos.length() - config.earth_radius;
+        if altitude <= config.min_sat_altitude {
+            report(
+                config.low_sats,
+                IssueKind::SatTooLow {
+                    sat: *sat,
+                    altitude,
+                },
+            );
+        }
+    }
+    for (user, weight) in &scenario.weights {
+        if !weight.is_finite() {
+            report(
+                config.non_finite,
+                IssueKind::NonFinite {
+                    kind: "user",
+                    id: user.0,
+                    field: "weight",
+                },
+            );
+        } else if *weight < 0.0 || !scenario.users.contains_key(user) {
+            report(
+                config.bad_weights,
+                IssueKind::BadWeight {
+                    user: *user,
+                    weight: *weight,
+                },
+            );
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_scenario_lenient;
+    use crate::util::Vector3;
+
+    #[test]
+    fn test_reports_each_anomaly() {
+        let text = "\
+sat 1 6921 0 0
+sat 2 6000 0 0
+user 1 6371 0 0
+user 2 6500 0 0
+user 3 6371 0 0
+user 1 0 6371 0
+weight 3 -1
+weight 9 2
+";
+        let scenario = parse_scenario_lenient(text, "anomalies.txt").unwrap();
+        let issues = validate(&scenario, &ValidationConfig::default());
+        assert_eq!(
+            issues,
+            vec![
+                Issue {
+                    severity: Severity::Error,
+                    kind: IssueKind::DuplicateId {
+                        kind: "user",
+                        id: 1,
+                        line: 6,
+                        first_line: 3
+                    }
+                },
+                Issue {
+                    severity: Severity::Warning,
+                    kind: IssueKind::UserOffSurface {
+                        user: User(2),
+                        radius: 6500.0
+                    }
+                },
+                Issue {
+                    severity: Severity::Error,
+                    kind: IssueKind::SatTooLow {
+                        sat: Sat(2),
+                        altitude: -371.0
+                    }
+                },
+                Issue {
+                    severity: Severity::Error,
+                    kind: IssueKind::BadWeight {
+                        user: User(3),
+                        weight: -1.0
+                    }
+                },
+                Issue {
+                    severity: Severity::Error,
+                    kind: IssueKind::BadWeight {
+                        user: User(9),
+                        weight: 2.0
+                    }
+                },
+            ]
+        );
+        assert_eq!(
+            issues[2].to_string(),
+            "error: satellite 2 is -371 km above the surface"
+        );
+        assert_eq!(
+            issues[3].to_string(),
+            "error: user 3 has negative weight -1"
+        );
+        assert_eq!(issues[4].to_string(), "error: weight for unknown user 9");
+    }
+
+    #[test]
+    fn test_non_finite_values() {
+        // The parser rejects NaN, but epochs, generators and bindings can still
+        // produce it.
+        let text = "sat 1 6921 0 0\nsat 2 6921 0 0\nuser 1 6371 0 0\nweight 1 1\n";
+        let mut scenario = parse_scenario_lenient(text, "nan.txt").unwrap();
+        scenario
+            .sats
+            .insert(Sat(2), Vector3::new(f32::NAN, 0.0, 0.0));
+        scenario.weights.insert(User(1), f32::NAN);
+        let issues = validate(&scenario, &ValidationConfig::default());
+        assert_eq!(
+            issues,
+            vec![
+                Issue {
+                    severity: Severity::Error,
+                    kind: IssueKind::NonFinite {
+                        kind: "satellite",
+                        id: 2,
+                        field: "position"
+                    }
+                },
+                Issue {
+                    severity: Severity::Error,
+                    kind: IssueKind::NonFinite {
+                        kind: "user",
+                        id: 1,
+                        field: "weight"
+                    }
+                },
+            ]
+        );
+        assert_eq!(
+            issues[0].to_string(),
+            "error: satellite 2 has a non-finite position"
+        );
+    }
+
+    #[test]
+    fn test_tolerances_and_severities() {
+        let text = "sat 1 6471 0 0\nuser 1 6400 0 0\nuser 1 6371 0 0\n";
+        let scenario = parse_scenario_lenient(text, "edge.txt").unwrap();
+
+        let config = ValidationConfig {
+            surface_tolerance: 30.0,
+            min_sat_altitude: 200.0,
+            duplicate_ids: Severity::Ignore,
+            ..Default::default()
+        };
+        let kinds: Vec<IssueKind> = validate(&scenario, &config)
+            .into_iter()
+            .map(|issue| issue.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![IssueKind::SatTooLow {
+                sat: Sat(1),
+                altitude: 100.0
+            }]
+        );
+
+        let config = ValidationConfig {
+            surface_tolerance: 10.0,
+            users_off_surface: Severity::Error,
+            low_sats: Severity::Ignore,
+            ..Default::default()
+        };
+        let severities: Vec<Severity> = validate(&scenario, &config)
+            .into_iter()
+            .map(|issue| issue.severity)
+            .collect();
+        assert_eq!(severities, vec![Severity::Error, Severity::Error]);
+    }
+
+    #[test]
+    fn test_repo_scenarios_are_clean() {
+        for name in [
+            "01_two_users",
+            "02_five_users",
+            "03_equatorial_band",
+            "04_five_thousand",
+            "05_fifty_thousand_low_coverage",
+            "06_ten_thousand",
+        ] {
+            let scenario = Scenario::new(&format!("../test/{}.txt", name)).unwrap();
+            assert!(validate(&scenario, &ValidationConfig::default()).is_empty());
+        }
+    }
+}
@@ -0,0 +1,512 @@
+use crate::json::Json;
+use crate::scenario::Scenario;
+use crate::util::{
+    Color, Sat, User, Vector3, MAX_ALLOWABLE_BEAM_ANGLE, MAX_ALLOWED_USERS, MINIMUM_BEAM_ANGLE,
+};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+use rayon::prelude::*;
+
+type SolutionMap = BTreeMap<User, (Sat, Color)>;
+// Sorts by satellite, then color, then user.
+type Beam = (Sat, Color, User);
+
+/// One way a solution breaks the rules. Angles are in degrees.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind", rename_all = "snake_case")
+)]
+pub enum Violation {
+    /// The solution serves a user the scenario doesn't have.
+    UnknownUser { user: User },
+    /// The solution assigns a user to a satellite the scenario doesn't have.
+    UnknownSat { user: User, sat: Sat },
+    /// The satellite is `angle` from the user's vertical, more than 45°.
+    NotVisible { user: User, sat: Sat, angle: f32 },
+    /// The satellite serves `users` users, more than 32.
+    OverCapacity { sat: Sat, users: usize },
+    /// Two users on the same satellite and color are `angle` apart as seen from the
+    /// satellite, less than 10°. `users.0 < users.1`.
+    CoChannel {
+        sat: Sat,
+        color: Color,
+        users: (User, User),
+        angle: f32,
+    },
+    /// Fewer users served than the scenario's `min_coverage` asks for.
+    LowCoverage {
+        served: usize,
+        users: usize,
+        min_coverage: f32,
+    },
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownUser { user } => write!(f, "User {} is not in the scenario", user),
+            Self::UnknownSat { user, sat } => write!(
+                f,
+                "User {} is assigned to satellite {}, which is not in the scenario",
+                user, sat
+            ),
+            Self::NotVisible { user, sat, angle } => write!(
+                f,
+                "User {} cannot see satellite {} ({} degrees from vertical)",
+                user, sat, angle
+            ),
+            Self::OverCapacity { sat, users } => write!(
+                f,
+                "Satellite {} cannot serve more than {} users ({} assigned)",
+                sat, MAX_ALLOWED_USERS, users
+            ),
+            Self::CoChannel {
+                sat,
+                color,
+                users: (user_1, user_2),
+                angle,
+            } => write!(
+                f,
+                "Users {} and {} on satellite {} {} are too close ({} degrees)",
+                user_1, user_2, sat, color, angle
+            ),
+            Self::LowCoverage {
+                served,
+                users,
+                min_coverage,
+            } => write!(
+                f,
+                "Too few users served ({} of {}, need {}%)",
+                served,
+                users,
+                100.0 * min_coverage
+            ),
+        }
+    }
+}
+
+impl Violation {
+    /// The violation as an object with a snake_case `kind`, its fields, and the
+    /// `Display` text as `message`.
+    pub fn to_json(&self) -> Json {
+        let kind = |kind: &str| Json::object().with("kind", kind);
+        let json = match *self {
+            Self::UnknownUser { user } => kind("unknown_user").with("user", user.0),
+            Self::UnknownSat { user, sat } => {
+                kind("unknown_sat").with("user", user.0).with("sat", sat.0)
+            }
+            Self::NotVisible { user, sat, angle } => kind("not_visible")
+                .with("user", user.0)
+                .with("sat", sat.0)
+                .with("angle", angle),
+            Self::OverCapacity { sat, users } => kind("over_capacity")
+                .with("sat", sat.0)
+                .with("users", users),
+            Self::CoChannel {
+                sat,
+                color,
+                users: (user_1, user_2),
+                angle,
+            } => kind("co_channel")
+                .with("sat", sat.0)
+                .with("color", color as i32)
+                .with("users", vec![user_1.0, user_2.0])
+                .with("angle", angle),
+            Self::LowCoverage {
+                served,
+                users,
+                min_coverage,
+            } => kind("low_coverage")
+                .with("served", served)
+                .with("users", users)
+                .with("min_coverage", min_coverage),
+        };
+        json.with("message", self.to_string())
+    }
+}
+
+/// Every violation in `solution`: assignments first, in user order, then each
+/// satellite's capacity and co-channel pairs, in satellite order, then coverage.
+/// Assignments to unknown users or satellites are left out of the later checks.
+pub fn verify(scenario: &Scenario, solution: &SolutionMap) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let mut beams: BTreeMap<Sat, BTreeMap<Color, Vec<User>>> = BTreeMap::new();
+    let mut served = 0;
+
+    for (user, (sat, color)) in solution {
+        let Some(user_pos) = scenario.users.get(user) else {
+            violations.push(Violation::UnknownUser { user: *user });
+            continue;
+        };
+        let Some(sat_pos) = scenario.sats.get(sat) else {
+            violations.push(Violation::UnknownSat {
+                user: *user,
+                sat: *sat,
+            });
+            continue;
+        };
+        served += 1;
+
+        let angle = user_pos
+            .unit()
+            .dot((sat_pos - user_pos).unit())
+            .clamp(-1.0, 1.0)
+            .acos()
+            .to_degrees();
+        if angle > MAX_ALLOWABLE_BEAM_ANGLE {
+            violations.push(Violation::NotVisible {
+                user: *user,
+                sat: *sat,
+                angle,
+            });
+        }
+        beams
+            .entry(*sat)
+            .or_default()
+            .entry(*color)
+            .or_default()
+            .push(*user);
+    }
+
+    for (sat, colors) in &beams {
+        let sat_pos = &scenario.sats[sat];
+        let users: usize = colors.values().map(Vec::len).sum();
+        if users > MAX_ALLOWED_USERS {
+            violations.push(Violation::OverCapacity { sat: *sat, users });
+        }
+        for (color, same_color) in colors {
+            // Users arrive in id order, so `i < j` gives each pair once, lower id first.
+            for (i, user_1) in same_color.iter().enumerate() {
+                for user_2 in &same_color[i + 1..] {
+                    let angle =
+                        sat_pos.angle_between(&scenario.users[user_1], &scenario.users[user_2]);
+                    if angle < MINIMUM_BEAM_ANGLE {
+                        violations.push(Violation::CoChannel {
+                            sat: *sat,
+                            color: *color,
+                            users: (*user_1, *user_2),
+                            angle,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    if (served as f32) < scenario.min_coverage * scenario.users.len() as f32 {
+        violations.push(Violation::LowCoverage {
+            served,
+            users: scenario.users.len(),
+            min_coverage: scenario.min_coverage,
+        });
+    }
+    violations
+}
+
+/// Same result as `verify`, in O(n log n) and in parallel, for solutions with millions
+/// of users.
+///
+/// Assignments are checked independently and then sorted by satellite. Each
+/// satellite's same-color users are bucketed by the direction the satellite sees them
+/// in: a grid over unit vectors whose cells are as wide as the chord of the minimum
+/// beam angle, so a user can only interfere with users in its own and the 26 adjacent
+/// cells. Valid solutions put few users in each cell; in invalid ones, the extra pairs
+/// are violations that have to be reported anyway.
+pub fn verify_fast(scenario: &Scenario, solution: &SolutionMap) -> Vec<Violation> {
+    let assignments: Vec<(&User, &(Sat, Color))> = solution.iter().collect();
+    // Per user: its violations, and `(sat, color, user)` if both ends exist.
+    let checked: Vec<(Option<Violation>, Option<Beam>)> = assignments
+        .par_iter()
+        .map(|&(user, &(sat, color))| {
+            let Some(user_pos) = scenario.users.get(user) else {
+                return (Some(Violation::UnknownUser { user: *user }), None);
+            };
+            let Some(sat_pos) = scenario.sats.get(&sat) else {
+                return (Some(Violation::UnknownSat { user: *user, sat }), None);
+            };
+            let angle = user_pos
+                .unit()
+                .dot((sat_pos - user_pos).unit())
+                .clamp(-1.0, 1.0)
+                .acos()
+                .to_degrees();
+            let violation = (angle > MAX_ALLOWABLE_BEAM_ANGLE).then_some(Violation::NotVisible {
+                user: *user,
+                sat,
+                angle,
+            });
+            (violation, Some((sat, color, *user)))
+        })
+        .collect();
+
+    let mut violations: Vec<Violation> = checked.iter().filter_map(|(v, _)| v.clone()).collect();
+    let mut beams: Vec<Beam> = checked.iter().filter_map(|(_, b)| *b).collect();
+    let served = beams.len();
+    beams.par_sort_unstable();
+
+    let by_sat: Vec<&[Beam]> = beams.chunk_by(|a, b| a.0 == b.0).collect();
+    let per_sat: Vec<Vec<Violation>> = by_sat
+        .par_iter()
+        .map(|beams| {
+            let sat = beams[0].0;
+            let mut found = Vec::new();
+            if beams.len() > MAX_ALLOWED_USERS {
+                found.push(Violation::OverCapacity {
+                    sat,
+                    users: beams.len(),
+                });
+            }
+            for same_color in beams.chunk_by(|a, b| a.1 == b.1) {
+                co_channel(scenario, sat, same_color, &mut found);
+            }
+            found
+        })
+        .collect();
+    violations.extend(per_sat.into_iter().flatten());
+
+    if (served as f32) < scenario.min_coverage * scenario.users.len() as f32 {
+        violations.push(Violation::LowCoverage {
+            served,
+            users: scenario.users.len(),
+            min_coverage: scenario.min_coverage,
+        });
+    }
+    violations
+}
+
+// Appends the too-close pairs among `beams`, one satellite's users on one color in id
+// order, sorted like `verify` finds them.
+fn co_channel(scenario: &Scenario, sat: Sat, beams: &[Beam], found: &mut Vec<Violation>) {
+    let sat_pos = scenario.sats[&sat];
+    // The same directions `angle_between` computes, so the angles match `verify`'s.
+    let directions: Vec<Vector3> = beams
+        .iter()
+        .map(|(_, _, user)| (scenario.users[user] - sat_pos).unit())
+        .collect();
+    // Directions less than 10° apart are less than 2 sin(5°) apart; the slack covers
+    // rounding in the unit vectors.
+    let cell = 2.0 * (MINIMUM_BEAM_ANGLE / 2.0).to_radians().sin() + 1e-4;
+    let key = |d: &Vector3| d.to_array().map(|c| (c / cell).floor() as i32);
+    let mut cells: Vec<([i32; 3], usize)> = directions
+        .iter()
+        .enumerate()
+        .map(|(i, d)| (key(d), i))
+        .collect();
+    cells.sort_unstable();
+
+    let mut pairs = Vec::new();
+    for (i, direction) in directions.iter().enumerate() {
+        let [x, y, z] = key(direction);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let neighbor = [x + dx, y + dy, z + dz];
+                    let start = cells.partition_point(|(k, _)| *k < neighbor);
+                    for &(_, j) in cells[start..].iter().take_while(|(k, _)| *k == neighbor) {
+                        // Each pair once, from its lower id.
+                        if j <= i {
+                            continue;
+                        }
+                        let angle = direction
+                            .dot(directions[j])
+                            .clamp(-1.0, 1.0)
+                            .acos()
+                            .to_degrees();
+                        if angle < MINIMUM_BEAM_ANGLE {
+                            pairs.push((i, j, angle));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    pairs.sort_unstable_by_key(|&(i, j, _)| (i, j));
+    found.extend(pairs.into_iter().map(|(i, j, angle)| Violation::CoChannel {
+        sat,
+        color: beams[i].1,
+        users: (beams[i].2, beams[j].2),
+        angle,
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_scenario;
+    use crate::solution_v;
+
+    // A satellite 550 km above (6371, 0, 0) and users around the point beneath it.
+    fn scenario() -> Scenario {
+        let mut text = String::from("min_coverage 0\nsat 1 6921 0 0\n");
+        for i in 0..40 {
+            // 0.2° of longitude apart: about 22 km, a little over 2° from the satellite.
+            let longitude = (i as f32 * 0.2).to_radians();
+            text += &format!(
+                "user {} {} {} 0\n",
+                i,
+                6371.0 * longitude.cos(),
+                6371.0 * longitude.sin()
+            );
+        }
+        text += "user 100 -6371 0 0\n";
+        parse_scenario(&text, "verify.txt").unwrap()
+    }
+
+    fn assign(pairs: &[(u64, u64, i32)]) -> SolutionMap {
+        pairs
+            .iter()
+            .map(|&(user, sat, color)| (User(user), (Sat(sat), Color::from_id(color))))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_solution_has_no_violations() {
+        let s = scenario();
+        // 7 users, 0.6° of longitude apart, cycling through colors so same-color users
+        // are 2.4° apart.
+        let solution: Vec<_> = (0..7).map(|i| (i * 3, 1, (i % 4 + 1) as i32)).collect();
+        assert_eq!(verify(&s, &assign(&solution)), vec![]);
+
+        let s = Scenario::new("../test/02_five_users.txt").unwrap();
+        let solution = solution_v::solve(&s.users, &s.sats);
+        assert_eq!(verify(&s, &solution), vec![]);
+    }
+
+    #[test]
+    fn test_unknown_user_and_sat() {
+        let s = scenario();
+        let violations = verify(&s, &assign(&[(0, 2, 1), (500, 1, 1)]));
+        assert_eq!(
+            violations,
+            vec![
+                Violation::UnknownSat {
+                    user: User(0),
+                    sat: Sat(2)
+                },
+                Violation::UnknownUser { user: User(500) },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_not_visible() {
+        let s = scenario();
+        let violations = verify(&s, &assign(&[(100, 1, 1)]));
+        let [Violation::NotVisible { user, sat, angle }] = &violations[..] else {
+            panic!("{:?}", violations);
+        };
+        assert_eq!((*user, *sat), (User(100), Sat(1)));
+        assert!(*angle > 170.0);
+    }
+
+    #[test]
+    fn test_over_capacity() {
+        let s = scenario();
+        // Every 4th user per color keeps same-color users 0.8° of longitude apart,
+        // which is still too close; only count the capacity violation here.
+        let solution: Vec<_> = (0..33).map(|i| (i, 1, (i % 4 + 1) as i32)).collect();
+        let violations = verify(&s, &assign(&solution));
+        assert_eq!(
+            violations
+                .iter()
+                .filter(|v| matches!(v, Violation::OverCapacity { .. }))
+                .collect::<Vec<_>>(),
+            vec![&Violation::OverCapacity {
+                sat: Sat(1),
+                users: 33
+            }]
+        );
+    }
+
+    #[test]
+    fn test_every_co_channel_pair() {
+        let s = scenario();
+        // Users 0, 1 and 2 are all within 10° of each other; user 3 has another color.
+        let violations = verify(&s, &assign(&[(0, 1, 2), (1, 1, 2), (2, 1, 2), (3, 1, 3)]));
+        let pairs: Vec<(User, User)> = violations
+            .iter()
+            .map(|v| match v {
+                Violation::CoChannel {
+                    sat, color, users, ..
+                } => {
+                    assert_eq!((*sat, *color), (Sat(1), Color::B));
+                    *users
+                }
+                _ => panic!("{:?}", v),
+            })
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![(User(0), User(1)), (User(0), User(2)), (User(1), User(2))]
+        );
+        let Violation::CoChannel { angle, .. } = violations[0] else {
+            unreachable!()
+        };
+        assert!(angle > 1.0 && angle < 10.0, "{}", angle);
+        let json = violations[0].to_json().to_string();
+        assert!(
+            json.starts_with(r#"{"kind":"co_channel","sat":1,"color":2,"users":[0,1],"angle":"#),
+            "{}",
+            json
+        );
+        assert!(json.contains(r#","message":"Users 0 and 1 on satellite 1 2 are too close ("#));
+    }
+
+    #[test]
+    fn test_fast_matches_reference() {
+        let s = scenario();
+        let solutions = [
+            assign(&[(0, 2, 1), (500, 1, 1), (100, 1, 1)]),
+            (0..41).map(|i| (User(i), (Sat(1), Color::A))).collect(),
+        ];
+        // Random assignments to a real constellation: most of them break some rule.
+        let big = Scenario::new("../test/04_five_thousand.txt").unwrap();
+        let sats: Vec<Sat> = big.sats.keys().copied().collect();
+        let mut rng = crate::generate::Rng::new(7);
+        let random: SolutionMap = big
+            .users
+            .keys()
+            .map(|user| {
+                let sat = sats[(rng.next_u64() % 20) as usize];
+                (
+                    *user,
+                    (sat, Color::from_id(1 + (rng.next_u64() % 4) as i32)),
+                )
+            })
+            .collect();
+        for (scenario, solution) in [(&s, &solutions[0]), (&s, &solutions[1]), (&big, &random)] {
+            let expected = verify(scenario, solution);
+            assert!(!expected.is_empty());
+            assert_eq!(verify_fast(scenario, solution), expected);
+        }
+
+        let solution = solution_v::solve(&big.users, &big.sats);
+        assert_eq!(verify_fast(&big, &solution), vec![]);
+    }
+
+    #[test]
+    fn test_low_coverage() {
+        let mut s = scenario();
+        s.min_coverage = 0.5;
+        let violations = verify(&s, &assign(&[(0, 1, 1), (9, 2, 1)]));
+        assert_eq!(
+            violations.last(),
+            Some(&Violation::LowCoverage {
+                served: 1,
+                users: 41,
+                min_coverage: 0.5
+            })
+        );
+        assert_eq!(
+            verify_fast(&s, &assign(&[(0, 1, 1), (9, 2, 1)])),
+            violations
+        );
+        assert_eq!(
+            violations.last().unwrap().to_string(),
+            "Too few users served (1 of 41, need 50%)"
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use crate::orbit::{Epoch, Orbit, OrbitError};
+use crate::scenario::Scenario;
+use crate::util::{Sat, User, Vector3, MAX_ALLOWABLE_BEAM_ANGLE};
+use std::collections::BTreeMap;
+
+use rayon::prelude::*;
+
+// Shortest step between samples, in seconds. Passes that stay inside the cone for less
+// than about this long can be missed.
+const MIN_STEP: f64 = 1.0;
+// Rise and set times are refined until the bracket is this short, in seconds.
+const TOLERANCE: f64 = 1e-3;
+
+/// A time interval during which a satellite stays within a user's cone. Windows that
+/// are open at the start or end of the search are cut off there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub rise: Epoch,
+    pub set: Epoch,
+}
+
+impl Window {
+    pub fn duration_seconds(&self) -> f64 {
+        self.set.seconds_since(self.rise)
+    }
+
+    pub fn contains(&self, epoch: Epoch) -> bool {
+        self.rise <= epoch && epoch <= self.set
+    }
+}
+
+// Cosine of the satellite's angle from the user's vertical, minus the cosine of the
+// cone's half angle: positive inside the cone, negative outside.
+struct Margin<'a> {
+    orbit: &'a Orbit,
+    user: [f64; 3],
+    up: [f64; 3],
+    cos_cone: f64,
+}
+
+impl Margin<'_> {
+    fn at(&self, epoch: Epoch) -> Result<f64, OrbitError> {
+        let sat = self.orbit.position_ecef_f64(epoch)?;
+        let d = [
+            sat[0] - self.user[0],
+            sat[1] - self.user[1],
+            sat[2] - self.user[2],
+        ];
+        let range = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+        let cos = (d[0] * self.up[0] + d[1] * self.up[1] + d[2] * self.up[2]) / range;
+        Ok(cos - self.cos_cone)
+    }
+
+    // Shrinks a bracket whose ends have opposite signs around the crossing and returns
+    // the end on the visible side.
+    fn crossing(
+        &self,
+        mut lo: Epoch,
+        mut hi: Epoch,
+        lo_visible: bool,
+    ) -> Result<Epoch, OrbitError> {
+        while hi.seconds_since(lo) > TOLERANCE {
+            let mid = Epoch(0.5 * (lo.0 + hi.0));
+            if (self.at(mid)? >= 0.0) == lo_visible {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        Ok(if lo_visible { lo } else { hi })
+    }
+}
+
+/// Every interval between `start` and `end` during which the satellite on `orbit` is
+/// within 45° of `user`'s vertical, in time order.
+///
+/// The margin `cos(angle from vertical) - cos(45°)` can change no faster than the
+/// satellite's relative speed over its range, so after a sample with margin `m` the
+/// next `|m| / rate` seconds can't cross the cone edge and are skipped. Sign changes
+/// are then narrowed by bisection. Far from the cone this takes steps of minutes, so a
+/// day of a LEO pair needs a few thousand propagations.
+pub fn windows(
+    orbit: &Orbit,
+    user: &Vector3,
+    start: Epoch,
+    end: Epoch,
+) -> Result<Vec<Window>, OrbitError> {
+    let [x, y, z] = user.to_array().map(|c| c as f64);
+    let user_radius = (x * x + y * y + z * z).sqrt();
+    let min_range = orbit.min_radius() - user_radius;
+    if user_radius == 0.0 || min_range <= 0.0 {
+        // No vertical, or a satellite that can dip to the user's radius: the rate bound
+        // below doesn't hold.
+        return Err(OrbitError::InvalidElements(format!(
+            "orbit reaches below the user at radius {} km",
+            user_radius
+        )));
+    }
+    let margin = Margin {
+        orbit,
+        user: [x, y, z],
+        up: [x / user_radius, y / user_radius, z / user_radius],
+        cos_cone: (MAX_ALLOWABLE_BEAM_ANGLE as f64).to_radians().cos(),
+    };
+    let max_rate = orbit.max_speed() / min_range;
+
+    let mut found = Vec::new();
+    let mut t = start;
+    let mut value = margin.at(t)?;
+    let mut rise = (value >= 0.0).then_some(start);
+    while t < end {
+        let step = (value.abs() / max_rate).max(MIN_STEP);
+        let next = if end.seconds_since(t) <= step {
+            end
+        } else {
+            t.plus_seconds(step)
+        };
+        let next_value = margin.at(next)?;
+        match (value >= 0.0, next_value >= 0.0) {
+            (false, true) => rise = Some(margin.crossing(t, next, false)?),
+            (true, false) => {
+                let set = margin.crossing(t, next, true)?;
+                found.push(Window {
+                    rise: rise.take().unwrap(),
+                    set,
+                });
+            }
+            _ => {}
+        }
+        t = next;
+        value = next_value;
+    }
+    if let Some(rise) = rise {
+        found.push(Window { rise, set: end });
+    }
+    Ok(found)
+}
+
+/// `windows` for every user and every satellite with an orbit, keeping only pairs that
+/// see each other at some point. Satellites are searched in parallel.
+pub fn all_windows(
+    scenario: &Scenario,
+    start: Epoch,
+    end: Epoch,
+) -> Result<BTreeMap<(User, Sat), Vec<Window>>, OrbitError> {
+    let orbits: Vec<(&Sat, &Orbit)> = scenario.orbits.iter().collect();
+    let by_sat = orbits
+        .par_iter()
+        .map(|(sat, orbit)| {
+            let mut pairs = Vec::new();
+            for (user, position) in &scenario.users {
+                let found = windows(orbit, position, start, end)?;
+                if !found.is_empty() {
+                    pairs.push(((*user, **sat), found));
+                }
+            }
+            Ok(pairs)
+        })
+        .collect::<Result<Vec<_>, OrbitError>>()?;
+    Ok(by_sat.into_iter().flatten().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orbit::KeplerianElements;
+
+    fn iss() -> Orbit {
+        Orbit::from_tle(
+            "1 25544U 98067A   08264.51782528 -.00002182  00000-0 -11606-4 0  2927",
+            "2 25544  51.6416 247.4627 0006703 130.5360 325.0288 15.72125391563537",
+        )
+        .unwrap()
+    }
+
+    // Same cone test as `possible_connections`, at f32.
+    fn visible(orbit: &Orbit, user: &Vector3, at: Epoch) -> bool {
+        let sat = orbit.position_ecef(at).unwrap();
+        let cos_cone = MAX_ALLOWABLE_BEAM_ANGLE.to_radians().cos();
+        Vector3::zero().within_angle(user, &(sat - *user), cos_cone)
+    }
+
+    #[test]
+    fn test_windows_match_dense_sampling() {
+        let orbit = iss();
+        let start = orbit.epoch();
+        let end = start.plus_seconds(86400.0);
+        // A user under the starting point, a few elsewhere, and one the ground track
+        // never gets near.
+        let users = [
+            orbit.position_ecef(start).unwrap().unit().scale(6371.0),
+            Vector3::new(6371.0, 0.0, 0.0),
+            Vector3::new(0.0, 4504.98, 4504.98),
+            Vector3::new(0.0, 0.0, 6371.0),
+        ];
+        for user in &users {
+            let found = windows(&orbit, user, start, end).unwrap();
+            for pair in found.windows(2) {
+                assert!(pair[0].set < pair[1].rise);
+            }
+            for window in &found {
+                assert!(window.duration_seconds() > 0.0);
+                let middle = Epoch(0.5 * (window.rise.0 + window.set.0));
+                assert!(visible(&orbit, user, middle));
+            }
+            // Every 5 s sample agrees with the windows, away from their edges.
+            for i in 0..(86400 / 5) {
+                let at = start.plus_seconds(i as f64 * 5.0);
+                let near_edge = found.iter().any(|w| {
+                    w.rise.seconds_since(at).abs() < 0.01 || w.set.seconds_since(at).abs() < 0.01
+                });
+                if !near_edge {
+                    assert_eq!(
+                        found.iter().any(|w| w.contains(at)),
+                        visible(&orbit, user, at),
+                        "{:?} at {}",
+                        user,
+                        at
+                    );
+                }
+            }
+        }
+        assert!(!windows(&orbit, &users[0], start, end).unwrap().is_empty());
+        assert!(windows(&orbit, &users[3], start, end).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_windows_clip_to_search_interval() {
+        let epoch = Epoch(2460000.5);
+        // An equatorial orbit that starts straight above the user.
+        let orbit = Orbit::Kepler(KeplerianElements {
+            semi_major_axis: 6921.0,
+            eccentricity: 0.0,
+            inclination: 0.0,
+            raan: 0.0,
+            arg_of_perigee: 0.0,
+            mean_anomaly: 0.0,
+            epoch,
+        });
+        let user = orbit.position_ecef(epoch).unwrap().unit().scale(6371.0);
+        let found = windows(&orbit, &user, epoch, epoch.plus_seconds(60.0)).unwrap();
+        assert_eq!(
+            found,
+            vec![Window {
+                rise: epoch,
+                set: epoch.plus_seconds(60.0)
+            }]
+        );
+
+        let found = windows(&orbit, &user, epoch, epoch.plus_seconds(600.0)).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].rise, epoch);
+        assert!(found[0].set < epoch.plus_seconds(600.0));
+
+        let low = Vector3::new(0.0, 0.0, 7000.0);
+        assert!(windows(&orbit, &low, epoch, epoch.plus_seconds(60.0)).is_err());
+    }
+}
@@ -1,110 +1,233 @@
+use beam_planner::explain::{self, Explanation};
+use beam_planner::generate::{self, Population, Walker, WalkerPattern};
+use beam_planner::json::{self, Value};
+use beam_planner::orbit::Epoch;
+use beam_planner::stats::Stats;
+use beam_planner::{
+    cli, format, solution_v, Color, Format, Sat, Scenario, Solution, User, TIMEOUT,
+};
 use std::process::Command;
+use std::time::Instant;
+
+// What `solve` checks: every rule, the scenario's coverage and the time budget.
+fn solve_and_check(scenario: &Scenario) -> Solution {
+    let start = Instant::now();
+    let solution = solution_v::solve(&scenario.users, &scenario.sats);
+    let duration = start.elapsed();
+    assert!(duration <= TIMEOUT, "took {:?}", duration);
+    assert_eq!(scenario.verify(&solution), vec![]);
+    solution
+}
+
+fn solve_file(path: &str) -> Solution {
+    solve_and_check(&Scenario::new(path).unwrap())
+}
+
+fn run(args: &[&str]) -> i32 {
+    let args: Vec<String> = ["beam_planner"]
+        .iter()
+        .chain(args)
+        .map(|arg| arg.to_string())
+        .collect();
+    cli::run(&args)
+}
 
 #[test]
 fn two_users() {
-    let output = Command::new("cargo")
-        .args(&[
-            "run",
-            "--bin",
-            "beam_planner",
-            "output",
-            "../test/01_two_users.txt",
-        ])
-        .output()
-        .expect("Failed to execute command");
-
-    assert!(output.status.success());
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(stdout.contains("Scenario:"));
-    assert!(stdout.contains("Solution:"));
+    solve_file("../test/01_two_users.txt");
 }
 
 #[test]
 fn five_users() {
-    let output = Command::new("cargo")
-        .args(&[
-            "run",
-            "--bin",
-            "beam_planner",
-            "output",
-            "../test/02_five_users.txt",
-        ])
-        .output()
-        .expect("Failed to execute command");
-
-    assert!(output.status.success());
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(stdout.contains("Scenario:"));
-    assert!(stdout.contains("Solution:"));
+    solve_file("../test/02_five_users.txt");
 }
 
 #[test]
 fn equatorial_band_users() {
-    let output = Command::new("cargo")
-        .args(&[
-            "run",
-            "--bin",
-            "beam_planner",
-            "output",
-            "../test/03_equatorial_band.txt",
-        ])
-        .output()
-        .expect("Failed to execute command");
-
-    assert!(output.status.success());
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(stdout.contains("Scenario:"));
-    assert!(stdout.contains("Solution:"));
+    solve_file("../test/03_equatorial_band.txt");
 }
 
 #[test]
 fn five_thousand_users() {
-    let output = Command::new("cargo")
-        .args(&[
-            "run",
-            "--bin",
-            "beam_planner",
-            "output",
-            "../test/04_five_thousand.txt",
-        ])
-        .output()
-        .expect("Failed to execute command");
-
-    assert!(output.status.success());
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(stdout.contains("Scenario:"));
-    assert!(stdout.contains("Solution:"));
+    solve_file("../test/04_five_thousand.txt");
 }
 
 #[test]
 fn fifty_thousand_users_low_coverage() {
-    let output = Command::new("cargo")
-        .args(&[
-            "run",
-            "--bin",
-            "beam_planner",
-            "output",
-            "../test/05_fifty_thousand_low_coverage.txt",
-        ])
-        .output()
-        .expect("Failed to execute command");
-
-    assert!(output.status.success());
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(stdout.contains("Scenario:"));
-    assert!(stdout.contains("Solution:"));
+    solve_file("../test/05_fifty_thousand_low_coverage.txt");
 }
 
 #[test]
 fn ten_thousand_users() {
-    let output = Command::new("cargo")
-        .args(&[
-            "run",
-            "--bin",
-            "beam_planner",
-            "output",
-            "../test/06_ten_thousand.txt",
-        ])
+    solve_file("../test/06_ten_thousand.txt");
+}
+
+// Prints the median of 5 solve times on tests 05 and 06. Run with
+// `cargo test --release -- --ignored --nocapture solve_timing`. `cargo bench` compares
+// the old and new interference checks on the same scenarios.
+#[test]
+#[ignore]
+fn solve_timing() {
+    for path in [
+        "../test/05_fifty_thousand_low_coverage.txt",
+        "../test/06_ten_thousand.txt",
+    ] {
+        let scenario = Scenario::new(path).unwrap();
+        let mut times: Vec<_> = (0..5)
+            .map(|_| {
+                let start = Instant::now();
+                solution_v::solve(&scenario.users, &scenario.sats);
+                start.elapsed()
+            })
+            .collect();
+        times.sort();
+        println!("{}: {:?}", path, times[2]);
+    }
+}
+
+// 100,000 users spread uniformly under a 72 × 22 Walker shell at 550 km. The shell can
+// serve at most 1584 × 32 users, so about half of them.
+#[test]
+fn one_hundred_thousand_users() {
+    let walker = Walker {
+        pattern: WalkerPattern::Delta,
+        altitude: 550.0,
+        inclination: 53.0,
+        planes: 72,
+        sats_per_plane: 22,
+        phasing: 1,
+        epoch: Epoch::parse("2024-01-01T00:00:00Z").unwrap(),
+        first_id: 1,
+    };
+    let mut scenario = walker.scenario().unwrap();
+    scenario.users = generate::users(&Population::Uniform, 100_000, 11, 1).unwrap();
+    scenario.min_coverage = 0.47;
+    solve_and_check(&scenario);
+}
+
+// Ids don't have to run from 0 or 1 without gaps.
+#[test]
+fn sparse_ids() {
+    let text =
+        "sat 3 6921 0 0\nsat 40 0 6921 0\nuser 7 6371 0 0\nuser 9 6371 100 0\nuser 1000 0 6371 0\n";
+    let scenario = beam_planner::parse::parse_scenario(text, "sparse.txt").unwrap();
+    let solution = solve_and_check(&scenario);
+    assert_eq!(
+        solution.keys().map(|user| user.0).collect::<Vec<_>>(),
+        [7, 9, 1000]
+    );
+    assert_eq!(solution[&User::new(1000)].0, Sat::new(40));
+
+    let path = std::env::temp_dir().join("beam_planner_sparse_ids.txt");
+    std::fs::write(&path, text).unwrap();
+    for solver in ["v", "e"] {
+        assert_eq!(
+            run(&["solve", path.to_str().unwrap(), "--solver", solver]),
+            0
+        );
+    }
+}
+
+#[test]
+fn saved_solution_passes_check() {
+    let path = std::env::temp_dir().join("beam_planner_saved_solution.txt");
+    let path = path.to_str().unwrap();
+    assert_eq!(run(&[path, "../test/02_five_users.txt"]), 0);
+
+    let saved = std::fs::read_to_string(path).unwrap();
+    assert!(saved.starts_with("# ../test/02_five_users.txt 80"));
+    let solution = format::read_solution(path, Format::Text).unwrap();
+    assert_eq!(solution.len(), 4);
+    let scenario = Scenario::new("../test/02_five_users.txt").unwrap();
+    assert_eq!(scenario.verify(&solution), vec![]);
+    assert_eq!(run(&["check", "../test/02_five_users.txt", path]), 0);
+}
+
+#[test]
+fn commands_exit_codes() {
+    for command in ["stats", "compare"] {
+        assert_eq!(run(&[command, "../test/02_five_users.txt", "--json"]), 0);
+    }
+    assert_eq!(run(&["solve", "--seed", "x"]), 2);
+    assert_eq!(run(&["solve", "../test/missing.txt"]), 3);
+
+    let stats = Stats::new(&Scenario::new("../test/02_five_users.txt").unwrap());
+    assert!(stats.to_json().to_string().contains("\"users\":5"));
+}
+
+#[test]
+fn solve_writes_a_run_report() {
+    let path = std::env::temp_dir().join("beam_planner_run_report.json");
+    let path = path.to_str().unwrap();
+    assert_eq!(
+        run(&["solve", "../test/02_five_users.txt", "--report", path]),
+        0
+    );
+
+    let text = std::fs::read_to_string(path).unwrap();
+    let report = json::parse(&text).unwrap();
+    let get = |path: &[&str]| {
+        let mut node = &report;
+        for key in path {
+            node = node.get(key).unwrap_or_else(|| panic!("no {:?}", path));
+        }
+        node.value.clone()
+    };
+    let number = |text: &str| Value::Number(text.to_string());
+    assert_eq!(
+        get(&["schema"]),
+        Value::String("beam_planner/run_report".to_string())
+    );
+    assert_eq!(get(&["version"]), number("1"));
+    assert_eq!(
+        get(&["solver", "name"]),
+        Value::String("v/fewest".to_string())
+    );
+    assert_eq!(get(&["result", "unserved"]), number("1"));
+    assert_eq!(get(&["result", "weighted_coverage"]), number("0.8"));
+    assert_eq!(get(&["violations"]), Value::Array(Vec::new()));
+    for key in ["timings", "spans"] {
+        let Value::Array(items) = get(&[key]) else {
+            panic!("{} is not an array", key)
+        };
+        assert!(!items.is_empty());
+    }
+    assert!(text.contains("{\"name\":\"greedy\","));
+    assert_eq!(
+        run(&[
+            "solve",
+            "../test/02_five_users.txt",
+            "--report",
+            "-",
+            "--json"
+        ]),
+        2
+    );
+}
+
+#[test]
+fn explain_names_the_blocking_users() {
+    let scenario = Scenario::new("../test/02_five_users.txt").unwrap();
+    let solution = solve_file("../test/02_five_users.txt");
+    let Some(Explanation::Blocked { full, blocked }) =
+        explain::explain(&scenario, &solution, User(4))
+    else {
+        panic!("user 4 isn't blocked")
+    };
+    assert!(full.is_empty());
+    let colors: Vec<Color> = blocked[0].blockers.iter().map(|b| b.color).collect();
+    assert_eq!(colors, [Color::A, Color::B, Color::C, Color::D]);
+
+    assert_eq!(run(&["explain", "../test/02_five_users.txt", "4"]), 0);
+    assert_eq!(run(&["explain", "../test/02_five_users.txt", "99"]), 3);
+}
+
+// The binary is only `cli::run`; check it still takes the original arguments.
+#[test]
+fn binary_takes_the_original_arguments() {
+    let path = std::env::temp_dir().join("beam_planner_binary_solution.txt");
+    let output = Command::new(env!("CARGO_BIN_EXE_beam_planner"))
+        .args([path.to_str().unwrap(), "../test/01_two_users.txt"])
         .output()
         .expect("Failed to execute command");
 
@@ -114,21 +237,18 @@ fn ten_thousand_users() {
     assert!(stdout.contains("Solution:"));
 }
 
+// `beam_planner output TEST_CASE`, with a bare file name, is how the harnesses run it.
 #[test]
-fn one_hundred_thousand_users() {
-    let output = Command::new("cargo")
-        .args(&[
-            "run",
-            "--bin",
-            "beam_planner",
-            "output",
-            "../test/11_one_hundred_thousand_users.txt",
-        ])
+fn binary_writes_a_bare_output_path() {
+    let dir = std::env::temp_dir().join("beam_planner_bare_output");
+    std::fs::create_dir_all(&dir).unwrap();
+    let scenario = std::fs::canonicalize("../test/01_two_users.txt").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_beam_planner"))
+        .args(["output", scenario.to_str().unwrap()])
+        .current_dir(&dir)
         .output()
         .expect("Failed to execute command");
 
     assert!(output.status.success());
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(stdout.contains("Scenario:"));
-    assert!(stdout.contains("Solution:"));
+    assert!(dir.join("output").exists());
 }